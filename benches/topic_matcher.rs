@@ -0,0 +1,145 @@
+// paho-mqtt/benches/topic_matcher.rs
+//
+// Benchmarks for the topic matcher collections.
+//
+// This compares lookups in the plain `TopicMatcher`, the same collection
+// behind a `Mutex` (as it's commonly shared between threads), and the
+// lock-free `SharedTopicMatcher`. The "contended" group runs the lookups
+// while another thread continuously updates the collection.
+//
+// Run with:
+//     $ cargo bench --bench topic_matcher
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use paho_mqtt::{SharedTopicMatcher, TopicMatcher};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+// The number of sites in the routing table, each with a few filters.
+const SIZES: &[usize] = &[10, 100, 1000];
+
+// Creates a routing table with a mix of exact and wildcard filters.
+fn routing_table(n: usize) -> TopicMatcher<usize> {
+    let mut matcher = TopicMatcher::new();
+    for i in 0..n {
+        matcher.insert(format!("site/{}/+/temp", i), i);
+        matcher.insert(format!("site/{}/device/+", i), i);
+        matcher.insert(format!("site/{}/#", i), i);
+        matcher.insert(format!("site/{}/device/humidity", i), i);
+    }
+    matcher.insert("+/+/device/temp", n);
+    matcher
+}
+
+fn bench_matches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("matches");
+
+    for &n in SIZES {
+        let topic = format!("site/{}/device/temp", n / 2);
+
+        let plain = routing_table(n);
+        group.bench_with_input(BenchmarkId::new("TopicMatcher", n), &topic, |b, topic| {
+            b.iter(|| plain.matches(black_box(topic)).count())
+        });
+
+        let mutex = Mutex::new(routing_table(n));
+        group.bench_with_input(BenchmarkId::new("Mutex", n), &topic, |b, topic| {
+            b.iter(|| mutex.lock().unwrap().matches(black_box(topic)).count())
+        });
+
+        let shared = SharedTopicMatcher::from(routing_table(n));
+        group.bench_with_input(
+            BenchmarkId::new("SharedTopicMatcher", n),
+            &topic,
+            |b, topic| {
+                b.iter(|| {
+                    let mut count = 0;
+                    shared.for_each_match(black_box(topic), |_, _| count += 1);
+                    count
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+// Runs the closure in a background thread until the returned flag is set.
+fn background<F>(mut f: F) -> (Arc<AtomicBool>, thread::JoinHandle<()>)
+where
+    F: FnMut() + Send + 'static,
+{
+    let done = Arc::new(AtomicBool::new(false));
+    let flag = done.clone();
+    let thr = thread::spawn(move || {
+        while !flag.load(Ordering::Relaxed) {
+            f();
+        }
+    });
+    (done, thr)
+}
+
+fn bench_contended(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contended");
+    let n = 100;
+    let topic = format!("site/{}/device/temp", n / 2);
+
+    let mutex = Arc::new(Mutex::new(routing_table(n)));
+    let writer = mutex.clone();
+    let (done, thr) = background(move || {
+        let mut matcher = writer.lock().unwrap();
+        matcher.insert("extra/topic", 0);
+        matcher.remove("extra/topic");
+    });
+    group.bench_with_input(BenchmarkId::new("Mutex", n), &topic, |b, topic| {
+        b.iter(|| mutex.lock().unwrap().matches(black_box(topic)).count())
+    });
+    done.store(true, Ordering::Relaxed);
+    thr.join().unwrap();
+
+    let shared = Arc::new(SharedTopicMatcher::from(routing_table(n)));
+    let writer = shared.clone();
+    let (done, thr) = background(move || {
+        writer.insert("extra/topic", 0);
+        writer.remove("extra/topic");
+    });
+    group.bench_with_input(
+        BenchmarkId::new("SharedTopicMatcher", n),
+        &topic,
+        |b, topic| {
+            b.iter(|| {
+                let mut count = 0;
+                shared.for_each_match(black_box(topic), |_, _| count += 1);
+                count
+            })
+        },
+    );
+    done.store(true, Ordering::Relaxed);
+    thr.join().unwrap();
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_matches, bench_contended);
+criterion_main!(benches);
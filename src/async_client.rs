@@ -45,21 +45,29 @@
 //! tok.wait().unwrap();
 //! ```
 
+#[cfg(feature = "ssl")]
+use crate::cert_provider::CertReloader;
 use crate::{
     client_persistence::UserPersistence,
     connect_options::ConnectOptions,
-    create_options::{CreateOptions, PersistenceType},
+    create_options::{write_client_id, CreateOptions, PersistenceType},
     disconnect_options::{DisconnectOptions, DisconnectOptionsBuilder},
-    errors::{self, Error, Result},
+    errors::{self, Error, Result, ServerError},
+    failover::{probe_server, Failover, FailoverStrategy, ServerStats},
     ffi,
     message::Message,
-    properties::Properties,
+    presence::PresenceOptions,
+    properties::{PacketType, Properties},
     reason_code::ReasonCode,
+    reconnect::{ExponentialBackoff, ReconnectAttempt, ReconnectPolicy, Reconnector},
+    redirect::{parse_server_reference, RedirectKind, RedirectPolicy, ServerRedirect},
     response_options::{ResponseOptions, ResponseOptionsBuilder},
     server_response::ServerRequest,
+    ssl_options::SslOptions,
     string_collection::StringCollection,
     subscribe_options::SubscribeOptions,
     token::{ConnectToken, DeliveryToken, SubscribeManyToken, SubscribeToken, Token},
+    topic::{discouraged_topic_level, validate_topic_filter},
     types::*,
     AsyncReceiver, Receiver, UserData,
 };
@@ -68,11 +76,13 @@ use std::{
     ffi::{CStr, CString},
     mem,
     os::raw::{c_char, c_int, c_void},
+    path::PathBuf,
     ptr, slice, str,
     sync::{
-        atomic::{AtomicU32, Ordering},
-        Arc, Mutex, Once,
+        atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering},
+        Arc, Mutex, Once, Weak,
     },
+    thread,
     time::Duration,
 };
 
@@ -88,17 +98,52 @@ pub struct AsyncClient {
 /// Implementation details for the asynchronous MQTT connection client.
 pub(crate) struct InnerAsyncClient {
     // The handle to the Paho C client
-    handle: ffi::MQTTAsync,
+    handle: AtomicPtr<c_void>,
+    // Earlier C clients, replaced to connect with an assigned client ID
+    retired_handles: Mutex<Vec<ffi::MQTTAsync>>,
     // The MQTT version of the connection
     mqtt_version: AtomicU32,
     // The options for connecting to the broker
     opts: Mutex<ConnectOptions>,
     // The context to give to the C callbacks
     callback_context: Mutex<CallbackContext>,
+    // The policy for following server redirects, and the count followed
+    redirect: Mutex<RedirectState>,
+    // The policy for reconnecting when the connection is lost, if any
+    reconnector: Arc<Reconnector>,
+    // Whether the policy stands in for the automatic reconnect in the
+    // connect options
+    reconnect_takeover: AtomicBool,
+    // The failover strategy, and the state of the servers
+    failover: Mutex<Failover>,
+    // The error from a DISCONNECT sent by the server, if any
+    disconnect_error: Mutex<Option<ServerError>>,
+    // The birth and death messages, if any
+    presence: Mutex<Option<PresenceOptions>>,
     // The server URI
     server_uri: CString,
     // The MQTT client ID name
     client_id: CString,
+    // The client ID that the C client connects with
+    handle_client_id: Mutex<String>,
+    // The client ID assigned by the server, if any
+    assigned_client_id: Mutex<Option<String>>,
+    // The session expiry interval granted by the server, if any
+    session_expiry: Mutex<Option<Duration>>,
+    // The file to keep the server-assigned client ID, if any
+    client_id_file: Option<PathBuf>,
+    // Whether to check topics before sending them
+    strict_topics: bool,
+    // The reloader for the client certificates, if any
+    #[cfg(feature = "ssl")]
+    cert_reloader: Mutex<Option<CertReloader>>,
+    // The options for creating the C client
+    create_opts: ffi::MQTTAsync_createOptions,
+    // The type of persistence, and its context, for creating the C client
+    persistence_type: c_int,
+    persistence_context: *mut c_void,
+    // The path for file persistence (if any)
+    persistence_path: Option<CString>,
     // The user persistence (if any)
     user_persistence: Option<Box<UserPersistence>>,
     // Arbitrary, user-supplied data
@@ -121,6 +166,12 @@ pub type DisconnectedCallback = dyn FnMut(&AsyncClient, Properties, ReasonCode)
 /// User callback signature for when subscribed messages are received.
 pub type MessageArrivedCallback = dyn FnMut(&AsyncClient, Option<Message>) + Send + 'static;
 
+/// User callback type for when the client follows a server redirect.
+pub type RedirectCallback = dyn FnMut(&AsyncClient, &ServerRedirect) + Send + 'static;
+
+/// User callback type for when the reconnect policy attempts to reconnect.
+pub type ReconnectCallback = dyn FnMut(&AsyncClient, &ReconnectAttempt) + Send + 'static;
+
 // The context provided for the client callbacks.
 //
 // Originally these needed to be kept together and managed with a single
@@ -138,6 +189,19 @@ struct CallbackContext {
     on_disconnected: Option<Box<DisconnectedCallback>>,
     /// Callback for when a message arrives from the server.
     on_message_arrived: Option<Box<MessageArrivedCallback>>,
+    /// Callback for when the client follows a server redirect.
+    on_redirect: Option<Box<RedirectCallback>>,
+    /// Callback for when the reconnect policy attempts to reconnect.
+    on_reconnect: Option<Box<ReconnectCallback>>,
+}
+
+// The state for following server redirects.
+#[derive(Default)]
+struct RedirectState {
+    /// The policy, if redirects are being followed
+    policy: Option<RedirectPolicy>,
+    /// The number of consecutive redirects followed
+    count: u32,
 }
 
 // Runs code to initialize the underlying C library
@@ -166,30 +230,46 @@ impl AsyncClient {
         });
 
         // Create the client
-        let mut opts = opts.into();
+        let opts = opts.into();
         debug!("Create options: {:?}", opts);
 
         let mut cli = InnerAsyncClient {
-            handle: ptr::null_mut(),
+            handle: AtomicPtr::new(ptr::null_mut()),
+            retired_handles: Mutex::new(Vec::new()),
             mqtt_version: AtomicU32::new(MQTT_VERSION_DEFAULT),
             opts: Mutex::new(ConnectOptions::new()),
             callback_context: Mutex::new(CallbackContext::default()),
+            redirect: Mutex::new(RedirectState::default()),
+            reconnector: Arc::new(Reconnector::default()),
+            reconnect_takeover: AtomicBool::new(false),
+            failover: Mutex::new(Failover::default()),
+            disconnect_error: Mutex::new(None),
+            presence: Mutex::new(None),
             server_uri: CString::new(opts.server_uri)?,
+            handle_client_id: Mutex::new(opts.client_id.clone()),
             client_id: CString::new(opts.client_id)?,
+            assigned_client_id: Mutex::new(None),
+            session_expiry: Mutex::new(None),
+            client_id_file: opts.client_id_file,
+            strict_topics: opts.strict_topic_validation,
+            #[cfg(feature = "ssl")]
+            cert_reloader: Mutex::new(None),
+            create_opts: opts.copts,
+            persistence_type: 0,
+            persistence_context: ptr::null_mut(),
+            persistence_path: None,
             user_persistence: None,
             user_data: opts.user_data,
         };
 
-        // We might need this for file persistence path
-        let file_path;
-
         let (ptype, pptr) = match opts.persistence {
             PersistenceType::None => (ffi::MQTTCLIENT_PERSISTENCE_NONE, ptr::null_mut()),
             PersistenceType::File => (ffi::MQTTCLIENT_PERSISTENCE_DEFAULT, ptr::null_mut()),
             PersistenceType::FilePath(path) => {
                 let s = path.to_str().ok_or(errors::Error::PersistenceError)?;
-                file_path = CString::new(s).unwrap_or_default();
+                let file_path = CString::new(s).unwrap_or_default();
                 let pptr = file_path.as_ptr() as *mut c_void;
+                cli.persistence_path = Some(file_path);
                 (ffi::MQTTCLIENT_PERSISTENCE_DEFAULT, pptr)
             }
             PersistenceType::User(cli_persist) => {
@@ -202,30 +282,19 @@ impl AsyncClient {
 
         debug!("Creating client with persistence: {}", ptype);
 
-        let rc = unsafe {
-            ffi::MQTTAsync_createWithOptions(
-                &mut cli.handle as *mut *mut c_void,
-                cli.server_uri.as_ptr(),
-                cli.client_id.as_ptr(),
-                ptype as c_int,
-                pptr,
-                &mut opts.copts,
-            ) as i32
-        };
-
-        if rc != 0 {
-            warn!("Create failure: {}", rc);
-            return Err(rc.into());
-        }
+        cli.persistence_type = ptype as c_int;
+        cli.persistence_context = pptr;
+        *cli.handle.get_mut() = cli.create_handle(&cli.client_id)?;
 
         let cli = AsyncClient {
             inner: Arc::new(cli),
         };
+        cli.register_callbacks(cli.handle());
 
         debug!(
             "AsyncClient w/ Inner {:?} and Handle: {:?}",
             Arc::as_ptr(&cli.inner),
-            cli.inner.handle
+            cli.inner.handle()
         );
         Ok(cli)
     }
@@ -247,17 +316,31 @@ impl AsyncClient {
 
     /// Gets the client "C" handle, normally for diagnostics
     pub(crate) fn handle(&self) -> ffi::MQTTAsync {
-        self.inner.handle
+        self.inner.handle()
     }
 
     // Low-level callback from the C library when the client is connected.
-    // We just pass the call on to the handler registered with the client, if any.
+    // We record the server for failover, and publish the birth message,
+    // if any, then pass the call on to the handler registered with the
+    // client, if any.
     unsafe extern "C" fn on_connected(context: *mut c_void, _cause: *mut c_char) {
         debug!("Connected! Client {:?}", context);
 
         if !context.is_null() {
             let cli = AsyncClient::from_raw(context);
 
+            // Switching to the primary server isn't announced
+            let switching = cli.failover_reconnected();
+            cli.inner.disconnect_error.lock().unwrap().take();
+
+            let presence = cli.inner.presence.lock().unwrap().clone();
+            if let Some(presence) = presence.filter(|_| !switching) {
+                trace!("Publishing the birth message");
+                if let Err(err) = cli.try_publish(presence.online_message(&cli.client_id())) {
+                    warn!("Unable to publish the birth message: {}", err);
+                }
+            }
+
             if let Some(ref mut cb) = cli.inner.callback_context.lock().unwrap().on_connected {
                 trace!("Invoking connected callback");
                 cb(&cli);
@@ -267,6 +350,40 @@ impl AsyncClient {
         }
     }
 
+    // Low-level callback from the C library before each connection
+    // attempt, including automatic reconnects.
+    // We clear the last SSL/TLS error, so that a failed attempt only
+    // reports its own, and reload the client certificates, if they come
+    // from a provider.
+    // This could also update the user name and password in `data`, but
+    // doesn't, so it always returns zero.
+    unsafe extern "C" fn on_update_connect(
+        context: *mut c_void,
+        _data: *mut ffi::MQTTAsync_connectData,
+    ) -> c_int {
+        if !context.is_null() {
+            let cli = AsyncClient::from_raw(context);
+
+            if let Some(ssl) = cli.inner.opts.lock().unwrap().ssl_options() {
+                ssl.clear_last_error();
+            }
+
+            #[cfg(feature = "ssl")]
+            {
+                let reloader = cli.inner.cert_reloader.lock().unwrap().clone();
+                if let Some(reloader) = reloader {
+                    trace!("Reloading client certificates");
+                    if let Err(err) = reloader.reload() {
+                        warn!("Error reloading client certificates: {}", err);
+                    }
+                }
+            }
+
+            let _ = cli.into_raw();
+        }
+        0
+    }
+
     // Low-level callback from the C library when the connection is lost.
     // We pass the call on to the handler registered with the client, if any.
     unsafe extern "C" fn on_connection_lost(context: *mut c_void, _cause: *mut c_char) {
@@ -289,6 +406,11 @@ impl AsyncClient {
                     cb(&cli);
                 }
             }
+            cli.inner.failover.lock().unwrap().disconnected();
+
+            // The reason is the server's, if it sent a DISCONNECT
+            let reason = cli.disconnect_error();
+            cli.start_reconnect(reason.map_or(Error::Disconnected, Error::Server));
             let _ = cli.into_raw();
         }
     }
@@ -323,6 +445,21 @@ impl AsyncClient {
                     cb(&cli, props, reason_code);
                 }
             }
+
+            let reason_code = ReasonCode::from(reason);
+            if reason_code.is_err() {
+                let props = if cprops.is_null() {
+                    Properties::new()
+                }
+                else {
+                    Properties::from_c_struct(&*cprops)
+                };
+                let err = ServerError::from_properties(PacketType::Disconnect, reason_code, &props);
+                if RedirectKind::from_reason_code(reason_code).is_some() && !cprops.is_null() {
+                    cli.follow_redirect(&err, None);
+                }
+                *cli.inner.disconnect_error.lock().unwrap() = Some(err);
+            }
             let _ = cli.into_raw();
         }
     }
@@ -378,17 +515,17 @@ impl AsyncClient {
 
     // Set the disconnection callbacks, usually to prepare for creating
     // an input channel/stream of messages.
-    fn set_disconnection_callbacks(&self) {
+    pub(crate) fn set_disconnection_callbacks(&self) {
         let inner: &InnerAsyncClient = &self.inner;
 
         unsafe {
             ffi::MQTTAsync_setConnectionLostCallback(
-                inner.handle,
+                inner.handle(),
                 inner as *const _ as *mut c_void,
                 Some(AsyncClient::on_connection_lost),
             );
             ffi::MQTTAsync_setDisconnected(
-                inner.handle,
+                inner.handle(),
                 inner as *const _ as *mut c_void,
                 Some(AsyncClient::on_disconnected),
             );
@@ -443,19 +580,30 @@ impl AsyncClient {
     where
         T: Into<Option<ConnectOptions>>,
     {
-        debug!("Connecting. Handle: {:?}", self.inner.handle);
+        debug!("Connecting. Handle: {:?}", self.inner.handle());
 
         let mut opts = opts.into().unwrap_or_default();
+        if let Err(err) = opts.validate_properties() {
+            return ConnectToken::from_err(err);
+        }
         self.set_mqtt_version(opts.mqtt_version());
+        self.apply_reconnect(&mut opts);
+        self.apply_client_id(&opts);
+        self.apply_failover(&mut opts);
+        if let Err(err) = self.apply_presence(&mut opts) {
+            return ConnectToken::from_err(err);
+        }
 
         let tok = Token::from_request(self, ServerRequest::Connect);
         opts.set_token(tok.clone());
 
         debug!("Connect options: {:?}", opts);
+        self.prepare_ssl(opts.ssl_options());
+
         let mut lkopts = self.inner.opts.lock().unwrap();
         *lkopts = opts;
 
-        let rc = unsafe { ffi::MQTTAsync_connect(self.inner.handle, &lkopts.copts) };
+        let rc = unsafe { ffi::MQTTAsync_connect(self.inner.handle(), &lkopts.copts) };
 
         if rc != 0 {
             mem::drop(unsafe { Token::from_raw(lkopts.copts.context) });
@@ -484,7 +632,7 @@ impl AsyncClient {
     {
         debug!(
             "Connecting with callbacks. Handle: {:?}, opts: {:?}",
-            self.inner.handle, opts
+            self.inner.handle(), opts
         );
         unsafe {
             if !opts.copts.will.is_null() {
@@ -492,15 +640,26 @@ impl AsyncClient {
             }
         }
 
+        if let Err(err) = opts.validate_properties() {
+            return ConnectToken::from_err(err);
+        }
         self.set_mqtt_version(opts.mqtt_version());
+        self.apply_reconnect(&mut opts);
+        self.apply_client_id(&opts);
+        self.apply_failover(&mut opts);
+        if let Err(err) = self.apply_presence(&mut opts) {
+            return ConnectToken::from_err(err);
+        }
 
         let tok = Token::from_client(self, ServerRequest::Connect, success_cb, failure_cb);
         opts.set_token(tok.clone());
 
+        self.prepare_ssl(opts.ssl_options());
+
         let mut lkopts = self.inner.opts.lock().unwrap();
         *lkopts = opts;
 
-        let rc = unsafe { ffi::MQTTAsync_connect(self.inner.handle, &lkopts.copts) };
+        let rc = unsafe { ffi::MQTTAsync_connect(self.inner.handle(), &lkopts.copts) };
 
         if rc != 0 {
             mem::drop(unsafe { Token::from_raw(lkopts.copts.context) });
@@ -515,8 +674,7 @@ impl AsyncClient {
     /// attempted. It will retry with the same connect options.
     ///
     pub fn reconnect(&self) -> ConnectToken {
-        let connopts = self.inner.opts.lock().unwrap().clone();
-        self.connect(connopts)
+        self.connect(self.last_connect_options())
     }
 
     /// Attempts to reconnect to the broker, using callbacks to signal
@@ -534,8 +692,18 @@ impl AsyncClient {
         FS: Fn(&AsyncClient, u16) + Send + 'static,
         FF: Fn(&AsyncClient, u16, i32) + Send + 'static,
     {
-        let connopts = self.inner.opts.lock().unwrap().clone();
-        self.connect_with_callbacks(connopts, success_cb, failure_cb)
+        self.connect_with_callbacks(self.last_connect_options(), success_cb, failure_cb)
+    }
+
+    // Gets the options from the last connect, to connect again. An
+    // automatic reconnect that a reconnect policy stands in for is turned
+    // back on, so that the policy stays in place.
+    fn last_connect_options(&self) -> ConnectOptions {
+        let mut opts = self.inner.opts.lock().unwrap().clone();
+        if self.inner.reconnect_takeover.load(Ordering::SeqCst) {
+            opts.copts.automaticReconnect = 1;
+        }
+        opts
     }
 
     /// Disconnects from the MQTT broker.
@@ -550,13 +718,35 @@ impl AsyncClient {
         T: Into<Option<DisconnectOptions>>,
     {
         let mut opts = opt_opts.into().unwrap_or_default();
-        debug!("Disconnecting.  Handle: {:?}", self.inner.handle);
+        if let Err(err) = opts.properties().validate(PacketType::Disconnect) {
+            return Token::from_err(err);
+        }
+        if let Err(err) = self.check_session_expiry(&opts) {
+            return Token::from_err(err);
+        }
+        debug!("Disconnecting.  Handle: {:?}", self.inner.handle());
         trace!("Disconnect options: {:?}", opts);
 
+        // A normal disconnect makes the server discard the will
+        if let Some(presence) = self.presence() {
+            if self.is_connected() {
+                self.send_death_message(&presence, &mut opts);
+            }
+        }
+
+        // Don't let the reconnect policy or a switch back to the primary
+        // server undo a requested disconnect
+        self.inner.reconnector.cancel();
+        {
+            let mut failover = self.inner.failover.lock().unwrap();
+            failover.stop_probe();
+            failover.disconnected();
+        }
+
         let tok = Token::new();
         opts.set_token(tok.clone());
 
-        let rc = unsafe { ffi::MQTTAsync_disconnect(self.inner.handle, &opts.copts) };
+        let rc = unsafe { ffi::MQTTAsync_disconnect(self.inner.handle(), &opts.copts) };
 
         if rc != 0 {
             mem::drop(unsafe { Token::from_raw(opts.copts.context) });
@@ -596,7 +786,7 @@ impl AsyncClient {
 
     /// Determines if this client is currently connected to an MQTT broker.
     pub fn is_connected(&self) -> bool {
-        unsafe { ffi::MQTTAsync_isConnected(self.inner.handle) != 0 }
+        unsafe { ffi::MQTTAsync_isConnected(self.inner.handle()) != 0 }
     }
 
     /// Sets the callback for when the connection is established with the broker.
@@ -609,28 +799,13 @@ impl AsyncClient {
     where
         F: FnMut(&AsyncClient) + Send + 'static,
     {
-        // A pointer to the inner client will serve as the callback context
-        let inner: &InnerAsyncClient = &self.inner;
-
-        // This should be protected by a mutex if we'll have a thread-safe client
-        inner.callback_context.lock().unwrap().on_connected = Some(Box::new(cb));
-
-        unsafe {
-            ffi::MQTTAsync_setConnected(
-                inner.handle,
-                inner as *const _ as *mut c_void,
-                Some(AsyncClient::on_connected),
-            );
-        }
+        // The C callback is always registered, to keep track of the server
+        self.inner.callback_context.lock().unwrap().on_connected = Some(Box::new(cb));
     }
 
     /// Removes the callback for when the conection is established
     pub fn remove_connected_callback(&self) {
         self.inner.callback_context.lock().unwrap().on_connected = None;
-
-        unsafe {
-            ffi::MQTTAsync_setConnected(self.inner.handle, ptr::null_mut(), None);
-        }
     }
 
     /// Sets the callback for when the connection is lost with the broker.
@@ -650,7 +825,7 @@ impl AsyncClient {
 
         unsafe {
             ffi::MQTTAsync_setConnectionLostCallback(
-                inner.handle,
+                inner.handle(),
                 inner as *const _ as *mut c_void,
                 Some(AsyncClient::on_connection_lost),
             );
@@ -671,7 +846,7 @@ impl AsyncClient {
         /*
         unsafe {
             ffi::MQTTAsync_setConnectionLostCallback(
-                self.inner.handle,
+                self.inner.handle(),
                 ptr::null_mut(),
                 None
             );
@@ -697,7 +872,7 @@ impl AsyncClient {
 
         unsafe {
             ffi::MQTTAsync_setDisconnected(
-                inner.handle,
+                inner.handle(),
                 inner as *const _ as *mut c_void,
                 Some(AsyncClient::on_disconnected),
             );
@@ -708,11 +883,592 @@ impl AsyncClient {
     pub fn remove_disconnected_callback(&self) {
         self.inner.callback_context.lock().unwrap().on_disconnected = None;
 
+        // Redirects still need to see the disconnect packets
+        if self.inner.redirect.lock().unwrap().policy.is_none() {
+            unsafe {
+                ffi::MQTTAsync_setDisconnected(self.inner.handle(), ptr::null_mut(), None);
+            }
+        }
+    }
+
+    /// Sets the policy for following MQTT v5 server redirects.
+    ///
+    /// With a policy set, when the server rejects a connection or
+    /// disconnects the client with a reason code of _Use Another Server_ or
+    /// _Server Moved_ and a _Server Reference_ property, the client
+    /// reconnects to the referenced server.
+    ///
+    /// When a connection attempt is redirected, the token for the original
+    /// connect request completes with the result of connecting to the new
+    /// server. When the server disconnects the client, the message stream
+    /// is still sent a `None`, as for any disconnect, before the client
+    /// reconnects.
+    ///
+    /// Once the number of consecutive redirects reaches the limit in the
+    /// policy, the redirect is not followed, and the connect token fails
+    /// with the [`ServerError`] from the server.
+    pub fn set_redirect_policy(&self, policy: RedirectPolicy) {
+        let mut redirect = self.inner.redirect.lock().unwrap();
+        redirect.policy = Some(policy);
+        redirect.count = 0;
+
+        let inner: &InnerAsyncClient = &self.inner;
+        unsafe {
+            ffi::MQTTAsync_setDisconnected(
+                inner.handle(),
+                inner as *const _ as *mut c_void,
+                Some(AsyncClient::on_disconnected),
+            );
+        }
+    }
+
+    /// Stops following server redirects.
+    pub fn remove_redirect_policy(&self) {
+        self.inner.redirect.lock().unwrap().policy = None;
+    }
+
+    /// Gets the policy for following server redirects, if one is set.
+    pub fn redirect_policy(&self) -> Option<RedirectPolicy> {
+        self.inner.redirect.lock().unwrap().policy
+    }
+
+    /// Sets the callback for when the client follows a server redirect.
+    ///
+    /// This is called just before the client connects to the new server.
+    ///
+    /// # Arguments
+    ///
+    /// * `cb` The callback to register with the library. This can be a
+    ///   function or a closure.
+    pub fn set_redirect_callback<F>(&self, cb: F)
+    where
+        F: FnMut(&AsyncClient, &ServerRedirect) + Send + 'static,
+    {
+        self.inner.callback_context.lock().unwrap().on_redirect = Some(Box::new(cb));
+    }
+
+    /// Removes the callback for when the client follows a server redirect.
+    pub fn remove_redirect_callback(&self) {
+        self.inner.callback_context.lock().unwrap().on_redirect = None;
+    }
+
+    // Attempts to follow a server redirect, if there's a policy that
+    // allows it. When a connect token is given, it is reused for the
+    // connection to the new server. Returns true if the connection to the
+    // new server was started.
+    pub(crate) fn follow_redirect(&self, err: &ServerError, tok: Option<ConnectToken>) -> bool {
+        let kind = match RedirectKind::from_reason_code(err.reason_code()) {
+            Some(kind) => kind,
+            None => return false,
+        };
+        let reference = match err.server_reference() {
+            Some(reference) => reference,
+            None => return false,
+        };
+
+        let count = {
+            let mut redirect = self.inner.redirect.lock().unwrap();
+            let policy = match redirect.policy {
+                Some(policy) if policy.follows(kind) => policy,
+                _ => return false,
+            };
+            if redirect.count >= policy.max_redirects() {
+                warn!("Not following redirect to '{}'. Too many redirects", reference);
+                return false;
+            }
+            redirect.count += 1;
+            redirect.count
+        };
+
+        let mut opts = self.inner.opts.lock().unwrap().clone();
+        let cur_uri = opts
+            .server_uris()
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| self.server_uri());
+
+        let server_uris = parse_server_reference(reference, &cur_uri);
+        if server_uris.is_empty() {
+            return false;
+        }
+        opts.set_server_uris(&server_uris);
+
+        let redirect = ServerRedirect {
+            kind,
+            packet_type: err.packet_type(),
+            server_reference: reference.to_string(),
+            server_uris,
+            count,
+        };
+        debug!("Following server redirect: {:?}", redirect);
+
+        if let Some(ref mut cb) = self.inner.callback_context.lock().unwrap().on_redirect {
+            trace!("Invoking redirect callback");
+            cb(self, &redirect);
+        }
+
+        let tok = tok.unwrap_or_else(|| Token::from_request(self, ServerRequest::Connect));
+        opts.set_token(tok);
+
+        // A permanent redirect replaces the options used to reconnect.
+        let rc = if kind == RedirectKind::Permanent {
+            let mut lkopts = self.inner.opts.lock().unwrap();
+            *lkopts = opts;
+            let rc = unsafe { ffi::MQTTAsync_connect(self.inner.handle(), &lkopts.copts) };
+            if rc != 0 {
+                mem::drop(unsafe { Token::from_raw(lkopts.copts.context) });
+            }
+            rc
+        }
+        else {
+            let rc = unsafe { ffi::MQTTAsync_connect(self.inner.handle(), &opts.copts) };
+            if rc != 0 {
+                mem::drop(unsafe { Token::from_raw(opts.copts.context) });
+            }
+            rc
+        };
+
+        if rc != 0 {
+            warn!("Failed to follow redirect: {}", Error::from(rc));
+        }
+        rc == 0
+    }
+
+    /// Sets the policy for reconnecting to the server when the connection
+    /// is lost.
+    ///
+    /// When the connection is lost, the client asks the policy for the time
+    /// to wait before each attempt, then calls
+    /// [`reconnect()`](Self::reconnect), until it connects, the policy
+    /// gives up, or the application calls [`disconnect()`](Self::disconnect).
+    /// The attempts are made from a separate thread, and each one is
+    /// reported to the callback set with
+    /// [`set_reconnect_callback()`](Self::set_reconnect_callback).
+    ///
+    /// This replaces the automatic reconnect in the connect options, which
+    /// should not be turned on as well. If it is, the policy is not used.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` The policy, like an `ExponentialBackoff`, or a closure
+    ///   taking the attempt number and the reason the last attempt
+    ///   failed, and returning the delay before the next one.
+    pub fn set_reconnect_policy<P>(&self, policy: P)
+    where
+        P: ReconnectPolicy,
+    {
+        self.inner.reconnector.set_policy(Some(Box::new(policy)));
+        self.inner.reconnect_takeover.store(false, Ordering::SeqCst);
+
+        let inner: &InnerAsyncClient = &self.inner;
+        unsafe {
+            ffi::MQTTAsync_setConnectionLostCallback(
+                inner.handle(),
+                inner as *const _ as *mut c_void,
+                Some(AsyncClient::on_connection_lost),
+            );
+        }
+    }
+
+    /// Removes the reconnect policy, stopping any reconnect in progress.
+    pub fn remove_reconnect_policy(&self) {
+        self.inner.reconnector.set_policy(None);
+        self.inner.reconnect_takeover.store(false, Ordering::SeqCst);
+    }
+
+    /// Sets the callback for when the reconnect policy attempts to
+    /// reconnect.
+    ///
+    /// This is called from the reconnect thread just before each attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `cb` The callback to register with the library. This can be a
+    ///   function or a closure.
+    pub fn set_reconnect_callback<F>(&self, cb: F)
+    where
+        F: FnMut(&AsyncClient, &ReconnectAttempt) + Send + 'static,
+    {
+        self.inner.callback_context.lock().unwrap().on_reconnect = Some(Box::new(cb));
+    }
+
+    /// Removes the callback for when the reconnect policy attempts to
+    /// reconnect.
+    pub fn remove_reconnect_callback(&self) {
+        self.inner.callback_context.lock().unwrap().on_reconnect = None;
+    }
+
+    // Starts reconnecting with the policy, if there is one and it isn't
+    // already running. The attempts are made from a new thread, so that
+    // it can wait out the delays between them. The reason is why the
+    // connection was lost.
+    fn start_reconnect(&self, reason: Error) {
+        if self.inner.opts.lock().unwrap().copts.automaticReconnect != 0 {
+            if self.inner.reconnector.has_policy() {
+                warn!("Not using the reconnect policy while automatic reconnect is on");
+            }
+            return;
+        }
+
+        let reconnector = self.inner.reconnector.clone();
+        let gen = match reconnector.start() {
+            Some(gen) => gen,
+            None => return,
+        };
+
+        // The thread only holds a weak reference to the client between
+        // attempts, so that it doesn't keep a dropped client alive.
+        let inner = Arc::downgrade(&self.inner);
+        let res = thread::Builder::new()
+            .name("paho-reconnect".into())
+            .spawn(move || Self::run_reconnect(inner, reconnector, gen, reason));
+
+        if let Err(err) = res {
+            warn!("Unable to start the reconnect thread: {}", err);
+            self.inner.reconnector.finish(gen);
+        }
+    }
+
+    // Makes the attempts to reconnect, as long as the policy allows.
+    fn run_reconnect(
+        inner: Weak<InnerAsyncClient>,
+        reconnector: Arc<Reconnector>,
+        gen: u64,
+        mut reason: Error,
+    ) {
+        for attempt in 1.. {
+            let delay = match reconnector.wait(gen, attempt, &reason) {
+                Some(delay) => delay,
+                None => return,
+            };
+            let cli = match inner.upgrade() {
+                Some(inner) => AsyncClient { inner },
+                None => return,
+            };
+            if cli.is_connected() {
+                break;
+            }
+
+            let event = ReconnectAttempt {
+                attempt,
+                delay,
+                reason,
+            };
+            debug!("Reconnect attempt {} after {:?}", attempt, delay);
+
+            if let Some(ref mut cb) = cli.inner.callback_context.lock().unwrap().on_reconnect {
+                trace!("Invoking reconnect callback");
+                cb(&cli, &event);
+            }
+
+            match cli.reconnect().wait() {
+                Ok(_) => break,
+                Err(err) => {
+                    debug!("Reconnect attempt {} failed: {}", attempt, err);
+                    reason = err;
+                }
+            }
+        }
+        reconnector.finish(gen);
+    }
+
+    /// Sets the strategy for choosing among the servers in the connect
+    /// options.
+    ///
+    /// The strategy orders the servers before each call to
+    /// [`connect()`](Self::connect) or [`reconnect()`](Self::reconnect),
+    /// including the ones made by the reconnect policy. With any strategy
+    /// other than the default, [`FailoverStrategy::Ordered`], an automatic
+    /// reconnect in the connect options is done with a [`ReconnectPolicy`]
+    /// with the same delays, rather than by the C library, so that each
+    /// attempt is ordered by the strategy.
+    pub fn set_failover_strategy(&self, strategy: FailoverStrategy) {
+        self.inner.failover.lock().unwrap().set_strategy(strategy);
+    }
+
+    /// Gets the strategy for choosing among the servers.
+    pub fn failover_strategy(&self) -> FailoverStrategy {
+        self.inner.failover.lock().unwrap().strategy()
+    }
+
+    /// Gets the URI of the server that the client is connected to, if
+    /// any.
+    ///
+    /// This is the server reported in the [`ConnectResponse`](crate::ConnectResponse)
+    /// from the last successful call to [`connect()`](Self::connect) or
+    /// [`reconnect()`](Self::reconnect). After an automatic reconnect by
+    /// the C library, which doesn't report the server, this is only known
+    /// if there is a single server.
+    pub fn current_server(&self) -> Option<String> {
+        self.inner
+            .failover
+            .lock()
+            .unwrap()
+            .current()
+            .map(String::from)
+    }
+
+    /// Gets the connection statistics for each server that the client has
+    /// tried.
+    pub fn server_stats(&self) -> Vec<ServerStats> {
+        self.inner.failover.lock().unwrap().stats()
+    }
+
+    /// Sets the options to announce whether the client is online, with
+    /// birth and death messages.
+    ///
+    /// Each connect sets the death message as the will in the connect
+    /// options, replacing any will that they have. The birth message is
+    /// published each time the client connects, including automatic
+    /// reconnects, and the death message is sent by
+    /// [`disconnect()`](Self::disconnect).
+    ///
+    /// A `{client_id}` field in the topic or payloads needs a client ID:
+    /// either the one the client was created with, or one that the server
+    /// assigned earlier. Otherwise [`connect()`](Self::connect) fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `presence` The topic and messages announcing the client's status.
+    pub fn set_presence(&self, presence: PresenceOptions) {
+        *self.inner.presence.lock().unwrap() = Some(presence);
+    }
+
+    /// Gets the options to announce whether the client is online, if any.
+    pub fn presence(&self) -> Option<PresenceOptions> {
+        self.inner.presence.lock().unwrap().clone()
+    }
+
+    /// Removes the options to announce whether the client is online.
+    ///
+    /// This takes effect for the will on the next call to
+    /// [`connect()`](Self::connect).
+    pub fn remove_presence(&self) {
+        *self.inner.presence.lock().unwrap() = None;
+    }
+
+    // Sets the death message from the presence options, if any, as the
+    // will in the connect options.
+    fn apply_presence(&self, opts: &mut ConnectOptions) -> Result<()> {
+        if let Some(presence) = self.presence() {
+            let client_id = self.client_id();
+            if client_id.is_empty() && presence.uses_client_id() {
+                return Err(Error::General(
+                    "Presence messages with a {client_id} field need a client ID",
+                ));
+            }
+            opts.set_will(presence.will_options(&client_id));
+        }
+        Ok(())
+    }
+
+    // Gets the death message out when disconnecting cleanly. With MQTT v5
+    // the server can publish the will, which is the death message, unless
+    // the will delay would hold it back. Otherwise the client publishes
+    // it ahead of the disconnect, which waits up to its timeout for
+    // messages in flight, so this doesn't block.
+    fn send_death_message(&self, presence: &PresenceOptions, opts: &mut DisconnectOptions) {
+        if self.mqtt_version_raw() >= MQTT_VERSION_5
+            && opts.reason_code() == ReasonCode::Success
+            && !presence.has_will_delay()
+        {
+            trace!("Disconnecting with the death message as the will");
+            opts.copts.reasonCode = ReasonCode::DisconnectWithWillMessage as ffi::MQTTReasonCodes;
+            return;
+        }
+
+        trace!("Publishing the death message");
+        if let Err(err) = self.try_publish(presence.offline_message(&self.client_id())) {
+            warn!("Unable to publish the death message: {}", err);
+        }
+    }
+
+    // Orders the servers in the connect options with the failover
+    // strategy. Other than for the default, reconnects have to come
+    // through here to be ordered and recorded, so they are made with a
+    // reconnect policy rather than by the C library.
+    fn apply_failover(&self, opts: &mut ConnectOptions) {
+        let uris = opts.server_uris();
+        let order = self.inner.failover.lock().unwrap().order(uris.clone());
+        if order != uris {
+            debug!("Trying servers in order: {:?}", order);
+            opts.set_server_uris(&order);
+        }
+    }
+
+    // Records a successful connection to the server, and starts checking
+    // for the primary server, if the strategy wants to get back to it.
+    pub(crate) fn failover_connected(&self, uri: &str) {
+        let (primary, interval, gen) = {
+            let mut failover = self.inner.failover.lock().unwrap();
+            failover.connected(uri);
+            match failover.start_probe() {
+                Some(target) => target,
+                None => return,
+            }
+        };
+
+        let inner = Arc::downgrade(&self.inner);
+        let res = thread::Builder::new()
+            .name("paho-failover".into())
+            .spawn(move || Self::run_probe(inner, primary, interval, gen));
+
+        if let Err(err) = res {
+            warn!("Unable to start the failover thread: {}", err);
+            self.inner.failover.lock().unwrap().stop_probe();
+        }
+    }
+
+    // Records a connection to the server when it didn't come through a
+    // connect token, like an automatic reconnect by the C library. That
+    // only reports the server if there's just the one. Returns whether
+    // the connection was made to switch back to the primary server.
+    fn failover_reconnected(&self) -> bool {
+        let switching = {
+            let mut failover = self.inner.failover.lock().unwrap();
+            let switching = failover.take_switching();
+            if failover.current().is_some() {
+                return switching;
+            }
+            switching
+        };
+
+        let uris = self.inner.opts.lock().unwrap().server_uris();
+        match uris.len() {
+            0 => self.failover_connected(&self.server_uri()),
+            1 => self.failover_connected(&uris[0]),
+            _ => debug!("Reconnected to one of the servers: {:?}", uris),
+        }
+        switching
+    }
+
+    // Records a failed connection attempt.
+    pub(crate) fn failover_failed(&self) {
+        let uri = self.server_uri();
+        self.inner.failover.lock().unwrap().failed(&uri);
+    }
+
+    // Checks the primary server at the interval while the client is
+    // connected to another one, and reconnects once it can be reached.
+    // The thread quits once the probe of its generation is stopped, as
+    // by a disconnect(), or the client is no longer connected to another
+    // server. Since the check itself can take a while, this is checked
+    // again before switching.
+    fn run_probe(inner: Weak<InnerAsyncClient>, primary: String, interval: Duration, gen: u64) {
+        loop {
+            thread::sleep(interval);
+
+            let cli = match inner.upgrade() {
+                Some(inner) => AsyncClient { inner },
+                None => return,
+            };
+
+            if !cli.inner.failover.lock().unwrap().keep_probing(gen, &primary) {
+                return;
+            }
+
+            let timeout = interval.min(Duration::from_secs(10));
+            if probe_server(&primary, timeout) {
+                if !cli.inner.failover.lock().unwrap().start_switch(gen, &primary) {
+                    return;
+                }
+                info!("Primary server {} is reachable. Reconnecting to it.", primary);
+                cli.switch_server(timeout);
+                return;
+            }
+        }
+    }
+
+    // Disconnects from the backup server, then reconnects to get back to
+    // the primary. The switch must already be started in the failover
+    // state, and it's abandoned if a disconnect() stops it before the
+    // reconnect.
+    //
+    // Unlike disconnect(), this leaves the reconnect policy in place,
+    // doesn't send a None to the message streams, and doesn't publish
+    // any presence messages. The disconnect waits for messages in flight
+    // to complete, and the session is kept for the reconnect.
+    fn switch_server(&self, timeout: Duration) {
+        let mut opts = DisconnectOptionsBuilder::new().timeout(timeout).finalize();
+        let tok = Token::new();
+        opts.set_token(tok.clone());
+
+        let rc = unsafe { ffi::MQTTAsync_disconnect(self.inner.handle(), &opts.copts) };
+        if rc != 0 {
+            mem::drop(unsafe { Token::from_raw(opts.copts.context) });
+            warn!("Unable to disconnect from the server: {}", Error::from(rc));
+        }
+        else if let Err(err) = tok.wait_for(timeout + Duration::from_secs(1)) {
+            warn!("Error disconnecting from the server: {}", err);
+        }
+
+        {
+            let mut failover = self.inner.failover.lock().unwrap();
+            if !failover.is_switching() {
+                debug!("The switch to the primary server was stopped");
+                return;
+            }
+            failover.disconnected();
+        }
+
+        if let Err(err) = self.reconnect().wait() {
+            warn!("Unable to reconnect: {}", err);
+            self.inner.failover.lock().unwrap().take_switching();
+            self.start_reconnect(err);
+        }
+    }
+
+    // Gets ready to connect with the SSL/TLS options, if any, clearing the
+    // last error, and setting the reloader for the client certificates.
+    // The C callback that does both before each connection attempt is
+    // registered when there are options.
+    fn prepare_ssl(&self, ssl: Option<&SslOptions>) {
+        let inner: &InnerAsyncClient = &self.inner;
+        if let Some(ssl) = ssl {
+            ssl.clear_last_error();
+        }
+        #[cfg(feature = "ssl")]
+        {
+            *inner.cert_reloader.lock().unwrap() = ssl.and_then(|ssl| ssl.cert_reloader());
+        }
+
         unsafe {
-            ffi::MQTTAsync_setDisconnected(self.inner.handle, ptr::null_mut(), None);
+            if ssl.is_some() {
+                ffi::MQTTAsync_setUpdateConnectOptions(
+                    inner.handle(),
+                    inner as *const _ as *mut c_void,
+                    Some(AsyncClient::on_update_connect),
+                );
+            }
+            else {
+                ffi::MQTTAsync_setUpdateConnectOptions(inner.handle(), ptr::null_mut(), None);
+            }
         }
     }
 
+    // Attaches the last error from the SSL/TLS library, if any, to the
+    // error from a failed connection.
+    pub(crate) fn with_ssl_error(&self, err: Error) -> Error {
+        let ssl_err = self
+            .inner
+            .opts
+            .lock()
+            .unwrap()
+            .ssl_options()
+            .and_then(|ssl| ssl.last_error());
+
+        match ssl_err {
+            Some(msg) => Error::Ssl(Box::new(err), msg),
+            None => err,
+        }
+    }
+
+    // Resets the count of consecutive redirects after a connection
+    // succeeds.
+    pub(crate) fn reset_redirects(&self) {
+        self.inner.redirect.lock().unwrap().count = 0;
+    }
+
     /// Sets the callback for when a message arrives from the broker.
     ///
     /// # Arguments
@@ -732,7 +1488,7 @@ impl AsyncClient {
 
         unsafe {
             ffi::MQTTAsync_setMessageArrivedCallback(
-                self.inner.handle,
+                self.inner.handle(),
                 inner as *const _ as *mut c_void,
                 Some(AsyncClient::on_message_arrived),
             );
@@ -748,7 +1504,7 @@ impl AsyncClient {
             .on_message_arrived = None;
 
         unsafe {
-            ffi::MQTTAsync_setMessageArrivedCallback(self.inner.handle, ptr::null_mut(), None);
+            ffi::MQTTAsync_setMessageArrivedCallback(self.inner.handle(), ptr::null_mut(), None);
         }
     }
 
@@ -757,8 +1513,43 @@ impl AsyncClient {
     /// message.
     ///
     /// Returns a Publish Error on failure so that the original message
-    /// can be recovered and sent again.
+    /// can be recovered and sent again. If the message has properties that
+    /// are not allowed in a PUBLISH packet, or the client does strict topic
+    /// validation and the topic is not valid, that error is returned
+    /// instead.
     pub fn try_publish(&self, msg: Message) -> Result<DeliveryToken> {
+        self.check_publish(&msg)?;
+        self.send_message(msg).map_err(|(rc, msg)| Error::Publish(rc, msg))
+    }
+
+    /// Publishes a message to the MQTT broker.
+    ///
+    /// Returns a Delivery Token to track the progress of the operation.
+    ///
+    pub fn publish(&self, msg: Message) -> DeliveryToken {
+        if let Err(err) = self.check_publish(&msg) {
+            return DeliveryToken::from_err(msg, err);
+        }
+        match self.send_message(msg) {
+            Ok(tok) => tok,
+            Err((rc, msg)) => DeliveryToken::from_error(msg, rc),
+        }
+    }
+
+    // Checks that the message can be published: the topic, if the client
+    // does strict topic validation, and the properties.
+    fn check_publish(&self, msg: &Message) -> Result<()> {
+        if self.inner.strict_topics {
+            msg.validate_topic()?;
+            warn_discouraged_chars(msg.topic());
+        }
+        msg.properties().validate(PacketType::Publish)
+    }
+
+    // Queues a message that has been checked with check_publish(). If the
+    // C library can't take it, the message is given back with the error
+    // code, so that it can be recovered.
+    fn send_message(&self, msg: Message) -> std::result::Result<DeliveryToken, (i32, Message)> {
         debug!("Publish: {:?}", msg);
 
         let ver = self.mqtt_version();
@@ -768,7 +1559,7 @@ impl AsyncClient {
         let rc = unsafe {
             let msg = tok.message();
             ffi::MQTTAsync_sendMessage(
-                self.inner.handle,
+                self.inner.handle(),
                 msg.topic().as_ptr() as *const c_char,
                 &msg.cmsg,
                 &mut rsp_opts.copts,
@@ -777,24 +1568,20 @@ impl AsyncClient {
 
         if rc != 0 {
             mem::drop(unsafe { Token::from_raw(rsp_opts.copts.context) });
-            let msg: Message = tok.into();
-            return Err(Error::Publish(rc, msg));
+            return Err((rc, tok.into()));
         }
 
         tok.set_msgid(rsp_opts.copts.token as i16);
         Ok(tok)
     }
 
-    /// Publishes a message to the MQTT broker.
-    ///
-    /// Returns a Delivery Token to track the progress of the operation.
-    ///
-    pub fn publish(&self, msg: Message) -> DeliveryToken {
-        match self.try_publish(msg) {
-            Ok(tok) => tok,
-            Err(Error::Publish(rc, msg)) => DeliveryToken::from_error(msg, rc),
-            _ => panic!("Unknown publish error"),
+    // Checks the topic filter, if the client does strict topic validation.
+    fn check_topic_filter(&self, filter: &str) -> Result<()> {
+        if self.inner.strict_topics {
+            validate_topic_filter(filter)?;
+            warn_discouraged_chars(filter);
         }
+        Ok(())
     }
 
     /// Subscribes to a single topic.
@@ -809,16 +1596,21 @@ impl AsyncClient {
         S: Into<String>,
         Q: Into<QoS>,
     {
+        let topic = topic.into();
+        if let Err(err) = self.check_topic_filter(&topic) {
+            return SubscribeToken::from_err(err);
+        }
+
         let ver = self.mqtt_version();
         let tok = Token::from_request(None, ServerRequest::Subscribe);
         let mut rsp_opts = ResponseOptions::new(ver, tok.clone());
-        let topic = CString::new(topic.into()).unwrap();
+        let topic = CString::new(topic).unwrap();
         let qos = qos.into() as i32;
 
         debug!("Subscribe to '{:?}' @ QOS {}", topic, qos);
 
         let rc = unsafe {
-            ffi::MQTTAsync_subscribe(self.inner.handle, topic.as_ptr(), qos, &mut rsp_opts.copts)
+            ffi::MQTTAsync_subscribe(self.inner.handle(), topic.as_ptr(), qos, &mut rsp_opts.copts)
         };
 
         if rc != 0 {
@@ -853,20 +1645,30 @@ impl AsyncClient {
     {
         debug_assert!(self.mqtt_version() >= MqttVersion::V5);
 
+        let topic = topic.into();
+        if let Err(err) = self.check_topic_filter(&topic) {
+            return SubscribeToken::from_err(err);
+        }
+
+        let props = props.into().unwrap_or_default();
+        if let Err(err) = props.validate(PacketType::Subscribe) {
+            return SubscribeToken::from_err(err);
+        }
+
         let tok = Token::from_request(None, ServerRequest::Subscribe);
         let mut rsp_opts = ResponseOptionsBuilder::new()
             .token(tok.clone())
             .subscribe_options(opts.into())
-            .properties(props.into().unwrap_or_default())
+            .properties(props)
             .finalize();
 
-        let topic = CString::new(topic.into()).unwrap();
+        let topic = CString::new(topic).unwrap();
         let qos = qos.into() as i32;
 
         debug!("Subscribe to '{:?}' @ QOS {}", topic, qos);
 
         let rc = unsafe {
-            ffi::MQTTAsync_subscribe(self.inner.handle, topic.as_ptr(), qos, &mut rsp_opts.copts)
+            ffi::MQTTAsync_subscribe(self.inner.handle(), topic.as_ptr(), qos, &mut rsp_opts.copts)
         };
 
         if rc != 0 {
@@ -879,6 +1681,11 @@ impl AsyncClient {
 
     /// Subscribes to multiple topics simultaneously.
     ///
+    /// With MQTT v5, if the server refuses all of the topics, the token
+    /// fails with an [`Error::Server`] for the first one. If it accepts
+    /// some of them, the token succeeds, and the response has the reason
+    /// code for each topic.
+    ///
     /// # Arguments
     ///
     /// `topics` The collection of topic names
@@ -896,6 +1703,12 @@ impl AsyncClient {
             return SubscribeManyToken::from_error(-1);
         }
 
+        for topic in topics {
+            if let Err(err) = self.check_topic_filter(topic.as_ref()) {
+                return SubscribeManyToken::from_err(err);
+            }
+        }
+
         let ver = self.mqtt_version();
         let tok = Token::from_request(None, ServerRequest::SubscribeMany(n));
         let mut rsp_opts = ResponseOptions::new(ver, tok.clone());
@@ -906,7 +1719,7 @@ impl AsyncClient {
 
         let rc = unsafe {
             ffi::MQTTAsync_subscribeMany(
-                self.inner.handle,
+                self.inner.handle(),
                 n as c_int,
                 topics.as_c_arr_mut_ptr(),
                 qos.as_ptr(),
@@ -963,11 +1776,23 @@ impl AsyncClient {
 
         let n = topics.len();
         // TOOD: Make sure topics & qos are same length (or use min)
+
+        for topic in topics {
+            if let Err(err) = self.check_topic_filter(topic.as_ref()) {
+                return SubscribeManyToken::from_err(err);
+            }
+        }
+
+        let props = props.into().unwrap_or_default();
+        if let Err(err) = props.validate(PacketType::Subscribe) {
+            return SubscribeManyToken::from_err(err);
+        }
+
         let tok = Token::from_request(None, ServerRequest::SubscribeMany(n));
         let mut rsp_opts = ResponseOptionsBuilder::new()
             .token(tok.clone())
             .subscribe_many_options(opts)
-            .properties(props.into().unwrap_or_default())
+            .properties(props)
             .finalize();
 
         let topics = StringCollection::new(topics);
@@ -981,7 +1806,7 @@ impl AsyncClient {
 
         let rc = unsafe {
             ffi::MQTTAsync_subscribeMany(
-                self.inner.handle,
+                self.inner.handle(),
                 n as c_int,
                 topics.as_c_arr_mut_ptr(),
                 qos.as_ptr(),
@@ -1016,7 +1841,7 @@ impl AsyncClient {
         debug!("Unsubscribe from '{:?}'", topic);
 
         let rc = unsafe {
-            ffi::MQTTAsync_unsubscribe(self.inner.handle, topic.as_ptr(), &mut rsp_opts.copts)
+            ffi::MQTTAsync_unsubscribe(self.inner.handle(), topic.as_ptr(), &mut rsp_opts.copts)
         };
 
         if rc != 0 {
@@ -1041,6 +1866,10 @@ impl AsyncClient {
     {
         debug_assert!(self.mqtt_version() >= MqttVersion::V5);
 
+        if let Err(err) = props.validate(PacketType::Unsubscribe) {
+            return Token::from_err(err);
+        }
+
         let tok = Token::from_request(None, ServerRequest::Unsubscribe);
         let mut rsp_opts = ResponseOptionsBuilder::new()
             .token(tok.clone())
@@ -1052,7 +1881,7 @@ impl AsyncClient {
         debug!("Unsubscribe from '{:?}'", topic);
 
         let rc = unsafe {
-            ffi::MQTTAsync_unsubscribe(self.inner.handle, topic.as_ptr(), &mut rsp_opts.copts)
+            ffi::MQTTAsync_unsubscribe(self.inner.handle(), topic.as_ptr(), &mut rsp_opts.copts)
         };
 
         if rc != 0 {
@@ -1065,6 +1894,11 @@ impl AsyncClient {
 
     /// Unsubscribes from multiple topics simultaneously.
     ///
+    /// With MQTT v5, if the server refuses all of the topics, the token
+    /// fails with an [`Error::Server`] for the first one. If it accepts
+    /// some of them, the token succeeds, and the response has the reason
+    /// code for each topic.
+    ///
     /// # Arguments
     ///
     /// `topic` The topics to unsubscribe. Each must match a topic from a
@@ -1085,7 +1919,7 @@ impl AsyncClient {
 
         let rc = unsafe {
             ffi::MQTTAsync_unsubscribeMany(
-                self.inner.handle,
+                self.inner.handle(),
                 n as c_int,
                 topics.as_c_arr_mut_ptr(),
                 &mut rsp_opts.copts,
@@ -1114,6 +1948,10 @@ impl AsyncClient {
     {
         debug_assert!(self.mqtt_version() >= MqttVersion::V5);
 
+        if let Err(err) = props.validate(PacketType::Unsubscribe) {
+            return Token::from_err(err);
+        }
+
         let n = topics.len();
         let tok = Token::from_request(None, ServerRequest::UnsubscribeMany(n));
         let mut rsp_opts = ResponseOptionsBuilder::new()
@@ -1127,7 +1965,7 @@ impl AsyncClient {
 
         let rc = unsafe {
             ffi::MQTTAsync_unsubscribeMany(
-                self.inner.handle,
+                self.inner.handle(),
                 n as c_int,
                 topics.as_c_arr_mut_ptr(),
                 &mut rsp_opts.copts,
@@ -1226,9 +2064,235 @@ impl AsyncClient {
     /// Returns client ID used for client instance
     ///
     /// Client ID is returned as a rust String as set in a
-    /// CreateOptionsBuilder for symmetry
+    /// CreateOptionsBuilder for symmetry. If that was empty, and the
+    /// server has since assigned an ID to the client, this returns the
+    /// assigned ID.
     pub fn client_id(&self) -> String {
-        self.inner.client_id.clone().into_string().unwrap()
+        let id = self.inner.client_id.clone().into_string().unwrap();
+        if id.is_empty() {
+            if let Some(assigned_id) = self.assigned_client_id() {
+                return assigned_id;
+            }
+        }
+        id
+    }
+
+    /// Gets the client ID assigned by an MQTT v5 server, if any.
+    ///
+    /// When the client connects with an empty client ID, the server
+    /// assigns one, and reports it in the CONNACK. Later connections from
+    /// the client then use the assigned ID, so that they resume the same
+    /// session. For that, an automatic reconnect in the connect options is
+    /// done with a [`ReconnectPolicy`] instead of by the C library, which
+    /// would keep connecting with an empty ID.
+    ///
+    /// A client with persistence keeps connecting with an empty ID, since
+    /// its messages in flight are stored under the ID it was created with.
+    /// Use [`CreateOptionsBuilder::client_id_file()`](crate::CreateOptionsBuilder::client_id_file)
+    /// to create it with the assigned ID instead.
+    pub fn assigned_client_id(&self) -> Option<String> {
+        self.inner.assigned_client_id.lock().unwrap().clone()
+    }
+
+    // Records the client ID assigned by the server, writing it to the
+    // client ID file, if there is one.
+    pub(crate) fn set_assigned_client_id(&self, id: String) {
+        debug!("Server assigned client ID: {}", id);
+        if let Some(ref path) = self.inner.client_id_file {
+            if let Err(err) = write_client_id(path, &id) {
+                warn!("Error saving client ID to {:?}: {}", path, err);
+            }
+        }
+        *self.inner.assigned_client_id.lock().unwrap() = Some(id);
+    }
+
+    // Determines if the client connects with the ID that the server
+    // assigned the last time, if any, so that the session is resumed
+    // rather than a new one created with a different ID. That's for an
+    // MQTT v5 client created with an empty client ID.
+    //
+    // A C client with persistence keeps its messages in flight under its
+    // client ID, so it isn't replaced; the ID is only used when the
+    // client is created again, with the client ID file.
+    fn uses_assigned_id(&self, opts: &ConnectOptions) -> bool {
+        self.inner.client_id.as_bytes().is_empty()
+            && opts.mqtt_version_raw() >= MQTT_VERSION_5
+            && self.inner.persistence_type == ffi::MQTTCLIENT_PERSISTENCE_NONE as c_int
+    }
+
+    // Switches to the client ID that the server assigned, if the client
+    // uses it.
+    fn apply_client_id(&self, opts: &ConnectOptions) {
+        if let Some(id) = self.assigned_client_id().filter(|_| self.uses_assigned_id(opts)) {
+            self.use_client_id(id);
+        }
+    }
+
+    // Turns an automatic reconnect in the connect options into a reconnect
+    // policy with the same delays, when each reconnect has to go through
+    // connect(): to use the client ID assigned by the server, or to order
+    // the servers with the failover strategy. The C library only sends
+    // the ID that its client was created with, and doesn't report the
+    // CONNACK for its own reconnects.
+    //
+    // The policy only lasts while the connect options ask for an automatic
+    // reconnect. A policy set by the application is kept as it is.
+    fn apply_reconnect(&self, opts: &mut ConnectOptions) {
+        let takeover = opts.copts.automaticReconnect != 0
+            && (self.uses_assigned_id(opts)
+                || self.failover_strategy() != FailoverStrategy::Ordered);
+
+        if !takeover && self.inner.reconnect_takeover.load(Ordering::SeqCst) {
+            debug!("Removing the reconnect policy for earlier connect options");
+            self.remove_reconnect_policy();
+        }
+
+        if takeover {
+            if !self.inner.reconnector.has_policy() {
+                let secs = |n: c_int| Duration::from_secs(n.max(1) as u64);
+                let min = secs(opts.copts.minRetryInterval);
+                let max = secs(opts.copts.maxRetryInterval);
+                self.set_reconnect_policy(ExponentialBackoff::new(min, max));
+                self.inner.reconnect_takeover.store(true, Ordering::SeqCst);
+            }
+            opts.copts.automaticReconnect = 0;
+        }
+    }
+
+    // Switches to a new C client that connects with the client ID.
+    // This only happens while the current one is disconnected, with
+    // nothing left to send. The old one is kept until the client is
+    // dropped, since a C callback might still be running on it.
+    fn use_client_id(&self, id: String) {
+        let mut handle_id = self.inner.handle_client_id.lock().unwrap();
+        if *handle_id == id {
+            return;
+        }
+        if self.is_connected() || self.has_pending_tokens() {
+            debug!("Not switching to client ID {} with work in progress", id);
+            return;
+        }
+
+        let cid = match CString::new(id.as_str()) {
+            Ok(cid) => cid,
+            Err(_) => return,
+        };
+        let handle = match self.inner.create_handle(&cid) {
+            Ok(handle) => handle,
+            Err(err) => {
+                warn!("Unable to use client ID {}: {}", id, err);
+                return;
+            }
+        };
+        debug!("Connecting with client ID {}. Handle: {:?}", id, handle);
+
+        self.register_callbacks(handle);
+        let old = self.inner.handle.swap(handle, Ordering::SeqCst);
+        self.inner.retired_handles.lock().unwrap().push(old);
+        *handle_id = id;
+    }
+
+    // Determines if the C client has any operations that haven't
+    // completed, including messages buffered while disconnected.
+    fn has_pending_tokens(&self) -> bool {
+        let mut toks: *mut ffi::MQTTAsync_token = ptr::null_mut();
+        unsafe {
+            if ffi::MQTTAsync_getPendingTokens(self.inner.handle(), &mut toks) != 0 {
+                return true;
+            }
+            if toks.is_null() {
+                return false;
+            }
+            let pending = *toks != -1;
+            ffi::MQTTAsync_free(toks as *mut c_void);
+            pending
+        }
+    }
+
+    // Registers the C callbacks on a new C client for the callbacks,
+    // streams, and policies that are set on this client. The connected
+    // callback is always needed, to keep track of the server.
+    fn register_callbacks(&self, handle: ffi::MQTTAsync) {
+        let inner: &InnerAsyncClient = &self.inner;
+        let context = inner as *const _ as *mut c_void;
+
+        let redirect = inner.redirect.lock().unwrap().policy.is_some();
+        let reconnect = inner.reconnector.has_policy();
+
+        let cbctx = inner.callback_context.lock().unwrap();
+        let streaming = cbctx.on_message_arrived.is_some();
+
+        unsafe {
+            ffi::MQTTAsync_setConnected(handle, context, Some(AsyncClient::on_connected));
+            if cbctx.on_connection_lost.is_some() || streaming || reconnect {
+                ffi::MQTTAsync_setConnectionLostCallback(
+                    handle,
+                    context,
+                    Some(AsyncClient::on_connection_lost),
+                );
+            }
+            if cbctx.on_disconnected.is_some() || streaming || redirect || reconnect {
+                ffi::MQTTAsync_setDisconnected(
+                    handle,
+                    context,
+                    Some(AsyncClient::on_disconnected),
+                );
+            }
+            if streaming {
+                ffi::MQTTAsync_setMessageArrivedCallback(
+                    handle,
+                    context,
+                    Some(AsyncClient::on_message_arrived),
+                );
+            }
+        }
+    }
+
+    /// Gets the error from an MQTT v5 DISCONNECT sent by the server, if
+    /// it ended the last connection with a failure reason code.
+    ///
+    /// It has the server's Reason String and User Properties, if any, and
+    /// is cleared when the client connects again. This is also the reason
+    /// given to the [`ReconnectPolicy`] for the first attempt to reconnect.
+    pub fn disconnect_error(&self) -> Option<ServerError> {
+        self.inner.disconnect_error.lock().unwrap().clone()
+    }
+
+    /// Gets the MQTT v5 session expiry interval for the last connection.
+    ///
+    /// This is the interval granted by the server in the CONNACK, if it
+    /// sent one, otherwise the one requested in the connect options. It is
+    /// `None` until the client makes an MQTT v5 connection.
+    pub fn session_expiry_interval(&self) -> Option<Duration> {
+        *self.inner.session_expiry.lock().unwrap()
+    }
+
+    // Records the session expiry interval for a new MQTT v5 connection,
+    // from the one granted by the server, if any.
+    pub(crate) fn set_session_expiry_interval(&self, granted: Option<Duration>) {
+        let expiry = granted.unwrap_or_else(|| {
+            let opts = self.inner.opts.lock().unwrap();
+            opts.session_expiry_interval().unwrap_or(Duration::ZERO)
+        });
+        debug!("Session expiry interval: {:?}", expiry);
+        *self.inner.session_expiry.lock().unwrap() = Some(expiry);
+    }
+
+    // Checks that the disconnect doesn't raise the session expiry interval
+    // from zero, which the spec forbids.
+    fn check_session_expiry(&self, opts: &DisconnectOptions) -> Result<()> {
+        match opts.session_expiry_interval() {
+            Some(expiry) if expiry > Duration::ZERO => {
+                let opts = self.inner.opts.lock().unwrap();
+                if opts.session_expiry_interval().unwrap_or(Duration::ZERO) == Duration::ZERO {
+                    return Err(Error::General(
+                        "Session expiry can't be set on disconnect if it was zero on connect",
+                    ));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
     }
 
     /// Returns server URI used for connection
@@ -1244,18 +2308,63 @@ impl AsyncClient {
 unsafe impl Send for AsyncClient {}
 unsafe impl Sync for AsyncClient {}
 
+impl InnerAsyncClient {
+    // Gets the handle to the current C client.
+    fn handle(&self) -> ffi::MQTTAsync {
+        self.handle.load(Ordering::SeqCst)
+    }
+
+    // Creates a C client with the client ID, and the options that this
+    // client was created with.
+    fn create_handle(&self, client_id: &CStr) -> Result<ffi::MQTTAsync> {
+        let mut handle = ptr::null_mut();
+        let mut copts = self.create_opts;
+
+        let rc = unsafe {
+            ffi::MQTTAsync_createWithOptions(
+                &mut handle as *mut *mut c_void,
+                self.server_uri.as_ptr(),
+                client_id.as_ptr(),
+                self.persistence_type,
+                self.persistence_context,
+                &mut copts,
+            ) as i32
+        };
+
+        if rc != 0 {
+            warn!("Create failure: {}", rc);
+            return Err(rc.into());
+        }
+        Ok(handle)
+    }
+}
+
 impl Drop for InnerAsyncClient {
     /// Drops the client by closing dpen all the underlying, dependent objects
     fn drop(&mut self) {
-        // Destroy the underlying C client.
-        if !self.handle.is_null() {
-            unsafe {
-                ffi::MQTTAsync_destroy(&mut self.handle as *mut *mut c_void);
+        // Destroy the underlying C clients.
+        let retired = mem::take(self.retired_handles.get_mut().unwrap());
+        for mut handle in retired.into_iter().chain([*self.handle.get_mut()]) {
+            if !handle.is_null() {
+                unsafe {
+                    ffi::MQTTAsync_destroy(&mut handle as *mut *mut c_void);
+                }
             }
         }
     }
 }
 
+// Warns about a topic with characters that the spec says should not be
+// used, which the strict topic checks let through.
+fn warn_discouraged_chars(topic: &str) {
+    if let Some(i) = discouraged_topic_level(topic) {
+        warn!(
+            "Topic level {} contains a control character or non-character: {:?}",
+            i, topic
+        );
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 //                              Unit Tests
 /////////////////////////////////////////////////////////////////////////////
@@ -1263,7 +2372,7 @@ impl Drop for InnerAsyncClient {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::create_options::CreateOptionsBuilder;
+    use crate::{connect_options::ConnectOptionsBuilder, create_options::CreateOptionsBuilder};
     use std::sync::{Arc, Mutex, RwLock};
     use std::thread;
 
@@ -1449,4 +2558,92 @@ mod tests {
         let retrieved = client.unwrap().server_uri();
         assert_eq!(retrieved, server_uri.to_string());
     }
+    #[test]
+    fn test_strict_topic_validation() {
+        let cli = CreateOptionsBuilder::new()
+            .server_uri("tcp://localhost:1883")
+            .strict_topic_validation(true)
+            .create_client()
+            .unwrap();
+
+        let res = cli.publish(Message::new("some/+/topic", "", 0)).wait();
+        assert!(matches!(res, Err(Error::BadTopic(_))));
+
+        let res = cli.try_publish(Message::new("some/+/topic", "", 0));
+        assert!(matches!(res, Err(Error::BadTopic(_))));
+
+        let res = cli.subscribe("some/#/topic", 1).wait();
+        assert!(matches!(res, Err(Error::BadTopic(_))));
+
+        let res = cli.subscribe_many(&["a/b", "a/b#"], &[1, 1]).wait();
+        assert!(matches!(res, Err(Error::BadTopic(_))));
+    }
+
+    #[test]
+    fn test_disconnect_session_expiry() {
+        let cli = AsyncClient::new("tcp://localhost:1883").unwrap();
+        assert_eq!(cli.session_expiry_interval(), None);
+
+        // The session expiry can't be raised from zero on disconnect
+        let opts = DisconnectOptionsBuilder::new()
+            .session_expiry_interval(Duration::from_secs(60))
+            .finalize();
+        assert!(cli.check_session_expiry(&opts).is_err());
+
+        let opts = DisconnectOptionsBuilder::new()
+            .session_expiry_interval(Duration::ZERO)
+            .finalize();
+        assert!(cli.check_session_expiry(&opts).is_ok());
+
+        *cli.inner.opts.lock().unwrap() = ConnectOptionsBuilder::new()
+            .session_expiry_interval(Duration::from_secs(10))
+            .finalize();
+        let opts = DisconnectOptionsBuilder::new()
+            .session_expiry_interval(Duration::from_secs(60))
+            .finalize();
+        assert!(cli.check_session_expiry(&opts).is_ok());
+
+        cli.set_session_expiry_interval(None);
+        assert_eq!(cli.session_expiry_interval(), Some(Duration::from_secs(10)));
+
+        cli.set_session_expiry_interval(Some(Duration::from_secs(5)));
+        assert_eq!(cli.session_expiry_interval(), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_presence_will() {
+        let cli = CreateOptionsBuilder::new()
+            .server_uri("tcp://localhost:1883")
+            .client_id("dev1")
+            .create_client()
+            .unwrap();
+        assert!(cli.presence().is_none());
+
+        let lwt = Message::new("other/will", "bye", 0);
+        let mut opts = ConnectOptionsBuilder::new().will_message(lwt).finalize();
+
+        cli.set_presence(PresenceOptions::new("status/{client_id}").offline_payload("gone"));
+        cli.apply_presence(&mut opts).unwrap();
+
+        assert!(!opts.copts.will.is_null());
+        let will = unsafe { &*opts.copts.will };
+        let topic = unsafe { CStr::from_ptr(will.topicName) };
+        assert_eq!(topic.to_str().unwrap(), "status/dev1");
+        assert_eq!(will.retained, 1);
+        assert_eq!(will.qos, 1);
+
+        cli.remove_presence();
+        assert!(cli.presence().is_none());
+
+        // The {client_id} field needs a client ID
+        let cli = CreateOptionsBuilder::new()
+            .server_uri("tcp://localhost:1883")
+            .create_client()
+            .unwrap();
+        cli.set_presence(PresenceOptions::new("status/{client_id}"));
+        assert!(cli.apply_presence(&mut opts).is_err());
+
+        cli.set_presence(PresenceOptions::new("status"));
+        assert!(cli.apply_presence(&mut opts).is_ok());
+    }
 }
@@ -31,8 +31,8 @@
 use crate::{
     async_client::AsyncClient, connect_options::ConnectOptions, create_options::CreateOptions,
     disconnect_options::DisconnectOptions, errors::Result, message::Message,
-    properties::Properties, server_response::ServerResponse, subscribe_options::SubscribeOptions,
-    QoS, Receiver,
+    presence::PresenceOptions, properties::Properties, server_response::ServerResponse,
+    subscribe_options::SubscribeOptions, QoS, Receiver,
 };
 use std::time::Duration;
 
@@ -320,10 +320,33 @@ impl Client {
     /// Returns client ID used for client instance
     ///
     /// Client ID is returned as a rust String as set in a
-    /// CreateOptionsBuilder for symmetry
+    /// CreateOptionsBuilder for symmetry, or the ID assigned by the
+    /// server if that was empty.
     pub fn client_id(&self) -> String {
         self.cli.client_id()
     }
+
+    /// Gets the client ID assigned by an MQTT v5 server, if any.
+    pub fn assigned_client_id(&self) -> Option<String> {
+        self.cli.assigned_client_id()
+    }
+
+    /// Gets the MQTT v5 session expiry interval for the last connection,
+    /// as granted by the server.
+    pub fn session_expiry_interval(&self) -> Option<Duration> {
+        self.cli.session_expiry_interval()
+    }
+
+    /// Sets the options to announce whether the client is online, with
+    /// birth and death messages.
+    pub fn set_presence(&self, presence: PresenceOptions) {
+        self.cli.set_presence(presence)
+    }
+
+    /// Removes the options to announce whether the client is online.
+    pub fn remove_presence(&self) {
+        self.cli.remove_presence()
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////////
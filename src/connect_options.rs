@@ -26,15 +26,17 @@
 //! MQTT broker/server.
 
 use crate::{
+    errors::{Error, Result},
     ffi, from_c_bool,
     message::Message,
     name_value::NameValueCollection,
-    properties::Properties,
+    properties::{PacketType, Properties, PropertyCode},
     ssl_options::SslOptions,
     string_collection::StringCollection,
     to_c_bool,
     token::{ConnectToken, Token, TokenInner},
     types::*,
+    user_properties::UserProperties,
     will_options::WillOptions,
 };
 use std::{ffi::CString, os::raw::c_int, pin::Pin, ptr, time::Duration};
@@ -273,6 +275,81 @@ impl ConnectOptions {
         }
     }
 
+    /// Gets the SSL/TLS options for the connection, if any.
+    pub fn ssl_options(&self) -> Option<&SslOptions> {
+        self.data.ssl.as_ref()
+    }
+
+    /// Gets the servers to which the client will connect, if they were
+    /// set in the options to override the one the client was created with.
+    pub fn server_uris(&self) -> Vec<String> {
+        self.data.server_uris.to_vec()
+    }
+
+    /// Sets the servers to which the client will connect, overriding the
+    /// one that the client was created with.
+    pub fn set_server_uris<T>(&mut self, server_uris: &[T])
+    where
+        T: AsRef<str>,
+    {
+        let mut data = (*self.data).clone();
+        data.server_uris = StringCollection::new(server_uris);
+        *self = Self::from_data(self.copts, data);
+    }
+
+    // Sets the will, replacing any in the options, along with its
+    // properties.
+    pub(crate) fn set_will(&mut self, will: WillOptions) {
+        let mut data = (*self.data).clone();
+        data.will_props = if will.properties().is_empty() {
+            None
+        }
+        else {
+            Some(will.properties().clone())
+        };
+        data.will = Some(will);
+        *self = Self::from_data(self.copts, data);
+    }
+
+    /// Gets the MQTT v5 properties for the connect packet, if any.
+    pub fn properties(&self) -> Option<&Properties> {
+        self.data.props.as_ref()
+    }
+
+    /// Gets the MQTT v5 user properties for the connect packet.
+    pub fn user_properties(&self) -> UserProperties {
+        self.data
+            .props
+            .as_ref()
+            .map(|props| props.user_properties())
+            .unwrap_or_default()
+    }
+
+    /// Gets the MQTT v5 session expiry interval requested in the options,
+    /// if any.
+    ///
+    /// Without one, the server ends the session when the connection
+    /// closes.
+    pub fn session_expiry_interval(&self) -> Option<Duration> {
+        self.data
+            .props
+            .as_ref()
+            .and_then(|props| props.get_int(PropertyCode::SessionExpiryInterval))
+            .map(|secs| Duration::from_secs(secs as u32 as u64))
+    }
+
+    /// Checks that the connect properties and the will properties, if
+    /// any, are allowed in a CONNECT packet.
+    pub fn validate_properties(&self) -> Result<()> {
+        if let Some(ref props) = self.data.props {
+            props.validate(PacketType::Connect)?;
+        }
+        if let Some(ref props) = self.data.will_props {
+            props.validate(PacketType::Will)?;
+        }
+        Ok(())
+    }
+
     /// Sets the token to ber used for connect completion callbacks.
     ///
     /// Note that we leak the token to give to the C lib. When we're
@@ -312,6 +389,8 @@ unsafe impl Sync for ConnectOptions {}
 pub struct ConnectOptionsBuilder {
     copts: ffi::MQTTAsync_connectOptions,
     data: ConnectOptionsData,
+    /// The first error from a setter, for try_finalize() to report
+    err: Option<Error>,
 }
 
 impl ConnectOptionsBuilder {
@@ -449,12 +528,22 @@ impl ConnectOptionsBuilder {
     /// `will` The LWT options for the connection.
     #[deprecated(note = "Pass in a message with `will_message` instead")]
     pub fn will_options(&mut self, will: WillOptions) -> &mut Self {
+        self.data.will_props = if will.properties().is_empty() {
+            None
+        }
+        else {
+            Some(will.properties().clone())
+        };
         self.data.will = Some(will);
         self
     }
 
     /// Sets the LWT message for the connection.
     ///
+    /// The properties of the message become the will properties, which
+    /// are checked by [`try_finalize()`](Self::try_finalize), and when
+    /// connecting.
+    ///
     /// # Arguments
     ///
     /// `will` The LWT options for the connection.
@@ -464,6 +553,13 @@ impl ConnectOptionsBuilder {
         self
     }
 
+    /// Sets the LWT message for the connection, checking that the
+    /// properties of the message are allowed as will properties.
+    pub fn try_will_message(&mut self, will: Message) -> Result<&mut Self> {
+        will.properties().validate(PacketType::Will)?;
+        Ok(self.will_message(will))
+    }
+
     /// Sets the SSL options for the connection.
     ///
     /// # Arguments
@@ -571,6 +667,10 @@ impl ConnectOptionsBuilder {
 
     /// Sets the collection of properties for the connections.
     ///
+    /// The properties are checked against the ones allowed in a CONNECT
+    /// packet by [`try_finalize()`](Self::try_finalize), and when
+    /// connecting.
+    ///
     /// # Arguments
     ///
     /// `props` The collection of properties to include with the connect message.
@@ -583,6 +683,48 @@ impl ConnectOptionsBuilder {
         self
     }
 
+    /// Sets the collection of properties for the connections, checking
+    /// that they are allowed in a CONNECT packet.
+    pub fn try_properties(&mut self, props: Properties) -> Result<&mut Self> {
+        props.validate(PacketType::Connect)?;
+        Ok(self.properties(props))
+    }
+
+    /// Sets the MQTT v5 user properties for the connect packet, replacing
+    /// any in the properties already set, and keeping the others.
+    ///
+    /// Like setting the properties, this sets the MQTT version to v5 if
+    /// it was set to an earlier version.
+    pub fn user_properties(&mut self, user_props: &UserProperties) -> &mut Self {
+        let mut props = self.data.props.take().unwrap_or_default();
+        props.set_user_properties(user_props);
+        self.properties(props)
+    }
+
+    /// Sets the MQTT v5 session expiry interval.
+    ///
+    /// This is how long the server keeps the session after the connection
+    /// closes. Zero ends the session with the connection, which is what
+    /// the server does if the interval is not set, and `u32::MAX` seconds
+    /// keeps it forever. The interval has a resolution of one second, and
+    /// saturates at `u32::MAX` seconds.
+    ///
+    /// The server may grant a different interval, which the client
+    /// reports in
+    /// [`AsyncClient::session_expiry_interval()`](crate::AsyncClient::session_expiry_interval).
+    ///
+    /// Like setting the properties, this sets the MQTT version to v5 if
+    /// it was set to an earlier version.
+    pub fn session_expiry_interval(&mut self, expiry: Duration) -> &mut Self {
+        let secs = u32::try_from(expiry.as_secs()).unwrap_or(u32::MAX);
+        let mut props = self.data.props.take().unwrap_or_default();
+        props.remove_all(PropertyCode::SessionExpiryInterval);
+        if let Err(err) = props.push_u32(PropertyCode::SessionExpiryInterval, secs) {
+            self.err.get_or_insert(err);
+        }
+        self.properties(props)
+    }
+
     /// Sets the additional HTTP headers that will be sent in the
     /// WebSocket opening handshake.
     pub fn http_headers<N, V>(&mut self, coll: &[(N, V)]) -> &mut Self
@@ -629,6 +771,22 @@ impl ConnectOptionsBuilder {
     pub fn finalize(&self) -> ConnectOptions {
         ConnectOptions::from_data(self.copts, self.data.clone())
     }
+
+    /// Finalize the builder to create the connect options, checking that
+    /// the connect and will properties are valid for the CONNECT packet.
+    ///
+    /// This also fails with the first error from a setter that couldn't
+    /// set a property, like
+    /// [`session_expiry_interval()`](Self::session_expiry_interval). The
+    /// error is only reported once.
+    pub fn try_finalize(&mut self) -> Result<ConnectOptions> {
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+        let opts = self.finalize();
+        opts.validate_properties()?;
+        Ok(opts)
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -980,4 +1138,133 @@ mod tests {
         });
         let _ = thr.join().unwrap();
     }
+
+    #[test]
+    fn test_try_finalize_properties() {
+        let mut props = Properties::new();
+        props.push_int(PropertyCode::TopicAlias, 1).unwrap();
+
+        let res = ConnectOptionsBuilder::new_v5().properties(props).try_finalize();
+        assert!(matches!(
+            res,
+            Err(crate::Error::PropertyNotAllowed(
+                PropertyCode::TopicAlias,
+                PacketType::Connect
+            ))
+        ));
+
+        let mut props = Properties::new();
+        props
+            .push_int(PropertyCode::SessionExpiryInterval, 60)
+            .unwrap();
+
+        let lwt = MessageBuilder::new()
+            .topic("event/failure")
+            .properties(props)
+            .finalize();
+
+        let res = ConnectOptionsBuilder::new_v5().will_message(lwt.clone()).try_finalize();
+        assert!(matches!(
+            res,
+            Err(crate::Error::PropertyNotAllowed(
+                PropertyCode::SessionExpiryInterval,
+                PacketType::Will
+            ))
+        ));
+
+        let mut bldr = ConnectOptionsBuilder::new_v5();
+        assert!(bldr.try_will_message(lwt).is_err());
+
+        let mut props = Properties::new();
+        props.push_int(PropertyCode::TopicAlias, 1).unwrap();
+        assert!(bldr.try_properties(props).is_err());
+
+        let mut props = Properties::new();
+        props
+            .push_int(PropertyCode::SessionExpiryInterval, 60)
+            .unwrap();
+        assert!(bldr.try_properties(props).is_ok());
+    }
+
+    #[test]
+    fn test_user_properties() {
+        let opts = ConnectOptionsBuilder::new().finalize();
+        assert!(opts.properties().is_none());
+        assert!(opts.user_properties().is_empty());
+
+        let mut props = Properties::new();
+        props
+            .push_int(PropertyCode::SessionExpiryInterval, 60)
+            .unwrap();
+
+        let user_props: UserProperties = [("app", "x"), ("app", "y")].into_iter().collect();
+
+        let opts = ConnectOptionsBuilder::new()
+            .properties(props)
+            .user_properties(&user_props)
+            .finalize();
+
+        assert_eq!(opts.mqtt_version(), MqttVersion::V5);
+        assert_eq!(opts.user_properties(), user_props);
+        assert_eq!(
+            opts.properties()
+                .unwrap()
+                .get_int(PropertyCode::SessionExpiryInterval),
+            Some(60)
+        );
+    }
+
+    #[test]
+    fn test_session_expiry_interval() {
+        let opts = ConnectOptionsBuilder::new().finalize();
+        assert_eq!(opts.session_expiry_interval(), None);
+
+        let opts = ConnectOptionsBuilder::new()
+            .session_expiry_interval(Duration::from_secs(60))
+            .session_expiry_interval(Duration::from_secs(3600))
+            .finalize();
+
+        assert_eq!(opts.mqtt_version(), MqttVersion::V5);
+        assert_eq!(opts.session_expiry_interval(), Some(Duration::from_secs(3600)));
+        assert_eq!(opts.properties().unwrap().len(), 1);
+        assert!(opts.validate_properties().is_ok());
+
+        let opts = ConnectOptionsBuilder::new()
+            .session_expiry_interval(Duration::MAX)
+            .finalize();
+        assert_eq!(
+            opts.properties()
+                .unwrap()
+                .get_val::<u32>(PropertyCode::SessionExpiryInterval),
+            Some(u32::MAX)
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_will_options_delay() {
+        let mut lwt = WillOptions::new("event/failure", "gone", 1);
+        lwt.set_will_delay_interval(Duration::from_secs(30));
+
+        let opts = ConnectOptionsBuilder::new_v5().will_options(lwt).finalize();
+
+        let will_props = opts.data.will_props.as_ref().unwrap();
+        assert_eq!(Some(30), will_props.get_int(PropertyCode::WillDelayInterval));
+        assert!(!opts.copts.willProperties.is_null());
+    }
+
+    #[test]
+    fn test_set_server_uris() {
+        let mut opts = ConnectOptionsBuilder::new()
+            .server_uris(&["tcp://broker1:1883"])
+            .finalize();
+        assert_eq!(opts.server_uris(), ["tcp://broker1:1883"]);
+
+        opts.set_server_uris(&["tcp://broker2:1883", "tcp://broker3:1883"]);
+        assert_eq!(opts.server_uris(), ["tcp://broker2:1883", "tcp://broker3:1883"]);
+        assert_eq!(opts.copts.serverURIcount, 2);
+
+        let uri = unsafe { CStr::from_ptr(*opts.copts.serverURIs) };
+        assert_eq!(uri.to_str().unwrap(), "tcp://broker2:1883");
+    }
 }
@@ -21,14 +21,14 @@
  *******************************************************************************/
 
 use std::{
-    cmp, fmt,
+    cmp, fmt, fs, io,
     os::raw::c_int,
     path::{Path, PathBuf},
 };
 
 use crate::{
-    async_client::AsyncClient, client_persistence::ClientPersistence, ffi, to_c_bool, MqttVersion,
-    Result, UserData,
+    async_client::AsyncClient, client_persistence::ClientPersistence, ffi, server_uri::ServerUri,
+    to_c_bool, MqttVersion, Result, UserData,
 };
 
 /*
@@ -138,6 +138,10 @@ pub struct CreateOptions {
     pub(crate) persistence: PersistenceType,
     /// User-defined data, if any
     pub(crate) user_data: Option<UserData>,
+    /// The file to keep a server-assigned client ID, if any
+    pub(crate) client_id_file: Option<PathBuf>,
+    /// Whether the client checks topics before sending them
+    pub(crate) strict_topic_validation: bool,
 }
 
 impl CreateOptions {
@@ -188,6 +192,12 @@ impl From<String> for CreateOptions {
     }
 }
 
+impl From<ServerUri> for CreateOptions {
+    fn from(server_uri: ServerUri) -> Self {
+        Self::from(String::from(server_uri))
+    }
+}
+
 impl<'a, 'b> From<(&'a str, &'b str)> for CreateOptions {
     /// Constructs the create options from two string reference giving the server URI
     /// and Client ID.
@@ -243,6 +253,8 @@ pub struct CreateOptionsBuilder {
     client_id: String,
     persistence: PersistenceType,
     user_data: Option<UserData>,
+    client_id_file: Option<PathBuf>,
+    strict_topic_validation: bool,
 }
 
 impl CreateOptionsBuilder {
@@ -303,6 +315,37 @@ impl CreateOptionsBuilder {
         self
     }
 
+    /// Sets a file in which to keep a client ID assigned by the server,
+    /// so that it can be reused when the client is created again, such as
+    /// after the application restarts.
+    ///
+    /// When the client ID is left empty, an MQTT v5 server assigns one to
+    /// the client, and reports it in the Assigned Client Identifier
+    /// property of the CONNACK. The client writes that ID to this file.
+    /// Then, when the client is created with an empty client ID and the
+    /// file already holds an ID, that ID is used instead, both for the
+    /// persistence store and for connecting. With
+    /// [`clean_start(false)`](crate::ConnectOptionsBuilder::clean_start)
+    /// this resumes the session that the server created for the original
+    /// connection.
+    ///
+    /// Within the same process, a client without persistence already
+    /// reconnects with the ID that the server assigned; the file carries
+    /// it across restarts. A client with persistence only uses the ID
+    /// from the file, since its stored messages are kept under the ID
+    /// that it was created with.
+    ///
+    /// # Arguments
+    ///
+    /// `path` The path to the file holding the client ID.
+    pub fn client_id_file<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.client_id_file = Some(path.into());
+        self
+    }
+
     /// Sets the type of persistence used by the client.
     /// The default is for the library to automatically use file persistence,
     /// although this can be turned off by specify `None` for a more
@@ -429,6 +472,25 @@ impl CreateOptionsBuilder {
         self
     }
 
+    /// Sets whether the client checks topics against the rules of the
+    /// MQTT spec before sending them.
+    ///
+    /// When on, the topic of each message published is checked with
+    /// [`validate_topic_name()`](crate::topic::validate_topic_name), and
+    /// each topic filter subscribed to is checked with
+    /// [`validate_topic_filter()`](crate::topic::validate_topic_filter).
+    /// The operation fails with the error if the topic is not valid. A
+    /// topic with characters that are only discouraged, as found by
+    /// [`discouraged_topic_level()`](crate::topic::discouraged_topic_level),
+    /// is logged as a warning.
+    ///
+    /// This is off by default, leaving the checks to the C library and the
+    /// server.
+    pub fn strict_topic_validation(mut self, on: bool) -> Self {
+        self.strict_topic_validation = on;
+        self
+    }
+
     /// Constructs a set of create options from the builder information.
     pub fn finalize(self) -> CreateOptions {
         let mut opts = CreateOptions {
@@ -437,7 +499,14 @@ impl CreateOptionsBuilder {
             client_id: self.client_id,
             persistence: self.persistence,
             user_data: self.user_data,
+            client_id_file: self.client_id_file,
+            strict_topic_validation: self.strict_topic_validation,
         };
+        if opts.client_id.is_empty() {
+            if let Some(id) = opts.client_id_file.as_deref().and_then(read_client_id) {
+                opts.client_id = id;
+            }
+        }
         match opts.persistence {
             PersistenceType::File if opts.client_id.is_empty() => {
                 opts.persistence = PersistenceType::None
@@ -453,6 +522,30 @@ impl CreateOptionsBuilder {
     }
 }
 
+// Reads a client ID previously assigned by the server from a file.
+fn read_client_id(path: &Path) -> Option<String> {
+    let id = fs::read_to_string(path).ok()?;
+    let id = id.trim();
+    if id.is_empty() {
+        None
+    }
+    else {
+        Some(id.to_string())
+    }
+}
+
+/// Writes a client ID assigned by the server to a file, so it can be
+/// reused when the client is created again.
+///
+/// This writes to a temporary file and then renames it, so that a crash
+/// can't leave a partial ID in the file.
+pub(crate) fn write_client_id(path: &Path, id: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, id)?;
+    fs::rename(&tmp, path)
+}
+
 /////////////////////////////////////////////////////////////////////////////
 //                              Unit Tests
 /////////////////////////////////////////////////////////////////////////////
@@ -601,4 +694,36 @@ mod tests {
         assert!(0 != opts.copts.sendWhileDisconnected);
         assert_eq!(MAX_BUF_MSGS, opts.copts.maxBufferedMessages);
     }
+
+    #[test]
+    fn test_client_id_file() {
+        let path = std::env::temp_dir().join(format!("paho-client-id-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        // No file yet, so the ID stays empty, with no persistence
+        let opts = CreateOptionsBuilder::new()
+            .persistence(PersistenceType::File)
+            .client_id_file(&path)
+            .finalize();
+        assert_eq!("", &opts.client_id);
+        assert!(matches!(opts.persistence, PersistenceType::None));
+
+        write_client_id(&path, "auto-1234").unwrap();
+
+        let opts = CreateOptionsBuilder::new()
+            .persistence(PersistenceType::File)
+            .client_id_file(&path)
+            .finalize();
+        assert_eq!("auto-1234", &opts.client_id);
+        assert!(matches!(opts.persistence, PersistenceType::File));
+
+        // An explicit ID takes precedence over the file
+        let opts = CreateOptionsBuilder::new()
+            .client_id("explicit")
+            .client_id_file(&path)
+            .finalize();
+        assert_eq!("explicit", &opts.client_id);
+
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -28,10 +28,12 @@
 use std::time::Duration;
 
 use crate::{
+    errors::{Error, Result},
     ffi,
-    properties::Properties,
+    properties::{PacketType, Properties, PropertyCode},
     reason_code::ReasonCode,
     token::{Token, TokenInner},
+    user_properties::UserProperties,
 };
 
 /// The collection of options for disconnecting from the client.
@@ -73,6 +75,18 @@ impl DisconnectOptions {
     pub fn properties(&self) -> &Properties {
         &self.props
     }
+
+    /// Gets the MQTT v5 user properties in the options.
+    pub fn user_properties(&self) -> UserProperties {
+        self.props.user_properties()
+    }
+
+    /// Gets the MQTT v5 session expiry interval in the options, if any.
+    pub fn session_expiry_interval(&self) -> Option<Duration> {
+        self.props
+            .get_int(PropertyCode::SessionExpiryInterval)
+            .map(|secs| Duration::from_secs(secs as u32 as u64))
+    }
 }
 
 impl Default for DisconnectOptions {
@@ -102,6 +116,8 @@ unsafe impl Sync for DisconnectOptions {}
 pub struct DisconnectOptionsBuilder {
     copts: ffi::MQTTAsync_disconnectOptions,
     props: Properties,
+    /// The first error from a setter, for try_finalize() to report
+    err: Option<Error>,
 }
 
 impl DisconnectOptionsBuilder {
@@ -142,6 +158,10 @@ impl DisconnectOptionsBuilder {
 
     /// Sets the collection of properties for the disconnect.
     ///
+    /// The properties are checked against the ones allowed in a DISCONNECT
+    /// packet by [`try_finalize()`](Self::try_finalize), and when
+    /// disconnecting.
+    ///
     /// # Arguments
     ///
     /// `props` The collection of properties to include with the
@@ -151,10 +171,55 @@ impl DisconnectOptionsBuilder {
         self
     }
 
+    /// Sets the collection of properties for the disconnect, checking
+    /// that they are allowed in a DISCONNECT packet.
+    pub fn try_properties(&mut self, props: Properties) -> Result<&mut Self> {
+        props.validate(PacketType::Disconnect)?;
+        Ok(self.properties(props))
+    }
+
+    /// Sets the MQTT v5 user properties for the disconnect, replacing any
+    /// in the properties already set, and keeping the others.
+    pub fn user_properties(&mut self, user_props: &UserProperties) -> &mut Self {
+        self.props.set_user_properties(user_props);
+        self
+    }
+
+    /// Sets the MQTT v5 session expiry interval, to change the one given
+    /// when connecting.
+    ///
+    /// The spec does not allow a non-zero interval here if the interval
+    /// was zero, or not set, when connecting. The client checks this, and
+    /// fails the disconnect if it happens. The interval has a resolution
+    /// of one second, and saturates at `u32::MAX` seconds.
+    pub fn session_expiry_interval(&mut self, expiry: Duration) -> &mut Self {
+        let secs = u32::try_from(expiry.as_secs()).unwrap_or(u32::MAX);
+        self.props.remove_all(PropertyCode::SessionExpiryInterval);
+        if let Err(err) = self.props.push_u32(PropertyCode::SessionExpiryInterval, secs) {
+            self.err.get_or_insert(err);
+        }
+        self
+    }
+
     /// Finalize the builder to create the disconnect options.
     pub fn finalize(&self) -> DisconnectOptions {
         DisconnectOptions::from_data(self.copts, self.props.clone())
     }
+
+    /// Finalize the builder to create the disconnect options, checking
+    /// that the properties are valid for the DISCONNECT packet.
+    ///
+    /// This also fails with the first error from a setter that couldn't
+    /// set a property, like
+    /// [`session_expiry_interval()`](Self::session_expiry_interval). The
+    /// error is only reported once.
+    pub fn try_finalize(&mut self) -> Result<DisconnectOptions> {
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+        self.props.validate(PacketType::Disconnect)?;
+        Ok(self.finalize())
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -260,4 +325,64 @@ mod tests {
             Some("causeIwanna".to_string())
         );
     }
+
+    #[test]
+    fn test_try_finalize_properties() {
+        let mut props = Properties::new();
+        props
+            .push_int(PropertyCode::SessionExpiryInterval, 1000)
+            .unwrap();
+        props
+            .push_int(PropertyCode::SessionExpiryInterval, 2000)
+            .unwrap();
+
+        assert!(DisconnectOptionsBuilder::new()
+            .try_properties(props.clone())
+            .is_err());
+
+        let res = DisconnectOptionsBuilder::new().properties(props).try_finalize();
+        assert!(matches!(
+            res,
+            Err(crate::Error::DuplicateProperty(
+                PropertyCode::SessionExpiryInterval,
+                PacketType::Disconnect
+            ))
+        ));
+
+        let mut props = Properties::new();
+        props
+            .push_val(PropertyCode::ReasonString, "causeIwanna")
+            .unwrap();
+        let res = DisconnectOptionsBuilder::new().properties(props).try_finalize();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_user_properties() {
+        let user_props: UserProperties = [("why", "shutdown"), ("why", "upgrade")]
+            .into_iter()
+            .collect();
+
+        let opts = DisconnectOptionsBuilder::new()
+            .user_properties(&user_props)
+            .finalize();
+
+        assert_eq!(opts.user_properties(), user_props);
+        assert_eq!(opts.properties().len(), 2);
+    }
+
+    #[test]
+    fn test_session_expiry_interval() {
+        let opts = DisconnectOptions::new();
+        assert_eq!(opts.session_expiry_interval(), None);
+
+        let opts = DisconnectOptionsBuilder::new()
+            .session_expiry_interval(Duration::from_secs(60))
+            .session_expiry_interval(Duration::ZERO)
+            .try_finalize()
+            .unwrap();
+
+        assert_eq!(opts.session_expiry_interval(), Some(Duration::ZERO));
+        assert_eq!(opts.properties().len(), 1);
+    }
 }
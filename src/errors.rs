@@ -21,7 +21,13 @@
  *    Frank Pagliughi - initial implementation and documentation
  *******************************************************************************/
 
-use crate::{ffi, message::Message, reason_code::ReasonCode};
+use crate::{
+    ffi,
+    message::Message,
+    properties::{PacketType, Properties, PropertyCode},
+    reason_code::ReasonCode,
+    user_properties::UserProperties,
+};
 use std::{fmt, io, result, str};
 use thiserror::Error;
 
@@ -84,6 +90,182 @@ impl TryFrom<u8> for ConnectReturnCode {
     }
 }
 
+/// The specific rule of the MQTT spec violated by an invalid topic name
+/// or filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TopicErrorKind {
+    /// The topic is an empty string.
+    Empty,
+    /// The topic is longer than 65535 bytes when UTF-8 encoded.
+    TooLong,
+    /// The topic contains a NUL character, U+0000.
+    NulChar,
+    /// A topic name contains a wildcard character, '+' or '#'.
+    WildcardInName,
+    /// A wildcard in a filter does not take up the entire level.
+    PartialWildcard,
+    /// A multi-level wildcard, '#', is not the last level of a filter.
+    MisplacedMultiLevel,
+    /// The share name of a shared subscription is empty or contains a
+    /// wildcard.
+    BadShareName,
+    /// A shared subscription is missing the topic filter after the share
+    /// name.
+    MissingShareFilter,
+}
+
+impl fmt::Display for TopicErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TopicErrorKind::*;
+        let msg = match *self {
+            Empty => "Topic is empty",
+            TooLong => "Topic is longer than 65535 bytes",
+            NulChar => "Topic contains a NUL character",
+            WildcardInName => "Topic name contains a wildcard",
+            PartialWildcard => "Wildcard does not occupy an entire level",
+            MisplacedMultiLevel => "Multi-level wildcard is not the last level",
+            BadShareName => "Invalid share name",
+            MissingShareFilter => "Shared subscription is missing a topic filter",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+/// An error validating a topic name or filter.
+///
+/// This contains the rule that was broken and, when it applies to a
+/// single level, the (zero-based) index and contents of the offending
+/// level of the topic.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub struct TopicError {
+    kind: TopicErrorKind,
+    level: Option<(usize, String)>,
+}
+
+impl TopicError {
+    /// Creates an error for a rule that applies to the whole topic.
+    pub fn new(kind: TopicErrorKind) -> Self {
+        Self { kind, level: None }
+    }
+
+    /// Creates an error for a rule broken by a specific level of the topic.
+    pub fn at_level<S: Into<String>>(kind: TopicErrorKind, idx: usize, field: S) -> Self {
+        Self {
+            kind,
+            level: Some((idx, field.into())),
+        }
+    }
+
+    /// Gets the rule that was broken.
+    pub fn kind(&self) -> TopicErrorKind {
+        self.kind
+    }
+
+    /// Gets the zero-based index of the offending level, if any.
+    pub fn level(&self) -> Option<usize> {
+        self.level.as_ref().map(|(idx, _)| *idx)
+    }
+
+    /// Gets the contents of the offending level, if any.
+    pub fn field(&self) -> Option<&str> {
+        self.level.as_ref().map(|(_, field)| field.as_str())
+    }
+}
+
+impl fmt::Display for TopicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            Some((idx, ref field)) => write!(f, "{} (level {}: '{}')", self.kind, idx, field),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// An MQTT v5 failure reported by the server.
+///
+/// This is the reason code from the acknowledgment or disconnect packet
+/// sent by the server, along with any diagnostics that the server included
+/// in the properties of the packet: the Reason String and User Properties.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    packet_type: PacketType,
+    reason_code: ReasonCode,
+    reason_string: Option<String>,
+    server_reference: Option<String>,
+    user_props: UserProperties,
+}
+
+impl ServerError {
+    /// Creates an error for a failure reason code, without diagnostics.
+    pub fn new(packet_type: PacketType, reason_code: ReasonCode) -> Self {
+        Self {
+            packet_type,
+            reason_code,
+            reason_string: None,
+            server_reference: None,
+            user_props: UserProperties::new(),
+        }
+    }
+
+    /// Creates an error for a failure reason code, taking the reason
+    /// string, server reference, and user properties from the properties
+    /// of the packet.
+    ///
+    /// This can be used with the reason code and properties passed to the
+    /// client's disconnected callback when the server sends a DISCONNECT.
+    pub fn from_properties(
+        packet_type: PacketType,
+        reason_code: ReasonCode,
+        props: &Properties,
+    ) -> Self {
+        Self {
+            packet_type,
+            reason_code,
+            reason_string: props.get_string(PropertyCode::ReasonString),
+            server_reference: props.get_string(PropertyCode::ServerReference),
+            user_props: props.user_properties(),
+        }
+    }
+
+    /// Gets the type of packet in which the server reported the failure,
+    /// such as CONNACK, SUBACK, or DISCONNECT.
+    pub fn packet_type(&self) -> PacketType {
+        self.packet_type
+    }
+
+    /// Gets the reason code sent by the server.
+    pub fn reason_code(&self) -> ReasonCode {
+        self.reason_code
+    }
+
+    /// Gets the human-readable reason string sent by the server, if any.
+    pub fn reason_string(&self) -> Option<&str> {
+        self.reason_string.as_deref()
+    }
+
+    /// Gets the Server Reference sent by the server with a redirect, if
+    /// any. This names one or more other servers that the client should
+    /// use, separated by spaces.
+    pub fn server_reference(&self) -> Option<&str> {
+        self.server_reference.as_deref()
+    }
+
+    /// Gets the user properties sent by the server with the failure.
+    pub fn user_properties(&self) -> &UserProperties {
+        &self.user_props
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}", self.reason_code, self.packet_type)?;
+        if let Some(ref reason) = self.reason_string {
+            write!(f, ": {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
 /// The errors from an MQTT operation.
 #[derive(Error, Debug)]
 #[allow(missing_docs)]
@@ -171,6 +353,10 @@ pub enum Error {
     /// A socket error occurred
     #[error("Socket error")]
     SocketError(i32),
+    /// A failed connection, with the last error reported by the SSL/TLS
+    /// library, such as a certificate that failed verification.
+    #[error("{0}: {1}")]
+    Ssl(Box<Error>, String),
     // An MQTT v3 connect return (failure) code
     #[error("(0)")]
     ConnectReturn(ConnectReturnCode),
@@ -184,9 +370,30 @@ pub enum Error {
     /// An MQTT v5 error from a reason code.
     #[error("{0}")]
     ReasonCode(ReasonCode),
+    /// An MQTT v5 failure reported by the server, with its diagnostics.
+    #[error("{0}")]
+    Server(#[from] ServerError),
     /// A bad topic filter
     #[error("Bad topic filter")]
     BadTopicFilter,
+    /// A topic name or filter that is invalid according to the MQTT spec
+    #[error("{0}")]
+    BadTopic(#[from] TopicError),
+    /// An MQTT v5 property that is not allowed in the packet
+    #[error("Property {0:?} is not allowed in the {1} properties")]
+    PropertyNotAllowed(PropertyCode, PacketType),
+    /// An MQTT v5 property that can only appear once in the packet
+    #[error("Property {0:?} can only appear once in the {1} properties")]
+    DuplicateProperty(PropertyCode, PacketType),
+    /// A topic that is outside the namespace of a `NamespacedClient`
+    #[error("Topic '{0}' is outside the namespace")]
+    NamespaceEscape(String),
+    /// A server URI that could not be parsed, with the reason
+    #[error("Bad server URI '{0}': {1}")]
+    BadServerUri(String, &'static str),
+    /// An invalid client configuration
+    #[error("Configuration error: {0}")]
+    Config(String),
     /// An low-level I/O error
     #[error("I/O failed: {0}")]
     Io(#[from] io::Error),
@@ -210,6 +417,68 @@ pub enum Error {
     GeneralString(String),
 }
 
+impl Error {
+    /// Gets the MQTT v5 reason code for the error, if it came from one.
+    pub fn reason_code(&self) -> Option<ReasonCode> {
+        match self {
+            Error::ReasonCode(rc) => Some(*rc),
+            Error::Server(err) => Some(err.reason_code()),
+            Error::Ssl(err, _) => err.reason_code(),
+            _ => None,
+        }
+    }
+
+    /// Gets the last error reported by the SSL/TLS library for a failed
+    /// connection, if any.
+    pub fn ssl_error(&self) -> Option<&str> {
+        match self {
+            Error::Ssl(_, msg) => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Determines if the failure is likely to be temporary, so that the
+    /// operation might succeed if retried later.
+    ///
+    /// This covers network failures and timeouts, the server being
+    /// unavailable, and retryable MQTT v5 reason codes. A failure with an
+    /// error from the SSL/TLS library is classified by the failure that
+    /// it's attached to.
+    pub fn is_retryable(&self) -> bool {
+        use Error::*;
+        match self {
+            Ssl(err, _) => err.is_retryable(),
+            Disconnected
+            | TcpConnectTimeout
+            | TcpConnectCompletionFailure
+            | TcpTlsConnectFailure
+            | SocketError(_)
+            | ReceivedDisconnect(_)
+            | Timeout
+            | Io(_)
+            | ConnectReturn(ConnectReturnCode::ServerUnavailable) => true,
+            _ => self.reason_code().map_or(false, |rc| rc.is_retryable()),
+        }
+    }
+
+    /// Determines if the server rejected the client's credentials or
+    /// permissions.
+    pub fn is_auth_failure(&self) -> bool {
+        match self {
+            Error::ConnectReturn(ConnectReturnCode::BadUserNameOrPassword)
+            | Error::ConnectReturn(ConnectReturnCode::NotAuthorized) => true,
+            _ => self.reason_code().map_or(false, |rc| rc.is_auth_failure()),
+        }
+    }
+
+    /// Determines if the client exceeded a quota or rate limit imposed by
+    /// the server.
+    pub fn is_quota_exceeded(&self) -> bool {
+        self.reason_code()
+            .map_or(false, |rc| rc.is_quota_exceeded())
+    }
+}
+
 impl From<i32> for Error {
     /// Create an error from a Paho C return code.
     fn from(rc: i32) -> Error {
@@ -354,4 +623,89 @@ mod tests {
         let err = Error::from((ffi::MQTTASYNC_FAILURE, "TCP connect timeout"));
         assert!(matches!(err, Error::TcpConnectTimeout));
     }
+
+    #[test]
+    fn test_topic_error() {
+        let err = TopicError::at_level(TopicErrorKind::PartialWildcard, 1, "a+");
+        assert_eq!(err.level(), Some(1));
+        assert_eq!(err.field(), Some("a+"));
+        assert_eq!(
+            err.to_string(),
+            "Wildcard does not occupy an entire level (level 1: 'a+')"
+        );
+
+        let err = Error::from(TopicError::new(TopicErrorKind::Empty));
+        assert!(matches!(err, Error::BadTopic(e) if e.kind() == TopicErrorKind::Empty));
+    }
+
+    #[test]
+    fn test_server_error() {
+        let mut props = Properties::new();
+        props
+            .push_string(PropertyCode::ReasonString, "Tenant over budget")
+            .unwrap();
+        props
+            .push_string_pair(PropertyCode::UserProperty, "limit", "100")
+            .unwrap();
+
+        let err = ServerError::from_properties(
+            PacketType::Puback,
+            ReasonCode::QuotaExceeded,
+            &props,
+        );
+        assert_eq!(err.packet_type(), PacketType::Puback);
+        assert_eq!(err.reason_code(), ReasonCode::QuotaExceeded);
+        assert_eq!(err.reason_string(), Some("Tenant over budget"));
+        assert_eq!(err.user_properties().get("limit"), Some("100"));
+
+        let err = Error::from(err);
+        assert_eq!(err.reason_code(), Some(ReasonCode::QuotaExceeded));
+        assert!(err.is_quota_exceeded());
+        assert!(err.is_retryable());
+        assert!(!err.is_auth_failure());
+
+        let err = Error::from(ServerError::new(
+            PacketType::Connack,
+            ReasonCode::NotAuthorized,
+        ));
+        assert!(err.is_auth_failure());
+        assert!(!err.is_retryable());
+        assert!(ServerError::new(PacketType::Suback, ReasonCode::NotAuthorized)
+            .reason_string()
+            .is_none());
+
+        let mut props = Properties::new();
+        props
+            .push_string(PropertyCode::ServerReference, "broker2:1883")
+            .unwrap();
+        let err =
+            ServerError::from_properties(PacketType::Connack, ReasonCode::ServerMoved, &props);
+        assert_eq!(err.server_reference(), Some("broker2:1883"));
+        assert!(Error::from(err).is_retryable());
+    }
+
+    #[test]
+    fn test_ssl_error() {
+        let msg = "error:0A000086:SSL routines::certificate verify failed";
+        let err = Error::Ssl(Box::new(Error::TcpTlsConnectFailure), msg.to_string());
+        assert_eq!(err.ssl_error(), Some(msg));
+        assert_eq!(err.to_string(), format!("TCP/TLS connect failure: {}", msg));
+        assert!(err.is_retryable());
+        assert!(!Error::Ssl(Box::new(Error::BadStructure), msg.to_string()).is_retryable());
+        assert!(Error::TcpTlsConnectFailure.ssl_error().is_none());
+    }
+
+    #[test]
+    fn test_error_classification() {
+        assert!(Error::TcpConnectTimeout.is_retryable());
+        assert!(Error::ConnectReturn(ConnectReturnCode::ServerUnavailable).is_retryable());
+        assert!(!Error::BadQos.is_retryable());
+
+        assert!(Error::ConnectReturn(ConnectReturnCode::NotAuthorized).is_auth_failure());
+        assert!(Error::ReasonCode(ReasonCode::BadUserNameOrPassword).is_auth_failure());
+        assert!(!Error::Timeout.is_auth_failure());
+
+        assert!(!Error::Timeout.is_quota_exceeded());
+        assert_eq!(Error::Timeout.reason_code(), None);
+    }
 }
@@ -0,0 +1,463 @@
+// failover.rs
+//
+// Strategies for choosing among multiple servers.
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! Strategies for choosing among multiple servers.
+//!
+//! When the connect options have a list of servers, set with
+//! [`ConnectOptionsBuilder::server_uris()`](crate::ConnectOptionsBuilder::server_uris),
+//! the C library tries them in order on each connection attempt, and stays
+//! with whichever one accepts the connection.
+//!
+//! A [`FailoverStrategy`] set with
+//! [`AsyncClient::set_failover_strategy()`](crate::AsyncClient::set_failover_strategy)
+//! reorders the list before each call to
+//! [`connect()`](crate::AsyncClient::connect) or
+//! [`reconnect()`](crate::AsyncClient::reconnect), including the ones
+//! made by a [`ReconnectPolicy`](crate::ReconnectPolicy). It does not apply
+//! to the automatic reconnects done by the C library.
+//!
+//! The client keeps track of the server that it's connected to, from the
+//! [`ConnectResponse`](crate::ConnectResponse), along with the
+//! [`ServerStats`] for each server.
+
+use crate::{reconnect::random_u64, server_uri::ServerUri};
+use std::{
+    mem,
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, SystemTime},
+};
+
+/// The strategy for choosing among multiple servers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FailoverStrategy {
+    /// Try the servers in the order given, on each connection attempt.
+    /// This is what the C library does on its own.
+    #[default]
+    Ordered,
+    /// Start each connection attempt with the next server in the list,
+    /// so the attempts rotate through the servers.
+    RoundRobin,
+    /// Try the servers in a random order on each connection attempt, to
+    /// spread clients across the servers.
+    Random,
+    /// Try the servers in the order given, and while connected to any
+    /// server other than the first, the primary, check whether the primary
+    /// can be reached at the interval. Once it can, the client disconnects
+    /// and reconnects, to get back to the primary.
+    ///
+    /// The check is only that a TCP connection can be made to the primary.
+    StickyPrimary {
+        /// The time between checks of the primary server
+        probe_interval: Duration,
+    },
+}
+
+/// The connection statistics for one server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    pub(crate) uri: String,
+    pub(crate) connects: u32,
+    pub(crate) failures: u32,
+    pub(crate) consecutive_failures: u32,
+    pub(crate) last_connect: Option<SystemTime>,
+    pub(crate) last_failure: Option<SystemTime>,
+}
+
+impl ServerStats {
+    fn new(uri: &str) -> Self {
+        Self {
+            uri: uri.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Gets the URI of the server.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Gets the number of successful connections to the server.
+    pub fn connects(&self) -> u32 {
+        self.connects
+    }
+
+    /// Gets the total number of failed attempts to connect to the server.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Gets the number of failed attempts to connect to the server since
+    /// the last successful one.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Gets the time of the last successful connection to the server.
+    pub fn last_connect(&self) -> Option<SystemTime> {
+        self.last_connect
+    }
+
+    /// Gets the time of the last failed attempt to connect to the server.
+    pub fn last_failure(&self) -> Option<SystemTime> {
+        self.last_failure
+    }
+
+    fn connected(&mut self) {
+        self.connects += 1;
+        self.consecutive_failures = 0;
+        self.last_connect = Some(SystemTime::now());
+    }
+
+    fn failed(&mut self) {
+        self.failures += 1;
+        self.consecutive_failures += 1;
+        self.last_failure = Some(SystemTime::now());
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+// The failover strategy of a client, along with the server it's connected
+// to and the statistics for each server.
+#[derive(Debug, Default)]
+pub(crate) struct Failover {
+    strategy: FailoverStrategy,
+    /// The servers, in the order configured
+    servers: Vec<String>,
+    /// The order of the servers for a connection attempt in progress.
+    /// This is empty when the client's own server is being tried.
+    order: Option<Vec<String>>,
+    /// The statistics for every server the client has tried
+    stats: Vec<ServerStats>,
+    /// The starting point in the list, for round robin
+    next: usize,
+    /// The server that the client is connected to, if any
+    current: Option<String>,
+    /// Whether a thread is checking the primary server
+    probing: bool,
+    /// The generation of the thread checking the primary server.
+    /// This changes when the probe is stopped, so the thread quits.
+    probe_gen: u64,
+    /// Whether the client is reconnecting to get back to the primary
+    switching: bool,
+}
+
+impl Failover {
+    // Gets the strategy.
+    pub(crate) fn strategy(&self) -> FailoverStrategy {
+        self.strategy
+    }
+
+    // Sets the strategy.
+    pub(crate) fn set_strategy(&mut self, strategy: FailoverStrategy) {
+        self.strategy = strategy;
+        self.next = 0;
+    }
+
+    // Orders the servers from the connect options for the next connection
+    // attempt. Options with the same servers in another order, as when
+    // reconnecting, are taken to be the same list.
+    pub(crate) fn order(&mut self, uris: Vec<String>) -> Vec<String> {
+        let mut sorted = uris.clone();
+        sorted.sort();
+        let mut servers = self.servers.clone();
+        servers.sort();
+        if sorted != servers {
+            self.servers = uris;
+            self.next = 0;
+        }
+
+        let mut order = self.servers.clone();
+        let n = order.len();
+        if n > 1 {
+            match self.strategy {
+                FailoverStrategy::Ordered | FailoverStrategy::StickyPrimary { .. } => (),
+                FailoverStrategy::RoundRobin => {
+                    order.rotate_left(self.next % n);
+                    self.next = (self.next + 1) % n;
+                }
+                FailoverStrategy::Random => {
+                    for i in (1..n).rev() {
+                        order.swap(i, (random_u64() % (i as u64 + 1)) as usize);
+                    }
+                }
+            }
+        }
+        self.order = Some(order.clone());
+        order
+    }
+
+    // Records a successful connection to the server. Any servers ahead of
+    // it in the attempt were tried, and failed.
+    pub(crate) fn connected(&mut self, uri: &str) {
+        let order = self.order.take().unwrap_or_default();
+        if order.iter().any(|s| s == uri) {
+            for server in order.iter().take_while(|s| *s != uri) {
+                self.stats_mut(server).failed();
+            }
+        }
+        self.stats_mut(uri).connected();
+        self.current = Some(uri.to_string());
+    }
+
+    // Records a failed connection attempt, in which all the servers were
+    // tried. With no list of servers, the one the client was created with
+    // was tried.
+    pub(crate) fn failed(&mut self, client_uri: &str) {
+        let tried = match self.order.take() {
+            Some(order) if order.is_empty() => vec![client_uri.to_string()],
+            Some(order) => order,
+            None => Vec::new(),
+        };
+        for server in tried {
+            self.stats_mut(&server).failed();
+        }
+        self.current = None;
+    }
+
+    // Records that the client is no longer connected.
+    pub(crate) fn disconnected(&mut self) {
+        self.current = None;
+    }
+
+    // Gets the server that the client is connected to, if any.
+    pub(crate) fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    // Gets the statistics for every server the client has tried.
+    pub(crate) fn stats(&self) -> Vec<ServerStats> {
+        self.stats.clone()
+    }
+
+    // Gets the primary server to check, and how often, if the strategy is
+    // sticky and the client is connected to another server.
+    pub(crate) fn probe_target(&self) -> Option<(String, Duration)> {
+        match (self.strategy, &self.current, self.servers.first()) {
+            (FailoverStrategy::StickyPrimary { probe_interval }, Some(cur), Some(primary))
+                if cur != primary =>
+            {
+                Some((primary.clone(), probe_interval))
+            }
+            _ => None,
+        }
+    }
+
+    // Starts checking the primary server, if there's one to check and no
+    // check is running. Returns the server, the interval, and the
+    // generation of the probe.
+    pub(crate) fn start_probe(&mut self) -> Option<(String, Duration, u64)> {
+        if self.probing {
+            return None;
+        }
+        let (primary, interval) = self.probe_target()?;
+        self.probing = true;
+        Some((primary, interval, self.probe_gen))
+    }
+
+    // Determines whether the probe of the generation should keep checking
+    // the primary server. It's over once the probe is stopped, or the
+    // client isn't connected to another server.
+    pub(crate) fn keep_probing(&mut self, gen: u64, primary: &str) -> bool {
+        if gen != self.probe_gen || !self.probing {
+            return false;
+        }
+        match self.probe_target() {
+            Some((target, _)) if target == primary => true,
+            _ => {
+                self.probing = false;
+                false
+            }
+        }
+    }
+
+    // Ends the probe of the generation to switch back to the primary
+    // server, if it should still go on. Returns whether to switch.
+    pub(crate) fn start_switch(&mut self, gen: u64, primary: &str) -> bool {
+        if !self.keep_probing(gen, primary) {
+            return false;
+        }
+        self.probing = false;
+        self.switching = true;
+        true
+    }
+
+    // Whether the client is switching back to the primary server.
+    pub(crate) fn is_switching(&self) -> bool {
+        self.switching
+    }
+
+    // Clears the switch back to the primary server, returning whether
+    // there was one.
+    pub(crate) fn take_switching(&mut self) -> bool {
+        mem::take(&mut self.switching)
+    }
+
+    // Stops any check of the primary server and any switch back to it,
+    // as when the application disconnects the client.
+    pub(crate) fn stop_probe(&mut self) {
+        self.probe_gen = self.probe_gen.wrapping_add(1);
+        self.probing = false;
+        self.switching = false;
+    }
+
+    fn stats_mut(&mut self, uri: &str) -> &mut ServerStats {
+        let i = match self.stats.iter().position(|s| s.uri == uri) {
+            Some(i) => i,
+            None => {
+                self.stats.push(ServerStats::new(uri));
+                self.stats.len() - 1
+            }
+        };
+        &mut self.stats[i]
+    }
+}
+
+// Checks whether a TCP connection can be made to the server.
+pub(crate) fn probe_server(uri: &str, timeout: Duration) -> bool {
+    let uri = match ServerUri::new(uri) {
+        Ok(uri) => uri,
+        Err(_) => return false,
+    };
+    match (uri.host(), uri.port()).to_socket_addrs() {
+        Ok(mut addrs) => addrs.any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok()),
+        Err(_) => false,
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn servers() -> Vec<String> {
+        vec!["tcp://a:1883".into(), "tcp://b:1883".into(), "tcp://c:1883".into()]
+    }
+
+    #[test]
+    fn test_ordered() {
+        let mut fo = Failover::default();
+        assert_eq!(fo.order(servers()), servers());
+
+        fo.connected("tcp://b:1883");
+        assert_eq!(fo.current(), Some("tcp://b:1883"));
+
+        let stats = fo.stats();
+        assert_eq!(stats[0].uri(), "tcp://a:1883");
+        assert_eq!(stats[0].failures(), 1);
+        assert_eq!(stats[1].connects(), 1);
+        assert_eq!(stats[1].failures(), 0);
+
+        fo.disconnected();
+        assert_eq!(fo.current(), None);
+
+        fo.order(servers());
+        fo.failed("tcp://x:1883");
+        let stats = fo.stats();
+        assert_eq!(stats[0].failures(), 2);
+        assert_eq!(stats[1].consecutive_failures(), 1);
+        assert_eq!(stats[2].failures(), 1);
+
+        // Each attempt is only counted once
+        fo.failed("tcp://x:1883");
+        assert_eq!(fo.stats()[0].failures(), 2);
+
+        // A server that wasn't in the list, as from a redirect
+        fo.order(servers());
+        fo.connected("tcp://d:1883");
+        assert_eq!(fo.current(), Some("tcp://d:1883"));
+        assert_eq!(fo.stats()[0].failures(), 2);
+        assert_eq!(fo.stats()[3].connects(), 1);
+
+        // Without a list, the client's own server is tried
+        fo.order(Vec::new());
+        fo.failed("tcp://x:1883");
+        assert_eq!(fo.stats()[4].uri(), "tcp://x:1883");
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut fo = Failover::default();
+        fo.set_strategy(FailoverStrategy::RoundRobin);
+
+        let first: Vec<_> = (0..4).map(|_| fo.order(servers())[0].clone()).collect();
+        assert_eq!(
+            first,
+            ["tcp://a:1883", "tcp://b:1883", "tcp://c:1883", "tcp://a:1883"]
+        );
+
+        // The reordered list, as from a reconnect, is the same servers
+        let order = fo.order(servers());
+        assert_eq!(fo.order(order)[0], "tcp://c:1883");
+    }
+
+    #[test]
+    fn test_random() {
+        let mut fo = Failover::default();
+        fo.set_strategy(FailoverStrategy::Random);
+
+        let mut order = fo.order(servers());
+        order.sort();
+        assert_eq!(order, servers());
+    }
+
+    #[test]
+    fn test_sticky_primary() {
+        let probe_interval = Duration::from_secs(30);
+
+        let mut fo = Failover::default();
+        fo.set_strategy(FailoverStrategy::StickyPrimary { probe_interval });
+        assert_eq!(fo.order(servers()), servers());
+
+        fo.connected("tcp://a:1883");
+        assert_eq!(fo.probe_target(), None);
+
+        fo.order(servers());
+        fo.connected("tcp://c:1883");
+        assert_eq!(
+            fo.probe_target(),
+            Some(("tcp://a:1883".to_string(), probe_interval))
+        );
+
+        let (primary, _, gen) = fo.start_probe().unwrap();
+        assert!(fo.start_probe().is_none());
+        assert!(fo.keep_probing(gen, &primary));
+
+        // A disconnect stops the probe, and a new one gets a new generation
+        fo.stop_probe();
+        fo.disconnected();
+        assert!(!fo.start_switch(gen, &primary));
+        assert!(!fo.is_switching());
+
+        fo.order(servers());
+        fo.connected("tcp://c:1883");
+        let (primary, _, new_gen) = fo.start_probe().unwrap();
+        assert_ne!(new_gen, gen);
+        assert!(!fo.keep_probing(gen, &primary));
+        assert!(fo.start_switch(new_gen, &primary));
+        assert!(fo.take_switching());
+        assert!(!fo.is_switching());
+    }
+}
@@ -46,6 +46,19 @@
 //! * **vendored-ssl**
 //!   Download an build OpenSSL for the target.
 //!
+//! * **serde**
+//!   Support for (de)serializing library types with serde. This covers
+//!   `TopicFilter`, `TopicMatcher` (as a map of filters to values), `QoS`,
+//!   `MqttVersion`, `SubscribeOptions`, `RetainHandling`, `Properties`
+//!   (as a map of property names to values), and `Message`, and allows
+//!   extracting the fields of a `TopicTemplate` into a struct. Binary
+//!   values, like message payloads, are base64-encoded.
+//!
+//! * **config**
+//!   A unified `ClientConfig` that can be loaded from TOML, environment
+//!   variables, or a URL, to create and connect a client. This enables
+//!   `serde`.
+//!
 //! # Logging
 //!
 //! The library uses the standard Rust log facility with the target/module
@@ -86,24 +99,41 @@ pub use async_channel::Receiver as AsyncReceiver;
 pub use crossbeam_channel::Receiver;
 
 pub use crate::async_client::*; //{AsyncClient, AsyncClientBuilder};
+#[cfg(feature = "ssl")]
+pub use crate::cert_provider::{CertificateProvider, CertificateSet, ExpiryCallback};
 pub use crate::client::*; //{Client, ClientBuilder};
+#[cfg(feature = "config")]
+pub use crate::client_config::{
+    ClientConfig, PersistenceConfig, ReconnectConfig, SslConfig, WillConfig,
+};
 pub use crate::client_persistence::*;
 pub use crate::connect_options::*; //{ConnectOptions, ConnectOptionsBuilder, MQTT_VERSION_3_1_1, ...};
 pub use crate::create_options::*; //{CreateOptions, CreateOptionsBuilder};
 pub use crate::disconnect_options::*; //{DisconnectOptions, DisconnectOptionsBuilder};
 pub use crate::errors::*;
+pub use crate::failover::{FailoverStrategy, ServerStats};
 pub use crate::message::*; //{Message, MessageBuilder};
 pub use crate::name_value::*; //{NameValueCollection};
+pub use crate::namespaced_client::*; //{NamespaceRouter, NamespacedClient};
+pub use crate::presence::PresenceOptions;
 pub use crate::properties::*; //{Property, Properties};
+pub use crate::quota_persistence::*; //{QuotaPersistence, EvictionPolicy};
 pub use crate::reason_code::*; //{ReasonCode}
+pub use crate::reconnect::*; //{ReconnectPolicy, ExponentialBackoff, ...};
+pub use crate::redirect::*; //{RedirectPolicy, ServerRedirect};
+pub use crate::retained_cache::*; //{RetainedCache, CacheEvent};
 pub use crate::response_options::*; //{ResponseOptions};
 pub use crate::server_response::*; //{ServerResponse, CommandResponse};
+pub use crate::server_uri::*; //{ServerUri, Scheme};
+pub use crate::shared_topic_matcher::SharedTopicMatcher;
 pub use crate::ssl_options::*; //{SslOptions, SslOptionsBuilder};
 pub use crate::subscribe_options::*; //{SubscribeOptions};
 pub use crate::token::*; //{Token}
 pub use crate::topic::*; //{Topic, TopicFilter, TopicMatcher};
 pub use crate::topic_matcher::TopicMatcher;
+pub use crate::topic_template::TopicTemplate;
 pub use crate::types::*; //...
+pub use crate::user_properties::UserProperties;
 pub use crate::will_options::*; //{WillOptions, WillOptionsBuilder}; //{Result, Error, ErrorKind};
 
 use std::{any::Any, os::raw::c_int};
@@ -122,12 +152,27 @@ pub mod create_options;
 /// Options for connecting to the server.
 pub mod connect_options;
 
+/// Typed, validated server URIs.
+pub mod server_uri;
+
+/// A unified client configuration, loaded from a file, the environment,
+/// or a URL.
+#[cfg(feature = "config")]
+pub mod client_config;
+
 /// Connect options for the Last Will and Testament (LWT) message.
 pub mod will_options;
 
 /// Connect options for creating secure connections to the server.
 pub mod ssl_options;
 
+/// Client certificates that are reloaded before each connection.
+#[cfg(feature = "ssl")]
+pub mod cert_provider;
+
+// In-memory certificates and keys for secure connections.
+mod ssl_secret;
+
 /// Options for disconnecting from the server.
 pub mod disconnect_options;
 
@@ -143,6 +188,9 @@ pub mod server_response;
 /// MQTT 5v properties.
 pub mod properties;
 
+/// MQTT v5 user properties, as an ordered multimap.
+pub mod user_properties;
+
 /// The message object
 pub mod message;
 
@@ -156,15 +204,42 @@ pub mod topic;
 // Topic matcher struct
 pub mod topic_matcher;
 
+/// Topic templates with named fields.
+pub mod topic_template;
+
+/// A topic matcher that can be shared between threads.
+pub mod shared_topic_matcher;
+
+/// A cache of the last message received on each topic.
+pub mod retained_cache;
+
 /// MQTT v5 Reason Codes
 pub mod reason_code;
 
+/// Following MQTT v5 server redirects.
+pub mod redirect;
+
+/// Policies for reconnecting when the connection is lost.
+pub mod reconnect;
+
+/// Strategies for choosing among multiple servers.
+pub mod failover;
+
+/// Birth and death messages announcing whether a client is online.
+pub mod presence;
+
 /// Miscelaneous types
 pub mod types;
 
 /// Definitions for creating user-defined persistence.
 pub mod client_persistence;
 
+/// Persistence wrapper to limit the size of the store.
+pub mod quota_persistence;
+
+/// Namespaced client views for multi-tenant applications.
+pub mod namespaced_client;
+
 /// The MQTT errors
 pub mod errors;
 
@@ -26,10 +26,44 @@ use std::{
     fmt,
     os::raw::{c_int, c_void},
     pin::Pin,
-    slice,
+    slice, str,
+    time::Duration,
 };
 
-use crate::{ffi, properties::Properties, to_c_bool, QoS};
+use crate::{
+    errors::{Error, Result},
+    ffi,
+    properties::{Binary, PacketType, Properties, Property, PropertyCode},
+    to_c_bool,
+    topic::validate_topic_name,
+    user_properties::UserProperties,
+    QoS,
+};
+
+/// The format of a message payload, from the MQTT v5 Payload Format
+/// Indicator property.
+#[repr(u8)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PayloadFormat {
+    /// The payload is unspecified bytes.
+    /// This is the same as not sending the indicator.
+    #[default]
+    Unspecified = 0,
+    /// The payload is UTF-8 encoded character data.
+    Utf8 = 1,
+}
+
+impl TryFrom<u8> for PayloadFormat {
+    type Error = Error;
+
+    fn try_from(val: u8) -> Result<Self> {
+        match val {
+            0 => Ok(PayloadFormat::Unspecified),
+            1 => Ok(PayloadFormat::Utf8),
+            _ => Err(Error::Conversion),
+        }
+    }
+}
 
 /// A `Message` represents all the information passed in an MQTT PUBLISH
 /// packet.
@@ -185,6 +219,85 @@ impl Message {
     pub fn properties(&self) -> &Properties {
         &self.data.props
     }
+
+    /// Gets the MQTT v5 content type of the payload, if any.
+    pub fn content_type(&self) -> Option<String> {
+        self.data.props.get_string(PropertyCode::ContentType)
+    }
+
+    /// Gets the MQTT v5 topic for a response to the message, if any.
+    pub fn response_topic(&self) -> Option<String> {
+        self.data.props.get_string(PropertyCode::ResponseTopic)
+    }
+
+    /// Gets the MQTT v5 correlation data for a request/response, if any.
+    pub fn correlation_data(&self) -> Option<Binary> {
+        self.data.props.get_binary(PropertyCode::CorrelationData)
+    }
+
+    /// Gets the MQTT v5 message expiry interval, if any.
+    ///
+    /// For an incoming message, this is the time remaining before the
+    /// message would have expired on the server.
+    pub fn message_expiry_interval(&self) -> Option<Duration> {
+        self.data
+            .props
+            .get_int(PropertyCode::MessageExpiryInterval)
+            .map(|secs| Duration::from_secs(secs as u32 as u64))
+    }
+
+    /// Gets the MQTT v5 payload format indicator.
+    ///
+    /// If the message doesn't have the property, this is
+    /// `PayloadFormat::Unspecified`. If the indicator says the payload is
+    /// UTF-8 text, the payload is checked, and an error is returned if it
+    /// is not valid UTF-8.
+    pub fn payload_format_indicator(&self) -> Result<PayloadFormat> {
+        let fmt = match self.data.props.get_int(PropertyCode::PayloadFormatIndicator) {
+            Some(val) => PayloadFormat::try_from(val as u8)?,
+            None => PayloadFormat::Unspecified,
+        };
+        if fmt == PayloadFormat::Utf8 {
+            str::from_utf8(self.payload())?;
+        }
+        Ok(fmt)
+    }
+
+    /// Gets the MQTT v5 subscription identifiers for an incoming message.
+    ///
+    /// These are the identifiers of all the subscriptions that matched the
+    /// message on the server.
+    pub fn subscription_identifiers(&self) -> Vec<u32> {
+        self.data
+            .props
+            .iter(PropertyCode::SubscriptionIdentifier)
+            .filter_map(|prop| prop.get_int())
+            .map(|id| id as u32)
+            .collect()
+    }
+
+    /// Gets the MQTT v5 user properties of the message, in order.
+    pub fn user_properties(&self) -> UserProperties {
+        self.data.props.user_properties()
+    }
+
+    /// Gets the MQTT v5 topic alias of the message, if any.
+    pub fn topic_alias(&self) -> Option<u16> {
+        self.data
+            .props
+            .get_int(PropertyCode::TopicAlias)
+            .map(|alias| alias as u16)
+    }
+
+    // Checks that the topic is a valid topic name.
+    // A v5 message can have an empty topic if it uses a topic alias.
+    pub(crate) fn validate_topic(&self) -> Result<()> {
+        let topic = self.topic();
+        if !topic.is_empty() || self.topic_alias().is_none() {
+            validate_topic_name(topic)?;
+        }
+        Ok(())
+    }
 }
 
 impl Default for Message {
@@ -246,6 +359,8 @@ pub struct MessageBuilder {
     qos: QoS,
     retained: bool,
     props: Properties,
+    // The first error from setting a property, if any
+    err: Option<Error>,
 }
 
 impl MessageBuilder {
@@ -257,6 +372,7 @@ impl MessageBuilder {
             qos: QoS::default(),
             retained: false,
             props: Properties::default(),
+            err: None,
         }
     }
 
@@ -310,6 +426,10 @@ impl MessageBuilder {
 
     /// Sets the collection of properties for the message.
     ///
+    /// The properties are checked against the ones allowed in a PUBLISH
+    /// packet by [`try_finalize()`](Self::try_finalize), and when the
+    /// message is published.
+    ///
     /// # Arguments
     ///
     /// `props` The collection of properties to include with the message.
@@ -318,8 +438,174 @@ impl MessageBuilder {
         self
     }
 
+    /// Sets the collection of properties for the message, checking that
+    /// the client can send them in a PUBLISH packet.
+    pub fn try_properties(self, props: Properties) -> Result<Self> {
+        props.validate(PacketType::Publish)?;
+        Ok(self.properties(props))
+    }
+
+    /// Sets the MQTT v5 content type of the payload.
+    pub fn content_type<S: AsRef<str>>(self, content_type: S) -> Self {
+        self.set_property(Property::new_string(
+            PropertyCode::ContentType,
+            content_type.as_ref(),
+        ))
+    }
+
+    /// Sets the MQTT v5 topic for a response to the message.
+    pub fn response_topic<S: AsRef<str>>(self, topic: S) -> Self {
+        self.set_property(Property::new_string(
+            PropertyCode::ResponseTopic,
+            topic.as_ref(),
+        ))
+    }
+
+    /// Sets the MQTT v5 correlation data to match a response with the
+    /// request.
+    pub fn correlation_data<V>(self, data: V) -> Self
+    where
+        V: Into<Binary>,
+    {
+        self.set_property(Property::new_binary(PropertyCode::CorrelationData, data))
+    }
+
+    /// Sets the MQTT v5 message expiry interval.
+    ///
+    /// The server discards the message if it can't deliver it to a
+    /// subscriber within this time. The interval has a resolution of one
+    /// second, and saturates at `u32::MAX` seconds.
+    pub fn message_expiry_interval(self, expiry: Duration) -> Self {
+        let secs = u32::try_from(expiry.as_secs()).unwrap_or(u32::MAX);
+        self.set_property(Property::new_u32(PropertyCode::MessageExpiryInterval, secs))
+    }
+
+    /// Sets the MQTT v5 payload format indicator.
+    ///
+    /// If this marks the payload as UTF-8, it is checked when the message
+    /// is created with [`try_finalize()`](Self::try_finalize).
+    pub fn payload_format_indicator(self, fmt: PayloadFormat) -> Self {
+        self.set_property(Property::new_byte(
+            PropertyCode::PayloadFormatIndicator,
+            fmt as u8,
+        ))
+    }
+
+    /// Adds an MQTT v5 subscription identifier to the message.
+    ///
+    /// Subscription identifiers are only sent by the server with the
+    /// messages it delivers. A client must not publish a message with one,
+    /// and [`try_finalize()`](Self::try_finalize) rejects it, but this can
+    /// be useful to create incoming messages with
+    /// [`finalize()`](Self::finalize), such as for testing handlers.
+    pub fn subscription_identifier(mut self, id: u32) -> Self {
+        let res = self
+            .props
+            .push_int(PropertyCode::SubscriptionIdentifier, id as i32);
+        self.keep_err(res);
+        self
+    }
+
+    /// Adds an MQTT v5 user property to the message.
+    ///
+    /// A message can have any number of user properties, and the same
+    /// name can appear more than once.
+    pub fn user_property<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let res = self.props.push_string_pair(
+            PropertyCode::UserProperty,
+            key.as_ref(),
+            val.as_ref(),
+        );
+        self.keep_err(res);
+        self
+    }
+
+    /// Sets the MQTT v5 user properties for the message, replacing any
+    /// that were already added.
+    pub fn user_properties(mut self, user_props: &UserProperties) -> Self {
+        self.props.set_user_properties(user_props);
+        self
+    }
+
+    /// Sets the MQTT v5 topic alias for the message.
+    ///
+    /// Once the server has seen an alias along with a topic, subsequent
+    /// messages can be sent with just the alias and an empty topic.
+    /// The alias must be non-zero and no larger than the Topic Alias
+    /// Maximum from the server.
+    pub fn topic_alias(self, alias: u16) -> Self {
+        self.set_property(Property::new_u16(PropertyCode::TopicAlias, alias))
+    }
+
+    // Sets a property, replacing any previous value for the same code.
+    fn set_property(mut self, prop: Result<Property>) -> Self {
+        let res = prop.and_then(|prop| {
+            self.props.remove_all(prop.property_code());
+            self.props.push(prop)
+        });
+        self.keep_err(res);
+        self
+    }
+
+    // Keeps the first error from setting a property, for try_finalize()
+    // to report.
+    fn keep_err(&mut self, res: Result<()>) {
+        if let Err(err) = res {
+            self.err.get_or_insert(err);
+        }
+    }
+
+    /// Validates the topic for the message.
+    /// A v5 message can have an empty topic if it uses a topic alias.
+    fn validate_topic(&self) -> Result<()> {
+        if !self.topic.is_empty() || self.props.get(PropertyCode::TopicAlias).is_none() {
+            validate_topic_name(&self.topic)?;
+        }
+        Ok(())
+    }
+
+    /// Validates the v5 properties for the message.
+    fn validate_properties(&self) -> Result<()> {
+        self.props.validate(PacketType::Publish)?;
+        if let Some(topic) = self.props.get_string(PropertyCode::ResponseTopic) {
+            validate_topic_name(&topic)?;
+        }
+        if let Some(val) = self.props.get_int(PropertyCode::PayloadFormatIndicator) {
+            if PayloadFormat::try_from(val as u8)? == PayloadFormat::Utf8 {
+                str::from_utf8(&self.payload)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Finalize the builder to create the message.
     pub fn finalize(self) -> Message {
+        self.finalize_unchecked()
+    }
+
+    /// Finalize the builder to create the message, checking that the
+    /// topic is a valid topic name.
+    ///
+    /// This also checks that the properties are allowed in a PUBLISH
+    /// packet, that any response topic is a valid topic name, and that the
+    /// payload is valid UTF-8 if the payload format indicator says that
+    /// it is. If one of the typed property setters failed, this returns
+    /// the first error from them.
+    pub fn try_finalize(mut self) -> Result<Message> {
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+        self.validate_topic()?;
+        self.validate_properties()?;
+        Ok(self.finalize_unchecked())
+    }
+
+    // Creates the message without checking the topic.
+    fn finalize_unchecked(self) -> Message {
         let cmsg = ffi::MQTTAsync_message {
             qos: self.qos as c_int,
             retained: to_c_bool(self.retained),
@@ -340,6 +626,66 @@ impl Default for MessageBuilder {
     }
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// Serde support
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::result::Result;
+
+    // The serialized form of a message.
+    #[derive(Serialize, Deserialize)]
+    struct MessageDef {
+        topic: String,
+        payload: String,
+        #[serde(default)]
+        qos: QoS,
+        #[serde(default)]
+        retained: bool,
+        #[serde(default, skip_serializing_if = "Properties::is_empty")]
+        properties: Properties,
+    }
+
+    /// The message is serialized as a struct with the fields `topic`,
+    /// `payload` (base64-encoded), `qos`, `retained`, and `properties`.
+    /// The properties are left out if there aren't any.
+    ///
+    /// When deserialized, the topic is checked to be a valid topic name.
+    /// The properties are not checked against the ones that a client can
+    /// publish, so that messages received from the server, such as those
+    /// with subscription identifiers, can be restored.
+    impl Serialize for Message {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            MessageDef {
+                topic: self.topic().to_string(),
+                payload: BASE64.encode(self.payload()),
+                qos: self.qos(),
+                retained: self.retained(),
+                properties: self.properties().clone(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Message {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let def = MessageDef::deserialize(deserializer)?;
+            let payload = BASE64.decode(def.payload).map_err(de::Error::custom)?;
+            let bldr = MessageBuilder::new()
+                .topic(def.topic)
+                .payload(payload)
+                .qos(def.qos)
+                .retained(def.retained)
+                .properties(def.properties);
+            bldr.validate_topic().map_err(de::Error::custom)?;
+            Ok(bldr.finalize())
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 //                              Unit Tests
 /////////////////////////////////////////////////////////////////////////////
@@ -546,4 +892,237 @@ mod tests {
         });
         let _ = thr.join().unwrap();
     }
+
+    #[test]
+    fn test_builder_try_finalize() {
+        let msg = MessageBuilder::new()
+            .topic(TOPIC)
+            .payload(PAYLOAD)
+            .try_finalize()
+            .unwrap();
+        assert_eq!(TOPIC, msg.topic());
+
+        let res = MessageBuilder::new().topic("bad/+/topic").try_finalize();
+        assert!(matches!(res, Err(crate::Error::BadTopic(_))));
+
+        let res = MessageBuilder::new().try_finalize();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_v5_accessors() {
+        let msg = MessageBuilder::new()
+            .topic(TOPIC)
+            .payload(PAYLOAD)
+            .content_type("text/plain")
+            .response_topic("replies")
+            .correlation_data(b"id-1".to_vec())
+            .message_expiry_interval(Duration::from_secs(30))
+            .payload_format_indicator(PayloadFormat::Utf8)
+            .user_property("a", "1")
+            .user_property("a", "2")
+            .topic_alias(3)
+            .finalize();
+
+        assert_eq!(msg.content_type().unwrap(), "text/plain");
+        assert_eq!(msg.response_topic().unwrap(), "replies");
+        assert_eq!(msg.correlation_data().unwrap(), b"id-1");
+        assert_eq!(msg.message_expiry_interval(), Some(Duration::from_secs(30)));
+        assert_eq!(msg.payload_format_indicator().unwrap(), PayloadFormat::Utf8);
+        assert!(msg.subscription_identifiers().is_empty());
+        assert_eq!(
+            Vec::from(msg.user_properties()),
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("a".to_string(), "2".to_string())
+            ]
+        );
+        assert_eq!(msg.user_properties().get_all("a").collect::<Vec<_>>(), ["1", "2"]);
+        assert_eq!(msg.topic_alias(), Some(3));
+
+        let msg = Message::new(TOPIC, PAYLOAD, QOS);
+        assert!(msg.content_type().is_none());
+        assert!(msg.message_expiry_interval().is_none());
+        assert_eq!(
+            msg.payload_format_indicator().unwrap(),
+            PayloadFormat::Unspecified
+        );
+        assert!(msg.user_properties().is_empty());
+    }
+
+    #[test]
+    fn test_v5_setters_replace() {
+        let msg = MessageBuilder::new()
+            .topic(TOPIC)
+            .response_topic("first")
+            .response_topic("second")
+            .subscription_identifier(1)
+            .subscription_identifier(42)
+            .finalize();
+
+        assert_eq!(msg.response_topic().unwrap(), "second");
+        assert_eq!(msg.properties().len(), 3);
+        assert_eq!(msg.subscription_identifiers(), vec![1, 42]);
+    }
+
+    #[test]
+    fn test_builder_user_properties() {
+        let mut user_props = UserProperties::new();
+        user_props.insert("trace", "1");
+        user_props.insert("trace", "2");
+
+        let msg = MessageBuilder::new()
+            .topic(TOPIC)
+            .content_type("text/plain")
+            .user_property("old", "x")
+            .user_properties(&user_props)
+            .finalize();
+
+        assert_eq!(msg.user_properties(), user_props);
+        assert_eq!(msg.content_type().unwrap(), "text/plain");
+    }
+
+    #[test]
+    fn test_payload_format_utf8() {
+        let bad = vec![0xC3u8, 0x28];
+
+        let msg = MessageBuilder::new()
+            .topic(TOPIC)
+            .payload(bad.clone())
+            .payload_format_indicator(PayloadFormat::Utf8)
+            .finalize();
+        assert!(matches!(
+            msg.payload_format_indicator(),
+            Err(crate::Error::Utf8(_))
+        ));
+
+        let res = MessageBuilder::new()
+            .topic(TOPIC)
+            .payload(bad.clone())
+            .payload_format_indicator(PayloadFormat::Utf8)
+            .try_finalize();
+        assert!(res.is_err());
+
+        let res = MessageBuilder::new()
+            .topic(TOPIC)
+            .payload(bad)
+            .payload_format_indicator(PayloadFormat::Unspecified)
+            .try_finalize();
+        assert!(res.is_ok());
+
+        let res = MessageBuilder::new()
+            .topic(TOPIC)
+            .response_topic("replies/#")
+            .try_finalize();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_try_finalize_properties() {
+        let mut props = Properties::new();
+        props
+            .push_int(PropertyCode::SessionExpiryInterval, 60)
+            .unwrap();
+
+        let res = MessageBuilder::new()
+            .topic(TOPIC)
+            .properties(props)
+            .try_finalize();
+        assert!(matches!(
+            res,
+            Err(crate::Error::PropertyNotAllowed(
+                PropertyCode::SessionExpiryInterval,
+                PacketType::Publish
+            ))
+        ));
+
+        let res = MessageBuilder::new()
+            .topic(TOPIC)
+            .subscription_identifier(1)
+            .try_finalize();
+        assert!(matches!(
+            res,
+            Err(crate::Error::PropertyNotAllowed(
+                PropertyCode::SubscriptionIdentifier,
+                PacketType::Publish
+            ))
+        ));
+
+        let mut props = Properties::new();
+        props.push_int(PropertyCode::SubscriptionIdentifier, 1).unwrap();
+        assert!(MessageBuilder::new().try_properties(props).is_err());
+    }
+
+    #[test]
+    fn test_try_finalize_setter_error() {
+        // A setter for a property that isn't allowed in a PUBLISH fails
+        // the message, even with other setters called after it.
+        let res = MessageBuilder::new()
+            .topic(TOPIC)
+            .content_type("text/plain")
+            .subscription_identifier(7)
+            .response_topic("replies")
+            .user_property("a", "1")
+            .try_finalize();
+        assert!(matches!(
+            res,
+            Err(crate::Error::PropertyNotAllowed(
+                PropertyCode::SubscriptionIdentifier,
+                PacketType::Publish
+            ))
+        ));
+
+        let msg = MessageBuilder::new()
+            .topic(TOPIC)
+            .content_type("text/plain")
+            .response_topic("replies")
+            .user_property("a", "1")
+            .try_finalize()
+            .unwrap();
+        assert_eq!(msg.properties().len(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let msg = MessageBuilder::new()
+            .topic(TOPIC)
+            .payload(b"\x00\x01hello".to_vec())
+            .qos(2)
+            .retained(true)
+            .response_topic("replies")
+            .correlation_data(b"id-1".to_vec())
+            .user_property("a", "1")
+            .finalize();
+
+        let json = serde_json::to_string(&msg).unwrap();
+        let msg2: Message = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(msg2.topic(), TOPIC);
+        assert_eq!(msg2.payload(), msg.payload());
+        assert_eq!(msg2.qos(), QoS::ExactlyOnce);
+        assert!(msg2.retained());
+        assert_eq!(msg2.response_topic().as_deref(), Some("replies"));
+        assert_eq!(msg2.correlation_data().as_deref(), Some(&b"id-1"[..]));
+        assert_eq!(
+            Vec::from(msg2.user_properties()),
+            vec![("a".to_string(), "1".to_string())]
+        );
+
+        let json = serde_json::to_string(&Message::new(TOPIC, "hi", 1)).unwrap();
+        assert!(!json.contains("properties"));
+
+        let res = serde_json::from_str::<Message>(r#"{"topic":"a/#","payload":""}"#);
+        assert!(res.is_err());
+
+        let msg = MessageBuilder::new()
+            .topic(TOPIC)
+            .subscription_identifier(1)
+            .subscription_identifier(42)
+            .finalize();
+
+        let json = serde_json::to_string(&msg).unwrap();
+        let msg2: Message = serde_json::from_str(&json).unwrap();
+        assert_eq!(msg2.subscription_identifiers(), vec![1, 42]);
+    }
 }
@@ -0,0 +1,818 @@
+// namespaced_client.rs
+//
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! Namespaced views of a client, for multi-tenant applications.
+//!
+//! This allows a number of logical tenants to share a single connection to
+//! the server, with each tenant restricted to the topics under its own
+//! prefix, like `tenant/<id>/`. Each tenant gets a [`NamespacedClient`]
+//! that publishes and subscribes with topics relative to its prefix, and
+//! receives its messages in its own stream, with the prefix removed from
+//! the topics.
+//!
+//! ```no_run
+//! use paho_mqtt as mqtt;
+//!
+//! let cli = mqtt::AsyncClient::new("mqtt://localhost:1883").unwrap();
+//! let router = mqtt::NamespaceRouter::new(&cli);
+//!
+//! let tenant = router.namespace("tenant/42").unwrap();
+//! let strm = tenant.get_stream(None);
+//!
+//! cli.connect(None).wait().unwrap();
+//!
+//! // Subscribes to "tenant/42/data/#"
+//! tenant.subscribe("data/#", 1);
+//! ```
+
+use crate::{
+    async_client::AsyncClient,
+    errors::{Error, Result},
+    message::{Message, MessageBuilder},
+    properties::{Properties, PropertyCode},
+    server_response::RequestResponse,
+    subscribe_options::SubscribeOptions,
+    token::{DeliveryToken, SubscribeManyToken, SubscribeToken, Token},
+    topic::{validate_topic_filter, validate_topic_name},
+    topic_matcher::topic_matches,
+    types::QoS,
+};
+use async_channel::Sender;
+use std::{
+    collections::{HashMap, HashSet},
+    slice,
+    sync::{Arc, Mutex},
+};
+
+/// The prefix for shared subscription filters.
+const SHARE_PREFIX: &str = "$share/";
+
+// The state for each namespace, shared by the router and the clients.
+#[derive(Default)]
+struct NamespaceState {
+    /// The full filters of the subscriptions made through the namespace
+    subs: HashSet<String>,
+    /// The sender for the namespace's stream of incoming messages
+    tx: Option<Sender<Option<Message>>>,
+}
+
+impl NamespaceState {
+    // Determines if the topic matches any of the namespace's subscriptions.
+    fn is_subscribed(&self, topic: &str) -> bool {
+        self.subs
+            .iter()
+            .any(|filter| topic_matches(split_share(filter).1, topic))
+    }
+
+    // Sends a message (or disconnect notification) to the stream, if any.
+    fn send(&self, msg: Option<Message>) {
+        if let Some(ref tx) = self.tx {
+            if let Err(err) = tx.try_send(msg) {
+                if err.is_full() {
+                    warn!("Namespace stream full. Losing messages");
+                }
+                else {
+                    trace!("Namespace stream error: {:?}", err);
+                }
+            }
+        }
+    }
+}
+
+// The state shared by the router and the clients.
+#[derive(Default)]
+struct RouterState {
+    /// The namespaces, keyed by prefix
+    namespaces: HashMap<String, NamespaceState>,
+    /// The number of namespaces subscribed to each (full) filter
+    filters: HashMap<String, usize>,
+}
+
+// The namespaces and their subscriptions.
+type Namespaces = Arc<Mutex<RouterState>>;
+
+/// Routes the messages arriving on a client connection to the
+/// namespaces that subscribed to them.
+///
+/// The router installs a message callback in the client, replacing any
+/// that was previously set, and then hands out [`NamespacedClient`]
+/// objects, each restricted to the topics under one prefix.
+///
+/// An incoming message is delivered to each namespace that made a
+/// matching subscription, with the namespace prefix removed from the
+/// topic. When the connection is lost, a `None` is sent to all the
+/// namespace streams.
+///
+/// Different namespaces can subscribe to the same filter on the server,
+/// like "tenant/1/" with "sub/#" and "tenant/1/sub/" with "#". The
+/// router keeps a count of the namespaces subscribed to each filter, and
+/// only unsubscribes from the server when the last one unsubscribes.
+#[derive(Clone)]
+pub struct NamespaceRouter {
+    /// The client shared by all the namespaces
+    cli: AsyncClient,
+    /// The namespaces, keyed by prefix
+    namespaces: Namespaces,
+}
+
+impl NamespaceRouter {
+    /// Creates a router for the messages arriving on the client.
+    ///
+    /// As with [`AsyncClient::get_stream`], this should be created before
+    /// connecting to the server, otherwise messages can be lost.
+    pub fn new(cli: &AsyncClient) -> Self {
+        let namespaces = Namespaces::default();
+
+        cli.set_disconnection_callbacks();
+
+        let nss = namespaces.clone();
+        cli.set_message_callback(move |_, msg| {
+            dispatch(&nss.lock().unwrap().namespaces, msg);
+        });
+
+        Self {
+            cli: cli.clone(),
+            namespaces,
+        }
+    }
+
+    /// Gets a client restricted to the namespace under the prefix.
+    ///
+    /// The prefix is a topic name, like "tenant/42", which is used as the
+    /// leading levels of all the topics in the namespace. A trailing
+    /// slash is optional. The prefix can not contain wildcards, and can
+    /// not start with a '$'.
+    ///
+    /// Clients for the same prefix share their subscriptions and stream.
+    pub fn namespace<S>(&self, prefix: S) -> Result<NamespacedClient>
+    where
+        S: Into<String>,
+    {
+        let mut prefix = prefix.into();
+        if prefix.ends_with('/') {
+            prefix.pop();
+        }
+
+        validate_topic_name(&prefix)?;
+        if prefix.starts_with('$') {
+            return Err(Error::NamespaceEscape(prefix));
+        }
+        prefix.push('/');
+
+        self.namespaces
+            .lock()
+            .unwrap()
+            .namespaces
+            .entry(prefix.clone())
+            .or_default();
+
+        Ok(NamespacedClient {
+            cli: self.cli.clone(),
+            prefix,
+            namespaces: self.namespaces.clone(),
+        })
+    }
+
+    /// Gets the client shared by all the namespaces.
+    pub fn client(&self) -> &AsyncClient {
+        &self.cli
+    }
+}
+
+/// A view of a client that is restricted to the topics under a prefix.
+///
+/// The topics for publishing, and the filters for subscribing, are
+/// relative to the prefix. For example, in the namespace with the prefix
+/// "tenant/42/", publishing to "data/temp" sends the message to
+/// "tenant/42/data/temp", and subscribing to "data/#" subscribes to
+/// "tenant/42/data/#". Shared subscriptions keep the share name in front,
+/// so "$share/group/data/#" becomes "$share/group/tenant/42/data/#".
+///
+/// Topics starting with '$', like "$SYS/#", refer to the server, not the
+/// namespace, so they are rejected, as are topic aliases in published
+/// messages, since an alias might refer to a topic in another namespace.
+/// Likewise, incoming messages with a topic that starts with '$' after
+/// the prefix is removed are dropped, as the server would not send them
+/// to a wildcard subscription.
+///
+/// The v5 Response Topic property of messages is mapped in and out of the
+/// namespace as well. An incoming message with a response topic outside
+/// the namespace has the property removed.
+#[derive(Clone)]
+pub struct NamespacedClient {
+    /// The client shared by all the namespaces
+    cli: AsyncClient,
+    /// The prefix for the namespace, ending with a '/'
+    prefix: String,
+    /// The state for all the namespaces
+    namespaces: Namespaces,
+}
+
+impl NamespacedClient {
+    /// Gets the prefix for the namespace, including the trailing '/'.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Gets the underlying client shared by all the namespaces.
+    pub fn client(&self) -> &AsyncClient {
+        &self.cli
+    }
+
+    /// Gets the full topic name for a topic in the namespace.
+    ///
+    /// This fails if the topic is not a valid topic name, or is outside
+    /// the namespace.
+    pub fn topic_name(&self, topic: &str) -> Result<String> {
+        prefixed_topic(&self.prefix, topic)
+    }
+
+    /// Gets the full topic filter for a filter in the namespace.
+    ///
+    /// This fails if the filter is not a valid topic filter, or is
+    /// outside the namespace.
+    pub fn topic_filter(&self, filter: &str) -> Result<String> {
+        prefixed_filter(&self.prefix, filter)
+    }
+
+    /// Gets the topic relative to the namespace from a full topic, or
+    /// `None` if the topic is not in the namespace.
+    pub fn local_topic<'a>(&self, topic: &'a str) -> Option<&'a str> {
+        local_topic(&self.prefix, topic)
+    }
+
+    /// Attempts to publish a message to a topic in the namespace.
+    pub fn try_publish(&self, msg: Message) -> Result<DeliveryToken> {
+        let msg = outgoing_message(&self.prefix, &msg)?;
+        self.cli.try_publish(msg)
+    }
+
+    /// Publishes a message to a topic in the namespace.
+    ///
+    /// If the topic is outside the namespace, the token is signaled with
+    /// an error.
+    pub fn publish(&self, msg: Message) -> DeliveryToken {
+        match outgoing_message(&self.prefix, &msg) {
+            Ok(msg) => self.cli.publish(msg),
+            Err(err) => DeliveryToken::from_err(msg, err),
+        }
+    }
+
+    /// Subscribes to a topic filter in the namespace.
+    ///
+    /// Messages for the filter are routed to the namespace once the
+    /// server grants the subscription.
+    pub fn subscribe<S, Q>(&self, filter: S, qos: Q) -> SubscribeToken
+    where
+        S: Into<String>,
+        Q: Into<QoS>,
+    {
+        match self.topic_filter(&filter.into()) {
+            Ok(filter) => {
+                let tok = self.cli.subscribe(filter.clone(), qos);
+                self.add_subscriptions(&tok, vec![filter]);
+                tok
+            }
+            Err(err) => SubscribeToken::from_err(err),
+        }
+    }
+
+    /// Subscribes to a topic filter in the namespace with v5 options.
+    pub fn subscribe_with_options<S, Q, T, P>(
+        &self,
+        filter: S,
+        qos: Q,
+        opts: T,
+        props: P,
+    ) -> SubscribeToken
+    where
+        S: Into<String>,
+        Q: Into<QoS>,
+        T: Into<SubscribeOptions>,
+        P: Into<Option<Properties>>,
+    {
+        match self.topic_filter(&filter.into()) {
+            Ok(filter) => {
+                let tok = self.cli.subscribe_with_options(filter.clone(), qos, opts, props);
+                self.add_subscriptions(&tok, vec![filter]);
+                tok
+            }
+            Err(err) => SubscribeToken::from_err(err),
+        }
+    }
+
+    /// Subscribes to multiple topic filters in the namespace.
+    ///
+    /// Messages are routed to the namespace for each of the filters that
+    /// the server grants.
+    pub fn subscribe_many<T, Q>(&self, filters: &[T], qos: &[Q]) -> SubscribeManyToken
+    where
+        T: AsRef<str>,
+        Q: Into<QoS> + Copy,
+    {
+        match self.topic_filters(filters) {
+            Ok(filters) => {
+                let tok = self.cli.subscribe_many(&filters, qos);
+                self.add_subscriptions(&tok, filters);
+                tok
+            }
+            Err(err) => SubscribeManyToken::from_err(err),
+        }
+    }
+
+    /// Unsubscribes from a topic filter in the namespace.
+    ///
+    /// The client only unsubscribes from the server if no other namespace
+    /// is subscribed to the same filter. Otherwise the token completes
+    /// right away.
+    pub fn unsubscribe<S>(&self, filter: S) -> Token
+    where
+        S: Into<String>,
+    {
+        match self.topic_filter(&filter.into()) {
+            Ok(filter) => match self.remove_subscriptions(&[filter]).pop() {
+                Some(filter) => self.cli.unsubscribe(filter),
+                None => Token::from_success(),
+            },
+            Err(err) => Token::from_err(err),
+        }
+    }
+
+    /// Unsubscribes from multiple topic filters in the namespace.
+    ///
+    /// The client only unsubscribes from the server from the filters that
+    /// no other namespace is subscribed to.
+    pub fn unsubscribe_many<T>(&self, filters: &[T]) -> Token
+    where
+        T: AsRef<str>,
+    {
+        match self.topic_filters(filters) {
+            Ok(filters) => {
+                let filters = self.remove_subscriptions(&filters);
+                if filters.is_empty() {
+                    Token::from_success()
+                }
+                else {
+                    self.cli.unsubscribe_many(&filters)
+                }
+            }
+            Err(err) => Token::from_err(err),
+        }
+    }
+
+    /// Creates a futures stream for the messages arriving in the
+    /// namespace.
+    ///
+    /// The topics of the messages are relative to the namespace. If the
+    /// client gets disconnected, a `None` is inserted into the stream.
+    ///
+    /// The stream uses a bounded channel with the given buffer capacity
+    /// if `buffer_lim` is `Some`, or an unbounded channel if it's `None`.
+    /// This replaces any stream previously created for the namespace.
+    pub fn get_stream<L>(&self, buffer_lim: L) -> crate::AsyncReceiver<Option<Message>>
+    where
+        L: Into<Option<usize>>,
+    {
+        let (tx, rx) = match buffer_lim.into() {
+            None => async_channel::unbounded(),
+            Some(lim) => async_channel::bounded(lim),
+        };
+        self.with_state(|ns| ns.tx = Some(tx));
+        rx
+    }
+
+    /// Stops streaming messages for the namespace.
+    pub fn stop_stream(&self) {
+        self.with_state(|ns| ns.tx = None);
+    }
+
+    // Gets the full filters for the filters in the namespace.
+    fn topic_filters<T: AsRef<str>>(&self, filters: &[T]) -> Result<Vec<String>> {
+        filters
+            .iter()
+            .map(|filter| self.topic_filter(filter.as_ref()))
+            .collect()
+    }
+
+    // Runs the function on the state for the namespace.
+    fn with_state<F>(&self, f: F)
+    where
+        F: FnOnce(&mut NamespaceState),
+    {
+        let mut state = self.namespaces.lock().unwrap();
+        f(state.namespaces.entry(self.prefix.clone()).or_default());
+    }
+
+    // Records the (full) filters subscribed in the namespace once the
+    // subscribe token completes, keeping only the ones the server granted.
+    // This happens before any messages for them are routed.
+    fn add_subscriptions(&self, tok: &Token, filters: Vec<String>) {
+        let namespaces = self.namespaces.clone();
+        let prefix = self.prefix.clone();
+
+        tok.when_complete(move |res| {
+            if let Ok(rsp) = res {
+                let filters = granted_filters(filters, rsp.request_response());
+                namespaces
+                    .lock()
+                    .unwrap()
+                    .add_subscriptions(&prefix, &filters);
+            }
+        });
+    }
+
+    // Removes the (full) filters subscribed in the namespace, returning
+    // the ones that no namespace is subscribed to any longer.
+    fn remove_subscriptions<T: AsRef<str>>(&self, filters: &[T]) -> Vec<String> {
+        self.namespaces
+            .lock()
+            .unwrap()
+            .remove_subscriptions(&self.prefix, filters)
+    }
+}
+
+impl RouterState {
+    // Records the (full) filters subscribed in the namespace, counting
+    // each one once per namespace.
+    fn add_subscriptions<T: AsRef<str>>(&mut self, prefix: &str, filters: &[T]) {
+        let ns = self.namespaces.entry(prefix.to_string()).or_default();
+        for filter in filters {
+            let filter = filter.as_ref();
+            if ns.subs.insert(filter.to_string()) {
+                *self.filters.entry(filter.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    // Removes the (full) filters subscribed in the namespace, returning
+    // the ones that no namespace is subscribed to any longer.
+    fn remove_subscriptions<T: AsRef<str>>(&mut self, prefix: &str, filters: &[T]) -> Vec<String> {
+        let ns = self.namespaces.entry(prefix.to_string()).or_default();
+        let mut released = Vec::new();
+
+        for filter in filters {
+            let filter = filter.as_ref();
+            if ns.subs.remove(filter) {
+                if let Some(n) = self.filters.get_mut(filter) {
+                    *n -= 1;
+                    if *n > 0 {
+                        continue;
+                    }
+                    self.filters.remove(filter);
+                }
+            }
+            else if self.filters.contains_key(filter) {
+                continue;
+            }
+            released.push(filter.to_string());
+        }
+        released
+    }
+}
+
+// Gets the filters that the server granted, from the reason codes in
+// the SUBACK. Without the codes, they're all taken to be granted.
+fn granted_filters(filters: Vec<String>, rsp: &RequestResponse) -> Vec<String> {
+    let codes = match rsp {
+        RequestResponse::Subscribe(code) => slice::from_ref(code),
+        RequestResponse::SubscribeMany(codes) => codes.as_slice(),
+        _ => return filters,
+    };
+    filters
+        .into_iter()
+        .zip(codes)
+        .filter(|(_, code)| **code < 0x80)
+        .map(|(filter, _)| filter)
+        .collect()
+}
+
+// Splits a filter into the share name, if it's a shared subscription,
+// and the topic filter.
+fn split_share(filter: &str) -> (Option<&str>, &str) {
+    if let Some(rest) = filter.strip_prefix(SHARE_PREFIX) {
+        if let Some((name, filter)) = rest.split_once('/') {
+            return (Some(name), filter);
+        }
+    }
+    (None, filter)
+}
+
+// Gets the full topic name for a topic relative to the prefix.
+fn prefixed_topic(prefix: &str, topic: &str) -> Result<String> {
+    validate_topic_name(topic)?;
+    if topic.starts_with('$') {
+        return Err(Error::NamespaceEscape(topic.to_string()));
+    }
+    let topic = format!("{}{}", prefix, topic);
+    validate_topic_name(&topic)?;
+    Ok(topic)
+}
+
+// Gets the full topic filter for a filter relative to the prefix.
+fn prefixed_filter(prefix: &str, filter: &str) -> Result<String> {
+    validate_topic_filter(filter)?;
+
+    let filter = match split_share(filter) {
+        (_, topic) if topic.starts_with('$') => {
+            return Err(Error::NamespaceEscape(filter.to_string()));
+        }
+        (Some(name), topic) => format!("{}{}/{}{}", SHARE_PREFIX, name, prefix, topic),
+        (None, topic) => format!("{}{}", prefix, topic),
+    };
+    validate_topic_filter(&filter)?;
+    Ok(filter)
+}
+
+// Gets the topic relative to the prefix, if it's in the namespace.
+fn local_topic<'a>(prefix: &str, topic: &'a str) -> Option<&'a str> {
+    topic
+        .strip_prefix(prefix)
+        .filter(|topic| !topic.is_empty() && !topic.starts_with('$'))
+}
+
+// Maps the response topic property, if any, with the function, dropping
+// the property if the function returns `None`.
+fn map_response_topic<F>(props: &Properties, f: F) -> Result<Properties>
+where
+    F: Fn(&str) -> Result<Option<String>>,
+{
+    if props.get(PropertyCode::ResponseTopic).is_none() {
+        return Ok(props.clone());
+    }
+
+    let mut new_props = Properties::new();
+    for prop in props.to_vec() {
+        if prop.property_code() == PropertyCode::ResponseTopic {
+            if let Some(topic) = prop.get_string() {
+                if let Some(topic) = f(&topic)? {
+                    new_props.push_string(PropertyCode::ResponseTopic, &topic)?;
+                }
+            }
+        }
+        else {
+            new_props.push(prop)?;
+        }
+    }
+    Ok(new_props)
+}
+
+// Creates the message to publish for a message in the namespace.
+fn outgoing_message(prefix: &str, msg: &Message) -> Result<Message> {
+    let props = msg.properties();
+    if props.get(PropertyCode::TopicAlias).is_some() {
+        return Err(Error::General("Topic aliases can't be used in a namespace"));
+    }
+
+    let topic = prefixed_topic(prefix, msg.topic())?;
+    let props = map_response_topic(props, |topic| prefixed_topic(prefix, topic).map(Some))?;
+
+    Ok(MessageBuilder::new()
+        .topic(topic)
+        .payload(msg.payload())
+        .qos(msg.qos())
+        .retained(msg.retained())
+        .properties(props)
+        .finalize())
+}
+
+// Creates the message for the namespace from an incoming message, or
+// `None` if the message is not in the namespace.
+fn incoming_message(prefix: &str, msg: &Message) -> Option<Message> {
+    let topic = local_topic(prefix, msg.topic())?;
+    let props = map_response_topic(msg.properties(), |topic| {
+        Ok(local_topic(prefix, topic).map(String::from))
+    })
+    .ok()?;
+
+    Some(
+        MessageBuilder::new()
+            .topic(topic)
+            .payload(msg.payload())
+            .qos(msg.qos())
+            .retained(msg.retained())
+            .properties(props)
+            .finalize(),
+    )
+}
+
+// Delivers an incoming message to the namespaces that subscribed to it,
+// or a disconnect notification (`None`) to all of them.
+fn dispatch(namespaces: &HashMap<String, NamespaceState>, msg: Option<Message>) {
+    match msg {
+        None => {
+            for ns in namespaces.values() {
+                ns.send(None);
+            }
+        }
+        Some(msg) => {
+            for (prefix, ns) in namespaces {
+                if ns.tx.is_none() || !ns.is_subscribed(msg.topic()) {
+                    continue;
+                }
+                if let Some(msg) = incoming_message(prefix, &msg) {
+                    ns.send(Some(msg));
+                }
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREFIX: &str = "tenant/42/";
+
+    #[test]
+    fn test_prefixed_topic() {
+        assert_eq!(
+            prefixed_topic(PREFIX, "data/temp").unwrap(),
+            "tenant/42/data/temp"
+        );
+        assert!(matches!(
+            prefixed_topic(PREFIX, "$SYS/broker/load"),
+            Err(Error::NamespaceEscape(_))
+        ));
+        assert!(matches!(
+            prefixed_topic(PREFIX, "data/+"),
+            Err(Error::BadTopic(_))
+        ));
+        assert!(prefixed_topic(PREFIX, "").is_err());
+    }
+
+    #[test]
+    fn test_prefixed_filter() {
+        assert_eq!(prefixed_filter(PREFIX, "#").unwrap(), "tenant/42/#");
+        assert_eq!(
+            prefixed_filter(PREFIX, "+/temp").unwrap(),
+            "tenant/42/+/temp"
+        );
+        assert_eq!(
+            prefixed_filter(PREFIX, "$share/group/data/#").unwrap(),
+            "$share/group/tenant/42/data/#"
+        );
+        assert!(matches!(
+            prefixed_filter(PREFIX, "$SYS/#"),
+            Err(Error::NamespaceEscape(_))
+        ));
+        assert!(matches!(
+            prefixed_filter(PREFIX, "$share/group/$SYS/#"),
+            Err(Error::NamespaceEscape(_))
+        ));
+        assert!(prefixed_filter(PREFIX, "$share/group").is_err());
+        assert!(prefixed_filter(PREFIX, "data/#/temp").is_err());
+    }
+
+    #[test]
+    fn test_local_topic() {
+        assert_eq!(local_topic(PREFIX, "tenant/42/data"), Some("data"));
+        assert_eq!(local_topic(PREFIX, "tenant/421/data"), None);
+        assert_eq!(local_topic(PREFIX, "tenant/42"), None);
+        assert_eq!(local_topic(PREFIX, "tenant/42/"), None);
+        assert_eq!(local_topic(PREFIX, "tenant/42/$SYS/load"), None);
+    }
+
+    #[test]
+    fn test_messages() {
+        let msg = Message::new("data/temp", "20", 1);
+        let out = outgoing_message(PREFIX, &msg).unwrap();
+        assert_eq!(out.topic(), "tenant/42/data/temp");
+        assert_eq!(out.payload(), b"20");
+        assert_eq!(out.qos(), QoS::AtLeastOnce);
+
+        let msg = incoming_message(PREFIX, &out).unwrap();
+        assert_eq!(msg.topic(), "data/temp");
+        assert_eq!(msg.payload(), b"20");
+
+        let msg = Message::new("tenant/7/data/temp", "20", 1);
+        assert!(incoming_message(PREFIX, &msg).is_none());
+    }
+
+    #[test]
+    fn test_response_topic() {
+        let mut props = Properties::new();
+        props
+            .push_string(PropertyCode::ResponseTopic, "replies")
+            .unwrap();
+        props
+            .push_string_pair(PropertyCode::UserProperty, "a", "b")
+            .unwrap();
+
+        let msg = MessageBuilder::new()
+            .topic("requests")
+            .payload("x")
+            .properties(props)
+            .finalize();
+
+        let out = outgoing_message(PREFIX, &msg).unwrap();
+        let props = out.properties();
+        assert_eq!(
+            props.get_string(PropertyCode::ResponseTopic).unwrap(),
+            "tenant/42/replies"
+        );
+        assert_eq!(props.find_user_property("a").unwrap(), "b");
+
+        let msg = incoming_message(PREFIX, &out).unwrap();
+        assert_eq!(
+            msg.properties()
+                .get_string(PropertyCode::ResponseTopic)
+                .unwrap(),
+            "replies"
+        );
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut namespaces = HashMap::new();
+        for prefix in ["tenant/1/", "tenant/1/sub/", "tenant/2/"] {
+            namespaces.insert(prefix.to_string(), NamespaceState::default());
+        }
+
+        let (tx1, rx1) = async_channel::unbounded();
+        let ns = namespaces.get_mut("tenant/1/").unwrap();
+        ns.subs.insert("tenant/1/#".to_string());
+        ns.tx = Some(tx1);
+
+        let (tx2, rx2) = async_channel::unbounded();
+        let ns = namespaces.get_mut("tenant/1/sub/").unwrap();
+        ns.subs.insert("$share/g/tenant/1/sub/+".to_string());
+        ns.tx = Some(tx2);
+
+        let (tx3, rx3) = async_channel::unbounded();
+        namespaces.get_mut("tenant/2/").unwrap().tx = Some(tx3);
+
+        dispatch(&namespaces, Some(Message::new("tenant/1/sub/x", "1", 0)));
+
+        let msg = rx1.try_recv().unwrap().unwrap();
+        assert_eq!(msg.topic(), "sub/x");
+        let msg = rx2.try_recv().unwrap().unwrap();
+        assert_eq!(msg.topic(), "x");
+        assert!(rx3.try_recv().is_err());
+
+        dispatch(&namespaces, None);
+        assert!(rx1.try_recv().unwrap().is_none());
+        assert!(rx2.try_recv().unwrap().is_none());
+        assert!(rx3.try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_granted_filters() {
+        let filters = || vec!["tenant/1/a".to_string(), "tenant/1/b".to_string()];
+
+        let rsp = RequestResponse::SubscribeMany(vec![1, 0x87]);
+        assert_eq!(granted_filters(filters(), &rsp), vec!["tenant/1/a"]);
+
+        let rsp = RequestResponse::Subscribe(0x80);
+        assert!(granted_filters(filters()[..1].to_vec(), &rsp).is_empty());
+
+        assert_eq!(granted_filters(filters(), &RequestResponse::None), filters());
+    }
+
+    #[test]
+    fn test_shared_filters() {
+        let mut state = RouterState::default();
+
+        state.add_subscriptions("tenant/1/", &["tenant/1/sub/#", "tenant/1/a"]);
+        state.add_subscriptions("tenant/1/", &["tenant/1/sub/#"]);
+        state.add_subscriptions("tenant/1/sub/", &["tenant/1/sub/#"]);
+        assert_eq!(state.filters["tenant/1/sub/#"], 2);
+
+        // Still held by the other namespace
+        let released = state.remove_subscriptions("tenant/1/", &["tenant/1/sub/#", "tenant/1/a"]);
+        assert_eq!(released, vec!["tenant/1/a"]);
+
+        // Not held by this namespace, so nothing changes
+        let released = state.remove_subscriptions("tenant/1/", &["tenant/1/sub/#"]);
+        assert!(released.is_empty());
+
+        let released = state.remove_subscriptions("tenant/1/sub/", &["tenant/1/sub/#"]);
+        assert_eq!(released, vec!["tenant/1/sub/#"]);
+        assert!(state.filters.is_empty());
+
+        // Unknown filters are passed on to the server
+        let released = state.remove_subscriptions("tenant/2/", &["tenant/2/x"]);
+        assert_eq!(released, vec!["tenant/2/x"]);
+    }
+}
@@ -0,0 +1,285 @@
+// presence.rs
+//
+// Birth and death messages announcing whether a client is online.
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! Birth and death messages announcing whether a client is online.
+//!
+//! A common pattern is for each client to keep a retained status message
+//! on a topic of its own: an "online" (birth) message that it publishes
+//! whenever it connects, and an "offline" (death) message that is
+//! published when it goes away. The death message is registered as the
+//! will, so that the server publishes it if the connection is lost, and
+//! the client publishes it itself when it disconnects cleanly.
+//!
+//! When [`PresenceOptions`] are set on a client with
+//! [`AsyncClient::set_presence()`](crate::AsyncClient::set_presence):
+//!
+//! - Each call to [`connect()`](crate::AsyncClient::connect) sets the
+//!   death message as the will in the connect options.
+//! - The birth message is published each time the client connects,
+//!   including automatic reconnects and those made by a
+//!   [`ReconnectPolicy`](crate::ReconnectPolicy).
+//! - [`disconnect()`](crate::AsyncClient::disconnect) gets the death
+//!   message out before disconnecting. With MQTT v5, and no will delay,
+//!   it asks the server to publish the will. Otherwise the client
+//!   publishes the message just ahead of the disconnect, which waits up
+//!   to its timeout for the message to go out. With a zero timeout, a
+//!   QoS 1 or 2 message might not complete.
+//!
+//! The topic and payloads can contain these fields, which are filled in
+//! when the message is created:
+//!
+//! - `{client_id}` The client ID, or the one assigned by the server.
+//! - `{timestamp}` The time, in seconds since the Unix epoch.
+//!
+//! Since the will is set when connecting, `{client_id}` needs a known
+//! client ID by then. With an empty client ID, and none assigned by the
+//! server yet, connecting fails.
+//!
+//! Note that the will is created when connecting, so a timestamp in the
+//! death message is the time of the connection when the message comes
+//! from the server.
+//!
+//! ```
+//! use paho_mqtt as mqtt;
+//!
+//! let presence = mqtt::PresenceOptions::new("devices/{client_id}/status")
+//!     .online_payload(r#"{"online":true,"since":{timestamp}}"#)
+//!     .offline_payload(r#"{"online":false}"#);
+//! ```
+
+use crate::{message::Message, types::QoS, will_options::WillOptions};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The default payload of the birth message.
+pub const DEFAULT_ONLINE_PAYLOAD: &str = "online";
+
+/// The default payload of the death message.
+pub const DEFAULT_OFFLINE_PAYLOAD: &str = "offline";
+
+/// The options for announcing whether a client is online, with birth and
+/// death messages on a status topic.
+///
+/// By default the messages are "online" and "offline", published at
+/// QoS 1, and retained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresenceOptions {
+    topic: String,
+    online_payload: String,
+    offline_payload: String,
+    qos: QoS,
+    retained: bool,
+    will_delay: Option<Duration>,
+}
+
+impl PresenceOptions {
+    /// Creates presence options to publish the status on the topic.
+    ///
+    /// The topic can contain the `{client_id}` and `{timestamp}` fields.
+    pub fn new<S>(topic: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            topic: topic.into(),
+            online_payload: DEFAULT_ONLINE_PAYLOAD.to_string(),
+            offline_payload: DEFAULT_OFFLINE_PAYLOAD.to_string(),
+            qos: QoS::AtLeastOnce,
+            retained: true,
+            will_delay: None,
+        }
+    }
+
+    /// Sets the payload of the birth message, published when the client
+    /// connects.
+    pub fn online_payload<S>(mut self, payload: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.online_payload = payload.into();
+        self
+    }
+
+    /// Sets the payload of the death message, used as the will, and
+    /// published when the client disconnects.
+    pub fn offline_payload<S>(mut self, payload: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.offline_payload = payload.into();
+        self
+    }
+
+    /// Sets the quality of service for the messages.
+    pub fn qos<Q>(mut self, qos: Q) -> Self
+    where
+        Q: Into<QoS>,
+    {
+        self.qos = qos.into();
+        self
+    }
+
+    /// Sets whether the messages are retained by the server.
+    pub fn retained(mut self, on: bool) -> Self {
+        self.retained = on;
+        self
+    }
+
+    /// Sets the MQTT v5 will delay interval for the death message.
+    ///
+    /// If the client reconnects within this time after losing the
+    /// connection, the server does not publish the death message.
+    pub fn will_delay_interval(mut self, delay: Duration) -> Self {
+        self.will_delay = Some(delay);
+        self
+    }
+
+    /// Gets the status topic, before the fields are filled in.
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    // Determines if the topic or payloads have a `{client_id}` field.
+    pub(crate) fn uses_client_id(&self) -> bool {
+        [&self.topic, &self.online_payload, &self.offline_payload]
+            .iter()
+            .any(|s| s.contains("{client_id}"))
+    }
+
+    // Determines if the death message has a will delay.
+    pub(crate) fn has_will_delay(&self) -> bool {
+        self.will_delay.map_or(false, |delay| !delay.is_zero())
+    }
+
+    /// Creates the birth message for the client.
+    pub fn online_message(&self, client_id: &str) -> Message {
+        self.message(&self.online_payload, client_id)
+    }
+
+    /// Creates the death message for the client.
+    pub fn offline_message(&self, client_id: &str) -> Message {
+        self.message(&self.offline_payload, client_id)
+    }
+
+    /// Creates the will options for the client, with the death message.
+    pub fn will_options(&self, client_id: &str) -> WillOptions {
+        let mut will = WillOptions::from(self.offline_message(client_id));
+        if let Some(delay) = self.will_delay {
+            will.set_will_delay_interval(delay);
+        }
+        will
+    }
+
+    // Creates a message with the payload, filling in the fields.
+    fn message(&self, payload: &str, client_id: &str) -> Message {
+        let topic = fill_fields(&self.topic, client_id);
+        let payload = fill_fields(payload, client_id);
+        if self.retained {
+            Message::new_retained(topic, payload, self.qos)
+        }
+        else {
+            Message::new(topic, payload, self.qos)
+        }
+    }
+}
+
+// Fills in the `{client_id}` and `{timestamp}` fields in the template.
+fn fill_fields(template: &str, client_id: &str) -> String {
+    if !template.contains('{') {
+        return template.to_string();
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|dur| dur.as_secs())
+        .unwrap_or_default();
+    template
+        .replace("{client_id}", client_id)
+        .replace("{timestamp}", &timestamp.to_string())
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        let presence = PresenceOptions::new("status/dev1");
+
+        let msg = presence.online_message("dev1");
+        assert_eq!(msg.topic(), "status/dev1");
+        assert_eq!(msg.payload_str(), DEFAULT_ONLINE_PAYLOAD);
+        assert_eq!(msg.qos(), QoS::AtLeastOnce);
+        assert!(msg.retained());
+
+        let will = presence.will_options("dev1");
+        assert_eq!(will.topic(), "status/dev1");
+        assert_eq!(will.payload_str(), DEFAULT_OFFLINE_PAYLOAD);
+        assert_eq!(will.qos(), 1);
+        assert!(will.retained());
+        assert_eq!(will.will_delay_interval(), None);
+    }
+
+    #[test]
+    fn test_options() {
+        let presence = PresenceOptions::new("status")
+            .online_payload("up")
+            .offline_payload("down")
+            .qos(QoS::ExactlyOnce)
+            .retained(false)
+            .will_delay_interval(Duration::from_secs(10));
+
+        let msg = presence.offline_message("dev1");
+        assert_eq!(msg.payload_str(), "down");
+        assert_eq!(msg.qos(), QoS::ExactlyOnce);
+        assert!(!msg.retained());
+
+        let will = presence.will_options("dev1");
+        assert_eq!(will.payload_str(), "down");
+        assert!(!will.retained());
+        assert_eq!(will.will_delay_interval(), Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_fields() {
+        let presence = PresenceOptions::new("devices/{client_id}/status")
+            .online_payload("{client_id} up at {timestamp}");
+        assert_eq!(presence.topic(), "devices/{client_id}/status");
+
+        let msg = presence.online_message("dev1");
+        assert_eq!(msg.topic(), "devices/dev1/status");
+
+        let payload = msg.payload_str();
+        let ts = payload.strip_prefix("dev1 up at ").unwrap();
+        assert!(ts.parse::<u64>().unwrap() > 0);
+
+        assert_eq!(fill_fields("{other}", "dev1"), "{other}");
+
+        assert!(presence.uses_client_id());
+        assert!(!PresenceOptions::new("status").uses_client_id());
+        assert!(PresenceOptions::new("status")
+            .offline_payload("{client_id} down")
+            .uses_client_id());
+    }
+}
@@ -27,12 +27,16 @@ use std::{
     any::{Any, TypeId},
     convert::TryFrom,
     ffi::CString,
-    mem,
+    fmt, mem,
     os::raw::{c_char, c_int},
-    ptr,
+    ptr, slice, str,
 };
 
-use crate::{errors::Result, ffi};
+use crate::{
+    errors::{Error, Result},
+    ffi,
+    user_properties::UserProperties,
+};
 
 /// Error code for property mismatches
 const INVALID_PROPERTY_ID: i32 = ffi::MQTT_INVALID_PROPERTY_ID;
@@ -151,6 +155,9 @@ pub enum PropertyCode {
 // Local alias for the C property code integer type
 type Code = ffi::MQTTPropertyCodes;
 
+// The largest property code value
+const MAX_PROPERTY_CODE: Code = PropertyCode::SharedSubscriptionAvailable as Code;
+
 impl PropertyCode {
     /// Tries to create a `PropertyCode` from the integer value.
     pub fn new(code: ffi::MQTTPropertyCodes) -> Option<Self> {
@@ -211,6 +218,205 @@ impl TryFrom<ffi::MQTTPropertyCodes> for PropertyCode {
 
 /////////////////////////////////////////////////////////////////////////////
 
+/// The MQTT v5 packets that can contain properties.
+///
+/// This is used to check that a collection of properties is valid for the
+/// packet that will carry it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PacketType {
+    /// A connection request
+    Connect,
+    /// The acknowledgment of a connection request
+    Connack,
+    /// A published message
+    Publish,
+    /// The acknowledgment of a QoS 1 message
+    Puback,
+    /// A QoS 2 message was received
+    Pubrec,
+    /// A QoS 2 message was released
+    Pubrel,
+    /// A QoS 2 message was completed
+    Pubcomp,
+    /// A subscription request
+    Subscribe,
+    /// The acknowledgment of a subscription request
+    Suback,
+    /// An unsubscribe request
+    Unsubscribe,
+    /// The acknowledgment of an unsubscribe request
+    Unsuback,
+    /// A disconnect notification
+    Disconnect,
+    /// An authentication exchange
+    Auth,
+    /// The Will properties, which are sent in the CONNECT packet, but
+    /// separately from the properties for the connection.
+    Will,
+}
+
+impl fmt::Display for PacketType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PacketType::*;
+        let name = match *self {
+            Connect => "CONNECT",
+            Connack => "CONNACK",
+            Publish => "PUBLISH",
+            Puback => "PUBACK",
+            Pubrec => "PUBREC",
+            Pubrel => "PUBREL",
+            Pubcomp => "PUBCOMP",
+            Subscribe => "SUBSCRIBE",
+            Suback => "SUBACK",
+            Unsubscribe => "UNSUBSCRIBE",
+            Unsuback => "UNSUBACK",
+            Disconnect => "DISCONNECT",
+            Auth => "AUTH",
+            Will => "Will",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PacketType {
+    // Gets the packet type from the MQTT control packet type number in
+    // the fixed header, as reported by the C library in failure data.
+    pub(crate) fn from_control_packet(pkt: i32) -> Option<Self> {
+        use PacketType::*;
+        let pkt_type = match pkt {
+            1 => Connect,
+            2 => Connack,
+            3 => Publish,
+            4 => Puback,
+            5 => Pubrec,
+            6 => Pubrel,
+            7 => Pubcomp,
+            8 => Subscribe,
+            9 => Suback,
+            10 => Unsubscribe,
+            11 => Unsuback,
+            14 => Disconnect,
+            15 => Auth,
+            _ => return None,
+        };
+        Some(pkt_type)
+    }
+}
+
+impl PropertyCode {
+    /// Determines if the property is allowed in the packet, according to
+    /// the table in the MQTT v5 spec:
+    /// <https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901029>
+    pub fn is_allowed_in(&self, pkt: PacketType) -> bool {
+        use PacketType::*;
+        use PropertyCode::*;
+        match *self {
+            PayloadFormatIndicator | MessageExpiryInterval | ContentType | ResponseTopic
+            | CorrelationData => matches!(pkt, Publish | Will),
+            SubscriptionIdentifier => matches!(pkt, Publish | Subscribe),
+            SessionExpiryInterval => matches!(pkt, Connect | Connack | Disconnect),
+            AuthenticationMethod | AuthenticationData => matches!(pkt, Connect | Connack | Auth),
+            RequestProblemInformation | RequestResponseInformation => pkt == Connect,
+            WillDelayInterval => pkt == Will,
+            ServerReference => matches!(pkt, Connack | Disconnect),
+            ReasonString => !matches!(pkt, Connect | Publish | Subscribe | Unsubscribe | Will),
+            ReceiveMaximum | TopicAliasMaximum | MaximumPacketSize => {
+                matches!(pkt, Connect | Connack)
+            }
+            TopicAlias => pkt == Publish,
+            AssignedClientIdentifer
+            | ServerKeepAlive
+            | ResponseInformation
+            | MaximumQos
+            | RetainAvailable
+            | WildcardSubscriptionAvailable
+            | SubscriptionIdentifiersAvailable
+            | SharedSubscriptionAvailable => pkt == Connack,
+            UserProperty => true,
+        }
+    }
+
+    /// Determines if the client can send the property in the packet.
+    ///
+    /// This is stricter than [`is_allowed_in()`](Self::is_allowed_in) for
+    /// a PUBLISH, which goes both ways: subscription identifiers are only
+    /// sent by the server, with the messages it delivers [MQTT-3.3.4-6].
+    pub fn is_allowed_from_client(&self, pkt: PacketType) -> bool {
+        match (*self, pkt) {
+            (PropertyCode::SubscriptionIdentifier, PacketType::Publish) => false,
+            _ => self.is_allowed_in(pkt),
+        }
+    }
+
+    /// Gets the name of the property, as it appears in the MQTT v5 spec,
+    /// without spaces, like "ResponseTopic".
+    pub fn name(&self) -> &'static str {
+        use PropertyCode::*;
+        match *self {
+            PayloadFormatIndicator => "PayloadFormatIndicator",
+            MessageExpiryInterval => "MessageExpiryInterval",
+            ContentType => "ContentType",
+            ResponseTopic => "ResponseTopic",
+            CorrelationData => "CorrelationData",
+            SubscriptionIdentifier => "SubscriptionIdentifier",
+            SessionExpiryInterval => "SessionExpiryInterval",
+            AssignedClientIdentifer => "AssignedClientIdentifier",
+            ServerKeepAlive => "ServerKeepAlive",
+            AuthenticationMethod => "AuthenticationMethod",
+            AuthenticationData => "AuthenticationData",
+            RequestProblemInformation => "RequestProblemInformation",
+            WillDelayInterval => "WillDelayInterval",
+            RequestResponseInformation => "RequestResponseInformation",
+            ResponseInformation => "ResponseInformation",
+            ServerReference => "ServerReference",
+            ReasonString => "ReasonString",
+            ReceiveMaximum => "ReceiveMaximum",
+            TopicAliasMaximum => "TopicAliasMaximum",
+            TopicAlias => "TopicAlias",
+            MaximumQos => "MaximumQoS",
+            RetainAvailable => "RetainAvailable",
+            UserProperty => "UserProperty",
+            MaximumPacketSize => "MaximumPacketSize",
+            WildcardSubscriptionAvailable => "WildcardSubscriptionAvailable",
+            SubscriptionIdentifiersAvailable => "SubscriptionIdentifiersAvailable",
+            SharedSubscriptionAvailable => "SharedSubscriptionAvailable",
+        }
+    }
+
+    /// Determines if the property can appear more than once in the packet.
+    ///
+    /// User properties can be repeated in any packet, and a PUBLISH sent
+    /// from the server can contain multiple subscription identifiers.
+    /// All other properties can appear at most once.
+    pub fn is_repeatable_in(&self, pkt: PacketType) -> bool {
+        match *self {
+            PropertyCode::UserProperty => true,
+            PropertyCode::SubscriptionIdentifier => pkt == PacketType::Publish,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for PropertyCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl str::FromStr for PropertyCode {
+    type Err = Error;
+
+    /// Gets the property code from its name, like "ResponseTopic".
+    fn from_str(name: &str) -> Result<Self> {
+        (1..=MAX_PROPERTY_CODE)
+            .filter_map(PropertyCode::new)
+            .find(|code| code.name() == name)
+            .ok_or(Error::Conversion)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
 /// A single MQTT v5 property.
 ///
 /// An MQTT v5 property consists of both a property "code"  and a value. The
@@ -975,6 +1181,56 @@ impl Properties {
         }
     }
 
+    /// Gets a copy of all the properties in the collection, in order.
+    pub(crate) fn to_vec(&self) -> Vec<Property> {
+        if self.cprops.count <= 0 || self.cprops.array.is_null() {
+            return Vec::new();
+        }
+        let cprops =
+            unsafe { slice::from_raw_parts(self.cprops.array, self.cprops.count as usize) };
+        cprops
+            .iter()
+            .filter_map(|cprop| Property::from_c_property(cprop).ok())
+            .collect()
+    }
+
+    /// Removes all the values for the property code from the collection.
+    pub(crate) fn remove_all(&mut self, code: PropertyCode) {
+        if self.get(code).is_none() {
+            return;
+        }
+        let props = self.to_vec();
+        self.clear();
+        for prop in props {
+            if prop.property_code() != code {
+                let _ = self.push(prop);
+            }
+        }
+    }
+
+    /// Checks that the properties are valid for a packet that the client
+    /// will send with them.
+    ///
+    /// This makes sure that the client can send each property in the
+    /// packet, and that the ones that can only appear once are not
+    /// repeated.
+    pub fn validate(&self, pkt: PacketType) -> Result<()> {
+        let mut seen = Vec::new();
+        for prop in self.to_vec() {
+            let code = prop.property_code();
+            if !code.is_allowed_from_client(pkt) {
+                return Err(Error::PropertyNotAllowed(code, pkt));
+            }
+            if !code.is_repeatable_in(pkt) {
+                if seen.contains(&code) {
+                    return Err(Error::DuplicateProperty(code, pkt));
+                }
+                seen.push(code);
+            }
+        }
+        Ok(())
+    }
+
     /// Gets an iterator for a property instance
     pub fn iter(&self, code: PropertyCode) -> PropertyIterator {
         PropertyIterator {
@@ -1054,6 +1310,9 @@ impl Properties {
 
     /// Searches for the specified key in the user properties and returns
     /// the value if found.
+    ///
+    /// If the key appears more than once, this returns the first value.
+    /// Use [`user_properties()`](Self::user_properties) to get all of them.
     pub fn find_user_property(&self, key: &str) -> Option<String> {
         for (k, v) in self.user_iter() {
             if k == key {
@@ -1062,10 +1321,51 @@ impl Properties {
         }
         None
     }
+
+    /// Gets all the user properties in the collection, as a multimap.
+    pub fn user_properties(&self) -> UserProperties {
+        UserProperties::from_properties(self)
+    }
+
+    /// Replaces all the user properties in the collection with the ones
+    /// given, leaving the other properties alone.
+    pub fn set_user_properties(&mut self, user_props: &UserProperties) {
+        user_props.replace_in(self);
+    }
 }
 
 unsafe impl Send for Properties {}
 
+impl fmt::Display for Properties {
+    /// Formats the properties for logs, like:
+    /// `{ResponseTopic: "replies", CorrelationData: 0x0102, UserProperty: ("a", "b")}`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, prop) in self.to_vec().iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: ", prop.property_code())?;
+            match prop.property_type() {
+                PropertyType::BinaryData => {
+                    write!(f, "0x")?;
+                    for b in prop.get_binary().unwrap_or_default() {
+                        write!(f, "{:02x}", b)?;
+                    }
+                }
+                PropertyType::Utf8EncodedString => {
+                    write!(f, "{:?}", prop.get_string().unwrap_or_default())?
+                }
+                PropertyType::Utf8StringPair => {
+                    write!(f, "{:?}", prop.get_string_pair().unwrap_or_default())?
+                }
+                _ => write!(f, "{}", prop.get_int().unwrap_or_default() as u32)?,
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
 impl Clone for Properties {
     /// Creates a clone of the property.
     /// For string any binary properties, this also clones the heap memory
@@ -1118,6 +1418,185 @@ impl<'a> Iterator for StringPairIterator<'a> {
     }
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// Serde support
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use serde::{
+        de::{self, DeserializeSeed, MapAccess, Visitor},
+        ser::SerializeMap,
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+    use std::result::Result;
+
+    // A single property value, in its natural type.
+    enum Value {
+        Int(u32),
+        String(String),
+        Binary(Binary),
+        StringPair(String, String),
+    }
+
+    impl Value {
+        fn from_property(prop: &Property) -> Option<Self> {
+            match prop.property_type() {
+                PropertyType::BinaryData => prop.get_binary().map(Value::Binary),
+                PropertyType::Utf8EncodedString => prop.get_string().map(Value::String),
+                PropertyType::Utf8StringPair => prop
+                    .get_string_pair()
+                    .map(|(key, val)| Value::StringPair(key, val)),
+                _ => prop.get_int().map(|val| Value::Int(val as u32)),
+            }
+        }
+    }
+
+    impl Serialize for Value {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Value::Int(val) => serializer.serialize_u32(*val),
+                Value::String(s) => serializer.serialize_str(s),
+                Value::Binary(bin) => serializer.serialize_str(&BASE64.encode(bin)),
+                Value::StringPair(key, val) => (key, val).serialize(serializer),
+            }
+        }
+    }
+
+    // Reads a single value of the type required by the property code.
+    struct ValueSeed(PropertyCode);
+
+    impl<'de> DeserializeSeed<'de> for ValueSeed {
+        type Value = Property;
+
+        fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Property, D::Error> {
+            let code = self.0;
+            let prop = match code.property_type() {
+                PropertyType::BinaryData => {
+                    let s = String::deserialize(deserializer)?;
+                    let bin = BASE64.decode(s).map_err(de::Error::custom)?;
+                    Property::new_binary(code, bin)
+                }
+                PropertyType::Utf8EncodedString => {
+                    Property::new_string(code, &String::deserialize(deserializer)?)
+                }
+                PropertyType::Utf8StringPair => {
+                    let (key, val) = <(String, String)>::deserialize(deserializer)?;
+                    Property::new_string_pair(code, &key, &val)
+                }
+                _ => Property::new_int(code, u32::deserialize(deserializer)? as i32),
+            };
+            prop.map_err(|_| de::Error::custom(format!("invalid value for {}", code)))
+        }
+    }
+
+    // Reads a list of values for a repeatable property.
+    struct ValuesSeed(PropertyCode);
+
+    impl<'de> DeserializeSeed<'de> for ValuesSeed {
+        type Value = Vec<Property>;
+
+        fn deserialize<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Vec<Property>, D::Error> {
+            deserializer.deserialize_seq(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for ValuesSeed {
+        type Value = Vec<Property>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a list of {} values", self.0)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<Property>, A::Error>
+        where
+            A: de::SeqAccess<'de>,
+        {
+            let mut props = Vec::new();
+            while let Some(prop) = seq.next_element_seed(ValueSeed(self.0))? {
+                props.push(prop);
+            }
+            Ok(props)
+        }
+    }
+
+    /// The properties are serialized as a map of the property names to
+    /// their values. Integers are numbers, strings are strings, binary
+    /// values are base64-encoded strings, and string pairs are two-element
+    /// lists. User properties and subscription identifiers are lists of
+    /// values, since they can be repeated.
+    impl Serialize for Properties {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let props = self.to_vec();
+            let mut codes: Vec<PropertyCode> = Vec::new();
+            for prop in &props {
+                let code = prop.property_code();
+                if !codes.contains(&code) {
+                    codes.push(code);
+                }
+            }
+
+            let mut map = serializer.serialize_map(Some(codes.len()))?;
+            for code in codes {
+                let mut vals = props
+                    .iter()
+                    .filter(|prop| prop.property_code() == code)
+                    .filter_map(Value::from_property);
+
+                // The properties that can repeat in a PUBLISH are lists
+                if code.is_repeatable_in(PacketType::Publish) {
+                    map.serialize_entry(code.name(), &vals.collect::<Vec<_>>())?;
+                }
+                else if let Some(val) = vals.next() {
+                    map.serialize_entry(code.name(), &val)?;
+                }
+            }
+            map.end()
+        }
+    }
+
+    struct PropertiesVisitor;
+
+    impl<'de> Visitor<'de> for PropertiesVisitor {
+        type Value = Properties;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a map of MQTT v5 property names to values")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Properties, A::Error> {
+            let mut props = Properties::new();
+            while let Some(name) = map.next_key::<String>()? {
+                let code: PropertyCode = name
+                    .parse()
+                    .map_err(|_| de::Error::custom(format!("unknown property: {}", name)))?;
+
+                let vals = if code.is_repeatable_in(PacketType::Publish) {
+                    map.next_value_seed(ValuesSeed(code))?
+                }
+                else {
+                    vec![map.next_value_seed(ValueSeed(code))?]
+                };
+
+                for prop in vals {
+                    props.push(prop).map_err(de::Error::custom)?;
+                }
+            }
+            Ok(props)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Properties {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_map(PropertiesVisitor)
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 //                              Unit Tests
 /////////////////////////////////////////////////////////////////////////////
@@ -1646,4 +2125,117 @@ mod tests {
             props.get_val::<i32>(PropertyCode::SessionExpiryInterval)
         );
     }
+
+    #[test]
+    fn test_properties_validate() {
+        use PacketType::*;
+
+        let props = properties! {
+            PropertyCode::SessionExpiryInterval => 60,
+            PropertyCode::UserProperty => ("a", "1"),
+            PropertyCode::UserProperty => ("a", "2"),
+        };
+        assert!(props.validate(Connect).is_ok());
+        assert!(props.validate(Disconnect).is_ok());
+        assert!(matches!(
+            props.validate(Publish),
+            Err(Error::PropertyNotAllowed(
+                PropertyCode::SessionExpiryInterval,
+                Publish
+            ))
+        ));
+
+        let mut props = Properties::new();
+        props
+            .push_int(PropertyCode::SubscriptionIdentifier, 1)
+            .unwrap();
+        props
+            .push_int(PropertyCode::SubscriptionIdentifier, 2)
+            .unwrap();
+        assert!(matches!(
+            props.validate(Publish),
+            Err(Error::PropertyNotAllowed(
+                PropertyCode::SubscriptionIdentifier,
+                Publish
+            ))
+        ));
+        assert!(matches!(
+            props.validate(Subscribe),
+            Err(Error::DuplicateProperty(
+                PropertyCode::SubscriptionIdentifier,
+                Subscribe
+            ))
+        ));
+        assert!(props.validate(Connect).is_err());
+
+        assert!(Properties::new().validate(Unsubscribe).is_ok());
+        assert!(PropertyCode::WillDelayInterval.is_allowed_in(Will));
+        assert!(!PropertyCode::WillDelayInterval.is_allowed_in(Publish));
+        assert!(PropertyCode::ReasonString.is_allowed_in(Puback));
+        assert!(!PropertyCode::ReasonString.is_allowed_in(Connect));
+
+        assert!(PropertyCode::SubscriptionIdentifier.is_allowed_in(Publish));
+        assert!(!PropertyCode::SubscriptionIdentifier.is_allowed_from_client(Publish));
+        assert!(PropertyCode::SubscriptionIdentifier.is_allowed_from_client(Subscribe));
+        assert!(!PropertyCode::WillDelayInterval.is_allowed_from_client(Publish));
+    }
+
+    #[test]
+    fn test_property_code_name() {
+        assert_eq!(PropertyCode::ResponseTopic.name(), "ResponseTopic");
+        assert_eq!(
+            PropertyCode::AssignedClientIdentifer.to_string(),
+            "AssignedClientIdentifier"
+        );
+        assert_eq!(
+            "MaximumQoS".parse::<PropertyCode>().unwrap(),
+            PropertyCode::MaximumQos
+        );
+        assert!("NotAProperty".parse::<PropertyCode>().is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Properties::new().to_string(), "{}");
+
+        let props = properties! {
+            PropertyCode::ResponseTopic => "replies",
+            PropertyCode::CorrelationData => b"\x01\xab".to_vec(),
+            PropertyCode::MessageExpiryInterval => 30,
+            PropertyCode::UserProperty => ("a", "1"),
+        };
+        assert_eq!(
+            props.to_string(),
+            r#"{ResponseTopic: "replies", CorrelationData: 0x01ab, MessageExpiryInterval: 30, UserProperty: ("a", "1")}"#
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut props = properties! {
+            PropertyCode::ContentType => "text/plain",
+            PropertyCode::CorrelationData => b"id-1".to_vec(),
+            PropertyCode::MessageExpiryInterval => 30,
+            PropertyCode::UserProperty => ("a", "1"),
+            PropertyCode::UserProperty => ("a", "2"),
+        };
+        props
+            .push_int(PropertyCode::SubscriptionIdentifier, 42)
+            .unwrap();
+
+        let json = serde_json::to_string(&props).unwrap();
+        assert_eq!(
+            json,
+            r#"{"ContentType":"text/plain","CorrelationData":"aWQtMQ==","MessageExpiryInterval":30,"UserProperty":[["a","1"],["a","2"]],"SubscriptionIdentifier":[42]}"#
+        );
+
+        let props2: Properties = serde_json::from_str(&json).unwrap();
+        assert_eq!(props2.len(), 6);
+        assert_eq!(props2.to_string(), props.to_string());
+
+        assert!(serde_json::from_str::<Properties>(r#"{"Bogus":1}"#).is_err());
+        assert!(serde_json::from_str::<Properties>(r#"{"ContentType":1}"#).is_err());
+        assert!(serde_json::from_str::<Properties>(r#"{"CorrelationData":"!!"}"#).is_err());
+    }
 }
@@ -0,0 +1,801 @@
+// quota_persistence.rs
+//
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! A persistence wrapper that limits the number of bytes kept in the store.
+//!
+//! The `max_buffered_messages` and `delete_oldest_messages` creation options
+//! limit the _number_ of messages kept by the client while it is offline,
+//! but not the amount of space they take up. The [`QuotaPersistence`]
+//! wraps any other [`ClientPersistence`] object and keeps the total size
+//! of the stored data under a byte budget, evicting entries according to
+//! an [`EvictionPolicy`] when a new entry would not otherwise fit.
+//!
+//! Only the messages waiting to be sent can be evicted. These are the
+//! commands that the C library persists while the client is offline.
+//! The state of the QoS 1 and 2 messages that are in flight is needed to
+//! complete the protocol exchange with the server, and is never evicted.
+//!
+//! Note that the quota only applies to the data in the store. The C library
+//! also keeps the messages waiting to be sent in memory, and evicting one
+//! from the store does not remove it from there. So an evicted message is
+//! still sent if the client reconnects before it is restarted, but it is
+//! lost if the client is restarted, and recovers its messages from the
+//! store.
+
+use crate::{client_persistence::ClientPersistence, errors::Result, types::QoS, Error};
+use std::{collections::HashMap, fmt};
+
+/// The policy used to select which entries to remove from the store when
+/// the byte budget is exceeded.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub enum EvictionPolicy {
+    /// Remove the oldest entries first.
+    #[default]
+    OldestFirst,
+    /// Remove the entries with the lowest QoS first, then the oldest of
+    /// those with the same QoS.
+    LowestQosFirst,
+    /// Remove the entries with the lowest priority first, where the
+    /// priority is an integer value taken from the MQTT v5 user property
+    /// with the specified name. Entries without the property have a
+    /// priority of zero. Ties are broken by age, oldest first.
+    UserPropertyPriority(String),
+}
+
+/// Information about a single entry in the persistence store, used to
+/// decide the eviction order.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EntryInfo {
+    /// The QoS of the message, if the entry holds a message.
+    pub qos: Option<QoS>,
+    /// The eviction priority of the entry. Lower values get evicted first.
+    pub priority: i32,
+}
+
+/// An entry that was evicted from the store to stay within the quota.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvictedEntry {
+    /// The persistence key of the entry
+    pub key: String,
+    /// The number of bytes the entry occupied
+    pub size: usize,
+    /// The information used to select the entry for eviction
+    pub info: EntryInfo,
+}
+
+/// Callback to inspect the data being placed into the store and determine
+/// its eviction information.
+pub type InspectorCallback = dyn Fn(&str, &[u8]) -> EntryInfo + Send + 'static;
+
+/// Callback to be notified when an entry is evicted from the store.
+pub type EvictionCallback = dyn FnMut(&EvictedEntry) + Send + 'static;
+
+// The bookkeeping for each entry in the store.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    size: usize,
+    seq: u64,
+    info: EntryInfo,
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A client persistence wrapper that enforces a byte budget on any other
+/// persistence store.
+///
+/// When a `put()` would take the total size of the store over the quota,
+/// existing entries are removed, according to the eviction policy, until
+/// the new data fits. If it can't fit, even in an otherwise empty store,
+/// the `put()` fails with a persistence error.
+///
+/// By default the QoS and priority of an entry are found by parsing the
+/// data as a publish command, which is how the C library stores the
+/// messages waiting to be sent. Other entries get a default `EntryInfo`.
+/// An application can supply its own inspector to classify the entries
+/// differently.
+///
+/// The in-flight entries are never evicted, so the store can go over the
+/// quota while it holds more in-flight data than that.
+///
+/// ```
+/// use paho_mqtt as mqtt;
+///
+/// # struct Store;
+/// # impl mqtt::ClientPersistence for Store {
+/// #     fn open(&mut self, _: &str, _: &str) -> mqtt::Result<()> { Ok(()) }
+/// #     fn close(&mut self) -> mqtt::Result<()> { Ok(()) }
+/// #     fn put(&mut self, _: &str, _: Vec<&[u8]>) -> mqtt::Result<()> { Ok(()) }
+/// #     fn get(&mut self, _: &str) -> mqtt::Result<Vec<u8>> { Ok(vec![]) }
+/// #     fn remove(&mut self, _: &str) -> mqtt::Result<()> { Ok(()) }
+/// #     fn keys(&mut self) -> mqtt::Result<Vec<String>> { Ok(vec![]) }
+/// #     fn clear(&mut self) -> mqtt::Result<()> { Ok(()) }
+/// #     fn contains_key(&mut self, _: &str) -> bool { false }
+/// # }
+/// let persist = mqtt::QuotaPersistence::new(Store, 4 * 1024 * 1024)
+///     .eviction_policy(mqtt::EvictionPolicy::LowestQosFirst)
+///     .on_eviction(|entry| println!("Evicted '{}'", entry.key));
+///
+/// let opts = mqtt::CreateOptionsBuilder::new()
+///     .user_persistence(persist)
+///     .finalize();
+/// ```
+pub struct QuotaPersistence<P> {
+    /// The underlying store
+    inner: P,
+    /// The maximum number of bytes to keep in the store
+    quota: usize,
+    /// The eviction policy
+    policy: EvictionPolicy,
+    /// The current size of the store, in bytes
+    size: usize,
+    /// The sequence number for the next entry (to track age)
+    seq: u64,
+    /// The bookkeeping for all the entries in the store
+    entries: HashMap<String, Entry>,
+    /// An optional, user-supplied inspector
+    inspector: Option<Box<InspectorCallback>>,
+    /// An optional eviction callback
+    on_eviction: Option<Box<EvictionCallback>>,
+}
+
+impl<P: ClientPersistence> QuotaPersistence<P> {
+    /// Creates a new persistence wrapper that keeps the total size of the
+    /// data in the `inner` store under `quota` bytes.
+    pub fn new(inner: P, quota: usize) -> Self {
+        Self {
+            inner,
+            quota,
+            policy: EvictionPolicy::default(),
+            size: 0,
+            seq: 0,
+            entries: HashMap::new(),
+            inspector: None,
+            on_eviction: None,
+        }
+    }
+
+    /// Sets the policy used to select the entries to remove when the
+    /// quota is exceeded.
+    pub fn eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets a function to determine the eviction information for the data
+    /// placed into the store. This replaces the default inspector which
+    /// parses the publish commands stored by the C library.
+    pub fn inspector<F>(mut self, cb: F) -> Self
+    where
+        F: Fn(&str, &[u8]) -> EntryInfo + Send + 'static,
+    {
+        self.inspector = Some(Box::new(cb));
+        self
+    }
+
+    /// Sets a callback to be notified whenever an entry is evicted from
+    /// the store to stay within the quota.
+    pub fn on_eviction<F>(mut self, cb: F) -> Self
+    where
+        F: FnMut(&EvictedEntry) + Send + 'static,
+    {
+        self.on_eviction = Some(Box::new(cb));
+        self
+    }
+
+    /// Gets the maximum number of bytes allowed in the store.
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+
+    /// Gets the number of bytes currently in the store.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Gets a reference to the underlying persistence store.
+    pub fn get_ref(&self) -> &P {
+        &self.inner
+    }
+
+    /// Consumes the wrapper, returning the underlying persistence store.
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    // Determines the eviction information for the data.
+    fn inspect(&self, key: &str, data: &[u8]) -> EntryInfo {
+        match self.inspector {
+            Some(ref inspector) => inspector(key, data),
+            None => {
+                let prop_name = match self.policy {
+                    EvictionPolicy::UserPropertyPriority(ref name) => Some(name.as_str()),
+                    _ => None,
+                };
+                inspect_command(key, data, prop_name)
+            }
+        }
+    }
+
+    // Adds the entry to the bookkeeping, replacing any previous entry
+    // with the same key.
+    fn track(&mut self, key: &str, size: usize, info: EntryInfo) {
+        let seq = self.seq;
+        self.seq += 1;
+        if let Some(old) = self.entries.insert(key.to_string(), Entry { size, seq, info }) {
+            self.size -= old.size;
+        }
+        self.size += size;
+    }
+
+    // Removes the entry from the bookkeeping.
+    fn untrack(&mut self, key: &str) {
+        if let Some(old) = self.entries.remove(key) {
+            self.size -= old.size;
+        }
+    }
+
+    // Selects the next entry to evict, other than the specified key.
+    // In-flight entries are never selected.
+    fn eviction_candidate(&self, skip_key: &str) -> Option<String> {
+        let by_qos = self.policy == EvictionPolicy::LowestQosFirst;
+        let by_prio = matches!(self.policy, EvictionPolicy::UserPropertyPriority(_));
+
+        self.entries
+            .iter()
+            .filter(|(key, _)| key.as_str() != skip_key && !is_in_flight_key(key))
+            .min_by_key(|(_, e)| {
+                let qos = if by_qos { e.info.qos.map(|q| q as i32 + 1).unwrap_or(0) } else { 0 };
+                let prio = if by_prio { e.info.priority } else { 0 };
+                (qos, prio, e.seq)
+            })
+            .map(|(key, _)| key.clone())
+    }
+
+    // Evicts entries until `needed` more bytes will fit in the store,
+    // never touching the entry for `key`.
+    fn make_room(&mut self, key: &str, needed: usize) -> Result<()> {
+        let existing = self.entries.get(key).map(|e| e.size).unwrap_or(0);
+
+        while self.size - existing + needed > self.quota {
+            let victim = match self.eviction_candidate(key) {
+                Some(victim) => victim,
+                None => {
+                    warn!("Persistence store over quota with in-flight data");
+                    break;
+                }
+            };
+            self.inner.remove(&victim)?;
+            if let Some(entry) = self.entries.remove(&victim) {
+                self.size -= entry.size;
+                debug!("Quota persistence evicted '{}' ({} bytes)", victim, entry.size);
+                if let Some(ref mut cb) = self.on_eviction {
+                    cb(&EvictedEntry {
+                        key: victim,
+                        size: entry.size,
+                        info: entry.info,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P: ClientPersistence> ClientPersistence for QuotaPersistence<P> {
+    /// Opens the underlying store and takes an inventory of the data
+    /// already in it.
+    fn open(&mut self, client_id: &str, server_uri: &str) -> Result<()> {
+        self.inner.open(client_id, server_uri)?;
+
+        self.entries.clear();
+        self.size = 0;
+
+        let mut keys = self.inner.keys()?;
+        keys.sort();
+
+        for key in keys {
+            let data = self.inner.get(&key)?;
+            let info = self.inspect(&key, &data);
+            self.track(&key, data.len(), info);
+        }
+
+        if self.size > self.quota {
+            warn!(
+                "Persistence store opened with {} bytes, over the quota of {} bytes",
+                self.size, self.quota
+            );
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn put(&mut self, key: &str, buffers: Vec<&[u8]>) -> Result<()> {
+        let size: usize = buffers.iter().map(|b| b.len()).sum();
+
+        if size > self.quota {
+            warn!(
+                "Persistence entry '{}' of {} bytes exceeds the quota of {} bytes",
+                key, size, self.quota
+            );
+            return Err(Error::PersistenceError);
+        }
+
+        self.make_room(key, size)?;
+
+        let info = if buffers.len() == 1 {
+            self.inspect(key, buffers[0])
+        }
+        else {
+            self.inspect(key, &buffers.concat())
+        };
+
+        self.inner.put(key, buffers)?;
+        self.track(key, size, info);
+        Ok(())
+    }
+
+    fn get(&mut self, key: &str) -> Result<Vec<u8>> {
+        self.inner.get(key)
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.inner.remove(key)?;
+        self.untrack(key);
+        Ok(())
+    }
+
+    fn keys(&mut self) -> Result<Vec<String>> {
+        self.inner.keys()
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.inner.clear()?;
+        self.entries.clear();
+        self.size = 0;
+        Ok(())
+    }
+
+    fn contains_key(&mut self, key: &str) -> bool {
+        self.inner.contains_key(key)
+    }
+}
+
+impl<P> fmt::Debug for QuotaPersistence<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotaPersistence")
+            .field("quota", &self.quota)
+            .field("size", &self.size)
+            .field("policy", &self.policy)
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Command inspection
+
+// The command type of a PUBLISH, in the C library's command format.
+const PUBLISH_COMMAND: i32 = 3;
+
+// Gets the key prefix, like "c5" for "c5-12".
+fn key_prefix(key: &str) -> &str {
+    key.split('-').next().unwrap_or_default()
+}
+
+// The C library persists the state of in-flight messages with keys like
+// "s-" (sent), "sc-" (sent PUBREL), and "r-" (received), and the v5
+// versions of each, like "s5-".
+fn is_in_flight_key(key: &str) -> bool {
+    key.contains('-') && matches!(key_prefix(key), "s" | "s5" | "sc" | "sc5" | "r" | "r5")
+}
+
+// The C library persists queued commands with keys like "c-" or "c5-".
+fn is_command_key(key: &str) -> bool {
+    key.contains('-') && matches!(key_prefix(key), "c" | "c5")
+}
+
+// A simple reader for the persisted data.
+struct DataReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> DataReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < n {
+            return None;
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    // An integer in the native byte order, as written by the C library
+    fn int(&mut self) -> Option<i32> {
+        self.take(4).map(|b| i32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // A NUL-terminated string
+    fn c_str(&mut self) -> Option<&'a [u8]> {
+        let n = self.buf.iter().position(|b| *b == 0)?;
+        let s = self.take(n)?;
+        self.take(1)?;
+        Some(s)
+    }
+
+    fn varint(&mut self) -> Option<usize> {
+        let mut val = 0usize;
+        for i in 0..4 {
+            let b = self.u8()?;
+            val |= ((b & 0x7F) as usize) << (7 * i);
+            if b & 0x80 == 0 {
+                return Some(val);
+            }
+        }
+        None
+    }
+
+    fn binary(&mut self) -> Option<&'a [u8]> {
+        let n = self.u16()? as usize;
+        self.take(n)
+    }
+}
+
+// Gets the eviction info by parsing the data as a publish command, in
+// the format that the C library persists them while the client is
+// offline:
+//
+//   type, token, topic (NUL-terminated), payload length, payload, QoS,
+//   retained, and, for v5, the properties in the MQTT wire format,
+//
+// where the type, token, length, QoS and retained flag are native `int`s.
+//
+// If `prop_name` is given, the priority is parsed from the value of the
+// user property with that name.
+fn inspect_command(key: &str, data: &[u8], prop_name: Option<&str>) -> EntryInfo {
+    let mut info = EntryInfo::default();
+    if !is_command_key(key) {
+        return info;
+    }
+
+    let mut rdr = DataReader { buf: data };
+    if rdr.int() != Some(PUBLISH_COMMAND) {
+        return info;
+    }
+
+    let qos = match publish_command_qos(&mut rdr) {
+        Some(qos) => qos,
+        None => return info,
+    };
+    info.qos = Some(qos);
+
+    if let Some(name) = prop_name {
+        if key_prefix(key) == "c5" {
+            if let Some(prio) = user_property(&mut rdr, name) {
+                info.priority = prio;
+            }
+        }
+    }
+    info
+}
+
+// Reads through a publish command, after the type, to get the QoS,
+// leaving the reader at the properties, if any.
+fn publish_command_qos(rdr: &mut DataReader) -> Option<QoS> {
+    rdr.int()?;
+    rdr.c_str()?;
+    let len = usize::try_from(rdr.int()?).ok()?;
+    rdr.take(len)?;
+    let qos = rdr.int()?;
+    rdr.int()?;
+    if (0..=2).contains(&qos) {
+        Some(QoS::from(qos))
+    }
+    else {
+        None
+    }
+}
+
+// Reads MQTT v5 properties in the wire format to find the integer value
+// of the named user property.
+fn user_property(rdr: &mut DataReader, name: &str) -> Option<i32> {
+    let len = rdr.varint()?;
+    let mut props = DataReader { buf: rdr.take(len)? };
+
+    while !props.buf.is_empty() {
+        match props.varint()? {
+            // Byte
+            0x01 | 0x17 | 0x19 | 0x24 | 0x25 | 0x28 | 0x29 | 0x2A => {
+                props.u8()?;
+            }
+            // Two-byte integer
+            0x13 | 0x21 | 0x22 | 0x23 => {
+                props.u16()?;
+            }
+            // Four-byte integer
+            0x02 | 0x11 | 0x18 | 0x27 => {
+                props.take(4)?;
+            }
+            // Variable-byte integer
+            0x0B => {
+                props.varint()?;
+            }
+            // String or binary data
+            0x03 | 0x08 | 0x09 | 0x12 | 0x15 | 0x16 | 0x1A | 0x1C | 0x1F => {
+                props.binary()?;
+            }
+            // User property (string pair)
+            0x26 => {
+                let key = props.binary()?;
+                let val = props.binary()?;
+                if key == name.as_bytes() {
+                    return std::str::from_utf8(val).ok()?.trim().parse().ok();
+                }
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    // A simple in-memory store for testing.
+    #[derive(Default)]
+    struct MemPersistence {
+        map: HashMap<String, Vec<u8>>,
+    }
+
+    impl ClientPersistence for MemPersistence {
+        fn open(&mut self, _client_id: &str, _server_uri: &str) -> Result<()> {
+            Ok(())
+        }
+        fn close(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn put(&mut self, key: &str, buffers: Vec<&[u8]>) -> Result<()> {
+            self.map.insert(key.to_string(), buffers.concat());
+            Ok(())
+        }
+        fn get(&mut self, key: &str) -> Result<Vec<u8>> {
+            self.map.get(key).cloned().ok_or(Error::PersistenceError)
+        }
+        fn remove(&mut self, key: &str) -> Result<()> {
+            self.map.remove(key);
+            Ok(())
+        }
+        fn keys(&mut self) -> Result<Vec<String>> {
+            Ok(self.map.keys().cloned().collect())
+        }
+        fn clear(&mut self) -> Result<()> {
+            self.map.clear();
+            Ok(())
+        }
+        fn contains_key(&mut self, key: &str) -> bool {
+            self.map.contains_key(key)
+        }
+    }
+
+    // Creates a publish command, as persisted by the C library, with the
+    // QoS, payload size, and v5 properties, if any.
+    fn publish_command(qos: i32, payload_len: usize, props: Option<&[u8]>) -> Vec<u8> {
+        let mut cmd = Vec::new();
+        cmd.extend_from_slice(&PUBLISH_COMMAND.to_ne_bytes());
+        cmd.extend_from_slice(&1i32.to_ne_bytes());
+        cmd.extend_from_slice(b"t\0");
+        cmd.extend_from_slice(&(payload_len as i32).to_ne_bytes());
+        cmd.extend(std::iter::repeat(0u8).take(payload_len));
+        cmd.extend_from_slice(&qos.to_ne_bytes());
+        cmd.extend_from_slice(&0i32.to_ne_bytes());
+        if let Some(props) = props {
+            cmd.push(props.len() as u8);
+            cmd.extend_from_slice(props);
+        }
+        cmd
+    }
+
+    // Creates a v5 QoS 1 publish command with a "prio" user property.
+    fn publish_command_v5(prio: &str) -> Vec<u8> {
+        let mut props = vec![0x01, 0x00, 0x26, 0, 4];
+        props.extend_from_slice(b"prio");
+        props.extend_from_slice(&[0, prio.len() as u8]);
+        props.extend_from_slice(prio.as_bytes());
+        publish_command(1, 4, Some(&props))
+    }
+
+    #[test]
+    fn test_inspect_command() {
+        let info = inspect_command("c-1", &publish_command(2, 4, None), None);
+        assert_eq!(info.qos, Some(QoS::ExactlyOnce));
+        assert_eq!(info.priority, 0);
+
+        let info = inspect_command("c-1", b"\x01\x02\x03", None);
+        assert_eq!(info, EntryInfo::default());
+
+        let info = inspect_command("c5-1", &publish_command_v5("7"), Some("prio"));
+        assert_eq!(info.qos, Some(QoS::AtLeastOnce));
+        assert_eq!(info.priority, 7);
+
+        // Property names are only parsed for v5 keys
+        let info = inspect_command("c-1", &publish_command_v5("7"), Some("prio"));
+        assert_eq!(info.qos, Some(QoS::AtLeastOnce));
+        assert_eq!(info.priority, 0);
+
+        // Only commands are inspected
+        let info = inspect_command("s-1", &publish_command(2, 4, None), None);
+        assert_eq!(info, EntryInfo::default());
+    }
+
+    #[test]
+    fn test_keys() {
+        assert!(is_command_key("c-1"));
+        assert!(is_command_key("c5-12"));
+        assert!(!is_command_key("c"));
+        assert!(!is_command_key("sc-1"));
+
+        for key in ["s-1", "s5-1", "sc-1", "sc5-1", "r-1", "r5-1"] {
+            assert!(is_in_flight_key(key));
+        }
+        assert!(!is_in_flight_key("c-1"));
+        assert!(!is_in_flight_key("s"));
+    }
+
+    #[test]
+    fn test_oldest_first() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let ev = evicted.clone();
+
+        let mut persist = QuotaPersistence::new(MemPersistence::default(), 25)
+            .on_eviction(move |entry| ev.lock().unwrap().push(entry.key.clone()));
+
+        persist.put("a", vec![&[0u8; 10]]).unwrap();
+        persist.put("b", vec![&[0u8; 10]]).unwrap();
+        assert_eq!(persist.size(), 20);
+
+        persist.put("c", vec![&[0u8; 5], &[0u8; 5]]).unwrap();
+        assert_eq!(persist.size(), 20);
+        assert!(!persist.contains_key("a"));
+        assert!(persist.contains_key("b"));
+        assert!(persist.contains_key("c"));
+        assert_eq!(*evicted.lock().unwrap(), vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn test_replace_existing() {
+        let mut persist = QuotaPersistence::new(MemPersistence::default(), 20);
+
+        persist.put("a", vec![&[0u8; 10]]).unwrap();
+        persist.put("b", vec![&[0u8; 10]]).unwrap();
+        persist.put("b", vec![&[0u8; 5]]).unwrap();
+        assert_eq!(persist.size(), 15);
+        assert!(persist.contains_key("a"));
+
+        persist.remove("a").unwrap();
+        assert_eq!(persist.size(), 5);
+    }
+
+    #[test]
+    fn test_lowest_qos_first() {
+        let n = publish_command(0, 10, None).len();
+        let mut persist = QuotaPersistence::new(MemPersistence::default(), 2 * n + 1)
+            .eviction_policy(EvictionPolicy::LowestQosFirst);
+
+        persist.put("c-1", vec![&publish_command(2, 10, None)]).unwrap();
+        persist.put("c-2", vec![&publish_command(0, 10, None)]).unwrap();
+        persist.put("c-3", vec![&publish_command(1, 10, None)]).unwrap();
+
+        assert!(!persist.contains_key("c-2"));
+        assert!(persist.contains_key("c-1"));
+        assert!(persist.contains_key("c-3"));
+    }
+
+    #[test]
+    fn test_priority() {
+        let cmd = publish_command_v5("5");
+        let n = cmd.len();
+
+        let mut persist = QuotaPersistence::new(MemPersistence::default(), 2 * n + 1)
+            .eviction_policy(EvictionPolicy::UserPropertyPriority("prio".into()));
+
+        persist.put("c5-1", vec![&cmd]).unwrap();
+        persist.put("c5-2", vec![&publish_command_v5("1")]).unwrap();
+        persist.put("c5-3", vec![&publish_command_v5("9")]).unwrap();
+
+        assert!(persist.contains_key("c5-1"));
+        assert!(!persist.contains_key("c5-2"));
+        assert!(persist.contains_key("c5-3"));
+    }
+
+    #[test]
+    fn test_in_flight_kept() {
+        let mut persist = QuotaPersistence::new(MemPersistence::default(), 25);
+
+        persist.put("s-1", vec![&[0u8; 10]]).unwrap();
+        persist.put("c-1", vec![&[0u8; 10]]).unwrap();
+        persist.put("c-2", vec![&[0u8; 10]]).unwrap();
+        assert!(persist.contains_key("s-1"));
+        assert!(!persist.contains_key("c-1"));
+
+        // With nothing left to evict, in-flight data goes over the quota
+        persist.put("r-1", vec![&[0u8; 10]]).unwrap();
+        assert!(!persist.contains_key("c-2"));
+        persist.put("sc-1", vec![&[0u8; 10]]).unwrap();
+        assert_eq!(persist.size(), 30);
+    }
+
+    #[test]
+    fn test_custom_inspector() {
+        let mut persist = QuotaPersistence::new(MemPersistence::default(), 20)
+            .eviction_policy(EvictionPolicy::UserPropertyPriority("prio".into()))
+            .inspector(|key, _| EntryInfo {
+                qos: None,
+                priority: if key.starts_with("keep") { 10 } else { 0 },
+            });
+
+        persist.put("keep", vec![&[0u8; 10]]).unwrap();
+        persist.put("drop", vec![&[0u8; 10]]).unwrap();
+        persist.put("new", vec![&[0u8; 10]]).unwrap();
+
+        assert!(persist.contains_key("keep"));
+        assert!(!persist.contains_key("drop"));
+        assert!(persist.contains_key("new"));
+    }
+
+    #[test]
+    fn test_too_big() {
+        let mut persist = QuotaPersistence::new(MemPersistence::default(), 8);
+        persist.put("a", vec![&[0u8; 4]]).unwrap();
+        assert!(persist.put("b", vec![&[0u8; 10]]).is_err());
+        assert!(persist.contains_key("a"));
+    }
+
+    #[test]
+    fn test_open_inventory() {
+        let mut mem = MemPersistence::default();
+        mem.put("a", vec![&[0u8; 10]]).unwrap();
+        mem.put("b", vec![&[0u8; 6]]).unwrap();
+
+        let mut persist = QuotaPersistence::new(mem, 20);
+        persist.open("id", "tcp://localhost:1883").unwrap();
+        assert_eq!(persist.size(), 16);
+
+        persist.put("c", vec![&[0u8; 10]]).unwrap();
+        assert_eq!(persist.size(), 16);
+        assert!(!persist.contains_key("a"));
+    }
+}
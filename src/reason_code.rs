@@ -96,6 +96,53 @@ impl ReasonCode {
     pub fn is_err(&self) -> bool {
         (*self as u32) >= 0x80
     }
+
+    /// Determines if the failure is likely to be temporary, so that the
+    /// operation might succeed if retried later, or on another server.
+    ///
+    /// This covers the server being busy, unavailable, or shutting down,
+    /// a redirect to another server, and the client exceeding a rate or
+    /// quota limit. It doesn't cover a session taken over by another
+    /// client or an administrative disconnect, where reconnecting would
+    /// undo what was intended, or a packet identifier in use, which is a
+    /// fault in the client.
+    pub fn is_retryable(&self) -> bool {
+        use ReasonCode::*;
+        matches!(
+            *self,
+            ServerUnavailable
+                | ServerBusy
+                | ServerShuttingDown
+                | KeepAliveTimeout
+                | ReceiveMaximumExceeded
+                | MessageRateTooHigh
+                | QuotaExceeded
+                | UseAnotherServer
+                | ServerMoved
+                | ConnectionRateExceeded
+                | MaximumConnectTime
+        )
+    }
+
+    /// Determines if the server rejected the client's credentials or
+    /// permissions.
+    pub fn is_auth_failure(&self) -> bool {
+        use ReasonCode::*;
+        matches!(
+            *self,
+            BadUserNameOrPassword | NotAuthorized | Banned | BadAuthenticationMethod
+        )
+    }
+
+    /// Determines if the client exceeded a quota or rate limit imposed by
+    /// the server.
+    pub fn is_quota_exceeded(&self) -> bool {
+        use ReasonCode::*;
+        matches!(
+            *self,
+            QuotaExceeded | MessageRateTooHigh | ReceiveMaximumExceeded | ConnectionRateExceeded
+        )
+    }
 }
 
 impl Default for ReasonCode {
@@ -245,6 +292,25 @@ mod tests {
         assert!(ReasonCode::ServerMoved.is_err());
     }
 
+    #[test]
+    fn test_classification() {
+        assert!(ReasonCode::ServerBusy.is_retryable());
+        assert!(ReasonCode::QuotaExceeded.is_retryable());
+        assert!(!ReasonCode::NotAuthorized.is_retryable());
+        assert!(!ReasonCode::SessionTakenOver.is_retryable());
+        assert!(!ReasonCode::PacketIdentifierInUse.is_retryable());
+        assert!(!ReasonCode::AdministrativeAction.is_retryable());
+        assert!(!ReasonCode::Success.is_retryable());
+
+        assert!(ReasonCode::BadUserNameOrPassword.is_auth_failure());
+        assert!(ReasonCode::NotAuthorized.is_auth_failure());
+        assert!(!ReasonCode::ServerBusy.is_auth_failure());
+
+        assert!(ReasonCode::QuotaExceeded.is_quota_exceeded());
+        assert!(ReasonCode::MessageRateTooHigh.is_quota_exceeded());
+        assert!(!ReasonCode::TopicNameInvalid.is_quota_exceeded());
+    }
+
     // Note: These strings are from the Paho C library in MQTTReasonCodes.c
     // They may change between versions, but we mainly want to see that
     // the Display trait is working.
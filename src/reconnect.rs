@@ -0,0 +1,469 @@
+// reconnect.rs
+//
+// Policies for reconnecting to the server when the connection is lost.
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! Policies for reconnecting to the server when the connection is lost.
+//!
+//! The automatic reconnect in the connect options is done by the C library,
+//! which doubles the delay between attempts, in whole seconds. When many
+//! clients lose their connection at once, like when a broker restarts,
+//! they all retry in lockstep.
+//!
+//! A [`ReconnectPolicy`] set on the client with
+//! [`AsyncClient::set_reconnect_policy()`](crate::AsyncClient::set_reconnect_policy)
+//! reconnects from the Rust side instead. When the connection is lost, the
+//! policy is asked for the delay before each attempt, given the attempt
+//! number and the reason the last one failed, and the client calls
+//! [`reconnect()`](crate::AsyncClient::reconnect) once the delay is up.
+//! Each attempt is reported to the callback set with
+//! [`AsyncClient::set_reconnect_callback()`](crate::AsyncClient::set_reconnect_callback).
+//!
+//! The built-in policies are [`ExponentialBackoff`], with optional
+//! [`Jitter`] to spread out the attempts from many clients, and
+//! [`FixedInterval`]. Any policy can be limited to a number of attempts
+//! with [`ReconnectPolicy::give_up_after()`].
+
+use crate::errors::Error;
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::{Condvar, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// A policy for reconnecting to the server after the connection is lost.
+///
+/// Any closure of the form `FnMut(u32, &Error) -> Option<Duration>` can be
+/// used as a policy.
+pub trait ReconnectPolicy: Send + 'static {
+    /// Gets the time to wait before the next attempt to reconnect, or
+    /// `None` to give up.
+    ///
+    /// The `attempt` starts at one after the connection is lost. The
+    /// `reason` is why the last attempt failed or, for the first attempt,
+    /// why the connection was lost.
+    fn next_delay(&mut self, attempt: u32, reason: &Error) -> Option<Duration>;
+
+    /// Resets any state in the policy, before it starts reconnecting after
+    /// a lost connection.
+    fn reset(&mut self) {}
+
+    /// Limits the policy to a number of attempts, after which the client
+    /// gives up.
+    fn give_up_after(self, max_attempts: u32) -> GiveUpAfter<Self>
+    where
+        Self: Sized,
+    {
+        GiveUpAfter::new(self, max_attempts)
+    }
+}
+
+impl<F> ReconnectPolicy for F
+where
+    F: FnMut(u32, &Error) -> Option<Duration> + Send + 'static,
+{
+    fn next_delay(&mut self, attempt: u32, reason: &Error) -> Option<Duration> {
+        self(attempt, reason)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// The randomness applied to an exponential backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    /// No jitter. The delay doubles with each attempt.
+    None,
+    /// The delay is random, between zero and the doubled delay.
+    Full,
+    /// The delay is random, between the minimum and three times the
+    /// previous delay. This spreads out the clients, while still keeping
+    /// the delays from getting too short.
+    Decorrelated,
+}
+
+/// A reconnect policy with delays that grow exponentially, up to a limit.
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+    min: Duration,
+    max: Duration,
+    jitter: Jitter,
+    /// The previous delay, for decorrelated jitter
+    prev: Duration,
+}
+
+impl ExponentialBackoff {
+    /// Creates a policy that doubles the delay from `min` with each attempt,
+    /// up to `max`, without any jitter.
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max: max.max(min),
+            jitter: Jitter::None,
+            prev: min,
+        }
+    }
+
+    /// Creates a policy with full jitter, where each delay is random, up to
+    /// the exponentially growing limit.
+    pub fn full_jitter(min: Duration, max: Duration) -> Self {
+        Self::new(min, max).jitter(Jitter::Full)
+    }
+
+    /// Creates a policy with decorrelated jitter, where each delay is
+    /// random, between `min` and three times the previous delay, up to
+    /// `max`.
+    pub fn decorrelated_jitter(min: Duration, max: Duration) -> Self {
+        Self::new(min, max).jitter(Jitter::Decorrelated)
+    }
+
+    /// Sets the jitter applied to the delays.
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    // Gets the delay without any jitter, doubling from the minimum.
+    fn exp_delay(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31);
+        self.min
+            .checked_mul(1 << exp)
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+}
+
+impl Default for ExponentialBackoff {
+    /// A policy from one second to one minute, with full jitter.
+    fn default() -> Self {
+        Self::full_jitter(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
+
+impl ReconnectPolicy for ExponentialBackoff {
+    fn next_delay(&mut self, attempt: u32, _reason: &Error) -> Option<Duration> {
+        let delay = match self.jitter {
+            Jitter::None => self.exp_delay(attempt),
+            Jitter::Full => random_between(Duration::ZERO, self.exp_delay(attempt)),
+            Jitter::Decorrelated => {
+                let hi = self.prev.checked_mul(3).unwrap_or(self.max);
+                random_between(self.min, hi).min(self.max)
+            }
+        };
+        self.prev = delay;
+        Some(delay)
+    }
+
+    fn reset(&mut self) {
+        self.prev = self.min;
+    }
+}
+
+/// A reconnect policy with the same delay before each attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedInterval {
+    interval: Duration,
+}
+
+impl FixedInterval {
+    /// Creates a policy that waits the same time before each attempt.
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+impl ReconnectPolicy for FixedInterval {
+    fn next_delay(&mut self, _attempt: u32, _reason: &Error) -> Option<Duration> {
+        Some(self.interval)
+    }
+}
+
+/// A reconnect policy that gives up after a number of attempts.
+///
+/// This is usually created with [`ReconnectPolicy::give_up_after()`].
+#[derive(Debug, Clone)]
+pub struct GiveUpAfter<P> {
+    policy: P,
+    max_attempts: u32,
+}
+
+impl<P: ReconnectPolicy> GiveUpAfter<P> {
+    /// Limits the policy to `max_attempts` attempts.
+    pub fn new(policy: P, max_attempts: u32) -> Self {
+        Self {
+            policy,
+            max_attempts,
+        }
+    }
+}
+
+impl<P: ReconnectPolicy> ReconnectPolicy for GiveUpAfter<P> {
+    fn next_delay(&mut self, attempt: u32, reason: &Error) -> Option<Duration> {
+        if attempt > self.max_attempts {
+            return None;
+        }
+        self.policy.next_delay(attempt, reason)
+    }
+
+    fn reset(&mut self) {
+        self.policy.reset();
+    }
+}
+
+// Gets a random number.
+//
+// This only needs to spread out the reconnects from different clients, so
+// it uses the randomly-keyed hasher from the std library rather than
+// bringing in a random number crate.
+pub(crate) fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(t) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u32(t.subsec_nanos());
+    }
+    hasher.finish()
+}
+
+// Gets a random duration in the range [lo, hi].
+fn random_between(lo: Duration, hi: Duration) -> Duration {
+    if hi <= lo {
+        return lo;
+    }
+    let span = (hi - lo).as_nanos().min(u64::MAX as u128 - 1) as u64;
+    lo + Duration::from_nanos(random_u64() % (span + 1))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// An attempt to reconnect to the server, made by the reconnect policy.
+///
+/// This is passed to the reconnect callback just before the attempt.
+#[derive(Debug)]
+pub struct ReconnectAttempt {
+    pub(crate) attempt: u32,
+    pub(crate) delay: Duration,
+    pub(crate) reason: Error,
+}
+
+impl ReconnectAttempt {
+    /// Gets the number of the attempt, starting at one after the connection
+    /// is lost.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Gets the time waited before the attempt.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Gets the reason the previous attempt failed or, for the first
+    /// attempt, why the connection was lost.
+    pub fn reason(&self) -> &Error {
+        &self.reason
+    }
+}
+
+// The reconnect policy of a client, along with the state of the thread
+// that is reconnecting, if any.
+//
+// Each time reconnecting is cancelled, the generation is bumped, so that
+// a thread from an earlier generation knows to stop.
+#[derive(Default)]
+pub(crate) struct Reconnector {
+    state: Mutex<ReconnectState>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct ReconnectState {
+    policy: Option<Box<dyn ReconnectPolicy>>,
+    generation: u64,
+    active: bool,
+}
+
+impl Reconnector {
+    // Sets or removes the policy, stopping any reconnect in progress.
+    pub(crate) fn set_policy(&self, policy: Option<Box<dyn ReconnectPolicy>>) {
+        let mut state = self.state.lock().unwrap();
+        state.policy = policy;
+        Self::cancel_locked(&mut state);
+        self.cond.notify_all();
+    }
+
+    // Determines if there's a policy.
+    pub(crate) fn has_policy(&self) -> bool {
+        self.state.lock().unwrap().policy.is_some()
+    }
+
+    // Stops any reconnect in progress.
+    pub(crate) fn cancel(&self) {
+        Self::cancel_locked(&mut self.state.lock().unwrap());
+        self.cond.notify_all();
+    }
+
+    fn cancel_locked(state: &mut ReconnectState) {
+        state.generation += 1;
+        state.active = false;
+    }
+
+    // Starts reconnecting, if there's a policy and it isn't already
+    // running, returning the generation for the new thread.
+    pub(crate) fn start(&self) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        if state.active {
+            return None;
+        }
+        state.policy.as_mut()?.reset();
+        state.active = true;
+        Some(state.generation)
+    }
+
+    // Gets the delay before the next attempt from the policy, and waits it
+    // out. Returns `None` if the policy gives up or reconnecting is
+    // cancelled in the meantime.
+    pub(crate) fn wait(&self, gen: u64, attempt: u32, reason: &Error) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != gen {
+            return None;
+        }
+
+        let delay = state
+            .policy
+            .as_mut()
+            .and_then(|policy| policy.next_delay(attempt, reason));
+
+        let delay = match delay {
+            Some(delay) => delay,
+            None => {
+                warn!("Giving up reconnecting after {} attempt(s)", attempt - 1);
+                state.active = false;
+                return None;
+            }
+        };
+
+        let (state, _) = self
+            .cond
+            .wait_timeout_while(state, delay, |state| state.generation == gen)
+            .unwrap();
+
+        if state.generation != gen {
+            return None;
+        }
+        Some(delay)
+    }
+
+    // Marks the thread of the generation as done.
+    pub(crate) fn finish(&self, gen: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation == gen {
+            state.active = false;
+        }
+    }
+}
+
+impl fmt::Debug for Reconnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("Reconnector")
+            .field("policy", &state.policy.is_some())
+            .field("active", &state.active)
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_exponential() {
+        let mut policy = ExponentialBackoff::new(SEC, 10 * SEC);
+        let err = Error::Disconnected;
+
+        let delays: Vec<_> = (1..=6)
+            .map(|n| policy.next_delay(n, &err).unwrap().as_secs())
+            .collect();
+        assert_eq!(delays, [1, 2, 4, 8, 10, 10]);
+        assert_eq!(policy.next_delay(1000, &err), Some(10 * SEC));
+    }
+
+    #[test]
+    fn test_jitter() {
+        let err = Error::Disconnected;
+
+        let mut policy = ExponentialBackoff::full_jitter(SEC, 10 * SEC);
+        for n in 1..20 {
+            let delay = policy.next_delay(n, &err).unwrap();
+            assert!(delay <= policy.exp_delay(n));
+        }
+
+        let mut policy = ExponentialBackoff::decorrelated_jitter(SEC, 10 * SEC);
+        let mut prev = SEC;
+        for n in 1..20 {
+            let delay = policy.next_delay(n, &err).unwrap();
+            assert!(delay >= SEC && delay <= 10 * SEC && delay <= 3 * prev);
+            prev = delay;
+        }
+        policy.reset();
+        assert!(policy.next_delay(1, &err).unwrap() <= 3 * SEC);
+    }
+
+    #[test]
+    fn test_give_up() {
+        let err = Error::Disconnected;
+        let mut policy = FixedInterval::new(SEC).give_up_after(2);
+
+        assert_eq!(policy.next_delay(1, &err), Some(SEC));
+        assert_eq!(policy.next_delay(2, &err), Some(SEC));
+        assert_eq!(policy.next_delay(3, &err), None);
+
+        let mut policy = |n: u32, err: &Error| (n < 3 && err.is_retryable()).then_some(SEC);
+        assert_eq!(policy.next_delay(1, &err), Some(SEC));
+        assert_eq!(policy.next_delay(1, &Error::BadProtocol), None);
+    }
+
+    #[test]
+    fn test_reconnector() {
+        let rc = Reconnector::default();
+        assert!(rc.start().is_none());
+
+        rc.set_policy(Some(Box::new(FixedInterval::new(Duration::ZERO).give_up_after(1))));
+        let gen = rc.start().unwrap();
+        assert!(rc.start().is_none());
+
+        let err = Error::Disconnected;
+        assert_eq!(rc.wait(gen, 1, &err), Some(Duration::ZERO));
+        assert_eq!(rc.wait(gen, 2, &err), None);
+
+        // Cancelling stops a thread that's waiting
+        rc.set_policy(Some(Box::new(FixedInterval::new(60 * SEC))));
+        let gen = rc.start().unwrap();
+        rc.cancel();
+        assert_eq!(rc.wait(gen, 1, &err), None);
+        assert!(rc.start().is_some());
+    }
+}
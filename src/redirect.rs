@@ -0,0 +1,341 @@
+// redirect.rs
+//
+// Support for following MQTT v5 server redirection.
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! Support for following MQTT v5 server redirection.
+//!
+//! An MQTT v5 server can tell a client to use a different server by
+//! rejecting the connection (in the CONNACK) or by disconnecting it (in a
+//! DISCONNECT) with a reason code of _Use Another Server_ (temporary) or
+//! _Server Moved_ (permanent), along with a _Server Reference_ property
+//! naming the new server(s).
+//!
+//! When a [`RedirectPolicy`] is set on the client with
+//! [`AsyncClient::set_redirect_policy()`](crate::AsyncClient::set_redirect_policy),
+//! the client follows these redirects automatically, reconnecting to the
+//! referenced server, and reports each one to the callback set with
+//! [`AsyncClient::set_redirect_callback()`](crate::AsyncClient::set_redirect_callback).
+
+use crate::{properties::PacketType, reason_code::ReasonCode, server_uri::Scheme};
+
+/// The default limit on the number of consecutive redirects to follow.
+pub const DEFAULT_MAX_REDIRECTS: u32 = 3;
+
+/// Whether a server redirect is temporary or permanent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RedirectKind {
+    /// The client should use the other server for now, from a reason code
+    /// of _Use Another Server_ (0x9C).
+    Temporary,
+    /// The client should use the other server from now on, from a reason
+    /// code of _Server Moved_ (0x9D).
+    Permanent,
+}
+
+impl RedirectKind {
+    /// Gets the kind of redirect from the reason code sent by the server,
+    /// if the code is a redirect.
+    pub fn from_reason_code(reason_code: ReasonCode) -> Option<Self> {
+        match reason_code {
+            ReasonCode::UseAnotherServer => Some(RedirectKind::Temporary),
+            ReasonCode::ServerMoved => Some(RedirectKind::Permanent),
+            _ => None,
+        }
+    }
+}
+
+/// The policy for following server redirects.
+///
+/// This is opt-in: a client does not follow redirects unless a policy is
+/// set. By default the policy follows both temporary and permanent
+/// redirects, up to [`DEFAULT_MAX_REDIRECTS`] in a row.
+///
+/// When following a _temporary_ redirect, the client connects to the
+/// referenced server, but keeps its connect options, so that a later
+/// call to [`reconnect()`](crate::AsyncClient::reconnect) returns to the
+/// original server(s). When following a _permanent_ redirect, the
+/// referenced server replaces the ones in the client's connect options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedirectPolicy {
+    max_redirects: u32,
+    follow_temporary: bool,
+    follow_permanent: bool,
+}
+
+impl RedirectPolicy {
+    /// Creates a policy that follows up to `max_redirects` consecutive
+    /// redirects of either kind.
+    ///
+    /// The count is reset each time the client connects successfully,
+    /// so the limit protects against servers redirecting in a loop.
+    pub fn new(max_redirects: u32) -> Self {
+        Self {
+            max_redirects,
+            follow_temporary: true,
+            follow_permanent: true,
+        }
+    }
+
+    /// Sets whether temporary redirects, _Use Another Server_, are
+    /// followed.
+    pub fn follow_temporary(mut self, on: bool) -> Self {
+        self.follow_temporary = on;
+        self
+    }
+
+    /// Sets whether permanent redirects, _Server Moved_, are followed.
+    pub fn follow_permanent(mut self, on: bool) -> Self {
+        self.follow_permanent = on;
+        self
+    }
+
+    /// Gets the limit on the number of consecutive redirects to follow.
+    pub fn max_redirects(&self) -> u32 {
+        self.max_redirects
+    }
+
+    /// Determines if the policy follows the kind of redirect.
+    pub fn follows(&self, kind: RedirectKind) -> bool {
+        match kind {
+            RedirectKind::Temporary => self.follow_temporary,
+            RedirectKind::Permanent => self.follow_permanent,
+        }
+    }
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_REDIRECTS)
+    }
+}
+
+/// A server redirect being followed by the client.
+///
+/// This is passed to the redirect callback before the client connects to
+/// the new server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerRedirect {
+    pub(crate) kind: RedirectKind,
+    pub(crate) packet_type: PacketType,
+    pub(crate) server_reference: String,
+    pub(crate) server_uris: Vec<String>,
+    pub(crate) count: u32,
+}
+
+impl ServerRedirect {
+    /// Gets whether the redirect is temporary or permanent.
+    pub fn kind(&self) -> RedirectKind {
+        self.kind
+    }
+
+    /// Gets the type of packet that carried the redirect: CONNACK or
+    /// DISCONNECT.
+    pub fn packet_type(&self) -> PacketType {
+        self.packet_type
+    }
+
+    /// Gets the Server Reference property, as sent by the server.
+    pub fn server_reference(&self) -> &str {
+        &self.server_reference
+    }
+
+    /// Gets the URIs of the servers that the client will try, in order.
+    pub fn server_uris(&self) -> &[String] {
+        &self.server_uris
+    }
+
+    /// Gets the number of consecutive redirects followed, including this
+    /// one.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Converts an MQTT v5 Server Reference into a list of server URIs.
+///
+/// The reference is a space-separated list of servers, each of the form
+/// `host[:port]`, where an IPv6 host is in square brackets. Each server
+/// gets the scheme (like "tcp" or "ssl") of `current_uri`, the server the
+/// client is being redirected from, so a secure connection stays secure.
+/// A server without a port gets the port of `current_uri`, if it has one,
+/// and for a WebSocket connection, the path of `current_uri`.
+///
+/// An entry that is already a full URI, with a scheme, is used as-is,
+/// unless `current_uri` is secure and the entry is not. Those entries are
+/// dropped, so that a server can't downgrade the connection.
+pub fn parse_server_reference(reference: &str, current_uri: &str) -> Vec<String> {
+    let (scheme, rest) = match current_uri.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None => ("tcp", current_uri),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let port = split_port(authority).1;
+
+    let secure = is_secure_scheme(scheme);
+    let path = match scheme.parse::<Scheme>() {
+        Ok(scheme) if scheme.is_websocket() => path,
+        _ => "",
+    };
+
+    reference
+        .split_whitespace()
+        .filter_map(|server| {
+            if let Some((server_scheme, _)) = server.split_once("://") {
+                if secure && !is_secure_scheme(server_scheme) {
+                    warn!("Ignoring redirect to insecure server: {}", server);
+                    return None;
+                }
+                return Some(server.to_string());
+            }
+            let uri = match split_port(server) {
+                (host, Some(port)) => format!("{}://{}:{}", scheme, host, port),
+                (host, None) => match port {
+                    Some(port) => format!("{}://{}:{}", scheme, host, port),
+                    None => format!("{}://{}", scheme, host),
+                },
+            };
+            Some(uri + path)
+        })
+        .collect()
+}
+
+// Determines if the URI scheme is for a secure connection.
+fn is_secure_scheme(scheme: &str) -> bool {
+    scheme
+        .parse::<Scheme>()
+        .map_or(false, |scheme| scheme.is_secure())
+}
+
+// Splits a `host[:port]` string into the host and optional port.
+// A bare IPv6 address, with more than one colon, is put in brackets.
+fn split_port(server: &str) -> (String, Option<&str>) {
+    if server.starts_with('[') {
+        return match server.split_once("]:") {
+            Some((host, port)) => (format!("{}]", host), Some(port)),
+            None => (server.to_string(), None),
+        };
+    }
+    match server.matches(':').count() {
+        0 => (server.to_string(), None),
+        1 => {
+            let (host, port) = server.split_once(':').unwrap();
+            (host.to_string(), Some(port))
+        }
+        _ => (format!("[{}]", server), None),
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy() {
+        let policy = RedirectPolicy::default();
+        assert_eq!(policy.max_redirects(), DEFAULT_MAX_REDIRECTS);
+        assert!(policy.follows(RedirectKind::Temporary));
+        assert!(policy.follows(RedirectKind::Permanent));
+
+        let policy = RedirectPolicy::new(1).follow_temporary(false);
+        assert!(!policy.follows(RedirectKind::Temporary));
+        assert!(policy.follows(RedirectKind::Permanent));
+
+        assert_eq!(
+            RedirectKind::from_reason_code(ReasonCode::ServerMoved),
+            Some(RedirectKind::Permanent)
+        );
+        assert_eq!(
+            RedirectKind::from_reason_code(ReasonCode::UseAnotherServer),
+            Some(RedirectKind::Temporary)
+        );
+        assert_eq!(RedirectKind::from_reason_code(ReasonCode::ServerBusy), None);
+    }
+
+    #[test]
+    fn test_parse_server_reference() {
+        assert_eq!(
+            parse_server_reference("broker2:1884", "ssl://broker1:8883"),
+            ["ssl://broker2:1884"]
+        );
+        assert_eq!(
+            parse_server_reference("broker2 broker3:1885", "tcp://broker1:1883"),
+            ["tcp://broker2:1883", "tcp://broker3:1885"]
+        );
+        assert_eq!(
+            parse_server_reference("[::1]:1884 [fe80::2]", "mqtt://broker1"),
+            ["mqtt://[::1]:1884", "mqtt://[fe80::2]"]
+        );
+        assert_eq!(
+            parse_server_reference("::1", "tcp://[::2]:1883"),
+            ["tcp://[::1]:1883"]
+        );
+        assert_eq!(
+            parse_server_reference("wss://broker2:443/mqtt", "tcp://broker1:1883"),
+            ["wss://broker2:443/mqtt"]
+        );
+        assert_eq!(
+            parse_server_reference("broker2", "localhost:1883"),
+            ["tcp://broker2:1883"]
+        );
+        assert!(parse_server_reference("  ", "tcp://broker1:1883").is_empty());
+    }
+
+    #[test]
+    fn test_parse_server_reference_secure() {
+        // A secure connection can't be redirected to an insecure one
+        assert_eq!(
+            parse_server_reference(
+                "tcp://broker2:1883 ws://broker3/mqtt mqtts://broker4",
+                "ssl://broker1:8883"
+            ),
+            ["mqtts://broker4"]
+        );
+        assert!(parse_server_reference("TCP://broker2", "wss://broker1").is_empty());
+        assert_eq!(
+            parse_server_reference("wss://broker2/mqtt", "mqtts://broker1"),
+            ["wss://broker2/mqtt"]
+        );
+    }
+
+    #[test]
+    fn test_parse_server_reference_websocket() {
+        assert_eq!(
+            parse_server_reference("broker2 broker3:8443", "wss://broker1:443/mqtt"),
+            ["wss://broker2:443/mqtt", "wss://broker3:8443/mqtt"]
+        );
+        assert_eq!(
+            parse_server_reference("broker2", "ws://broker1"),
+            ["ws://broker2"]
+        );
+        // Only WebSocket connections have a path
+        assert_eq!(
+            parse_server_reference("broker2", "tcp://broker1:1883/"),
+            ["tcp://broker2:1883"]
+        );
+    }
+}
@@ -0,0 +1,291 @@
+// retained_cache.rs
+//
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! A cache of the most recent message received on each topic.
+//!
+//! This is a common pattern for applications, like dashboards, that
+//! subscribe to a wildcard filter just to keep the latest value published
+//! to each topic, which is what the server does with retained messages.
+
+use crate::{
+    async_client::AsyncClient, errors::Result, message::Message, token::SubscribeToken,
+    topic::TopicFilter, topic_matcher::topic_matches, types::QoS,
+};
+use async_channel::Sender;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// A change to the contents of a [`RetainedCache`].
+#[derive(Debug, Clone)]
+pub enum CacheEvent {
+    /// A message was added to the cache, or replaced the previous message
+    /// for the same topic.
+    Updated(Message),
+    /// The message for the topic was removed from the cache.
+    Removed(String),
+}
+
+// The shared state of the cache.
+#[derive(Default)]
+struct CacheInner {
+    /// The last message for each topic
+    msgs: HashMap<String, Message>,
+    /// The senders for the change notification streams
+    watchers: Vec<Sender<CacheEvent>>,
+}
+
+impl CacheInner {
+    // Sends the event to all the watchers, dropping any that were closed.
+    fn notify(&mut self, evt: CacheEvent) {
+        self.watchers.retain(|tx| tx.try_send(evt.clone()).is_ok());
+    }
+}
+
+/// A cache that keeps the last message received on each concrete topic
+/// that matches a topic filter.
+///
+/// A message with an empty payload removes the topic from the cache,
+/// following the convention that publishing an empty retained message
+/// clears the retained value for a topic on the server. This is honored
+/// whether or not the retain flag is set on the incoming message, since
+/// the server clears the flag on messages forwarded to existing
+/// subscribers.
+///
+/// The cache can be cheaply cloned and shared between threads. All the
+/// clones refer to the same collection of messages.
+#[derive(Clone)]
+pub struct RetainedCache {
+    /// The filter for the topics to keep in the cache.
+    filter: TopicFilter,
+    /// The messages and watchers
+    inner: Arc<Mutex<CacheInner>>,
+}
+
+impl RetainedCache {
+    /// Creates a new, empty cache for the topics matching the filter.
+    pub fn new<S>(filter: S) -> Result<Self>
+    where
+        S: Into<String>,
+    {
+        Ok(Self {
+            filter: TopicFilter::new(filter)?,
+            inner: Arc::default(),
+        })
+    }
+
+    /// Attaches the cache to the client, and subscribes to the filter.
+    ///
+    /// This installs a message callback in the client to feed the cache,
+    /// replacing any callback that was previously set. Applications that
+    /// need to process messages themselves should instead call
+    /// [`RetainedCache::update`] for each incoming message.
+    pub fn attach<Q>(&self, cli: &AsyncClient, qos: Q) -> SubscribeToken
+    where
+        Q: Into<QoS>,
+    {
+        let cache = self.clone();
+        cli.set_message_callback(move |_cli, msg| {
+            if let Some(msg) = msg {
+                cache.update(&msg);
+            }
+        });
+        cli.subscribe(self.filter.to_string(), qos)
+    }
+
+    /// Gets the topic filter for the cache.
+    pub fn filter(&self) -> &TopicFilter {
+        &self.filter
+    }
+
+    /// Updates the cache with an incoming message.
+    ///
+    /// Messages whose topic doesn't match the filter for the cache are
+    /// ignored. Returns `true` if the contents of the cache changed.
+    pub fn update(&self, msg: &Message) -> bool {
+        let topic = msg.topic();
+        if !self.filter.is_match(topic) {
+            return false;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+
+        if msg.payload().is_empty() {
+            if inner.msgs.remove(topic).is_none() {
+                return false;
+            }
+            inner.notify(CacheEvent::Removed(topic.to_string()));
+        }
+        else {
+            inner.msgs.insert(topic.to_string(), msg.clone());
+            inner.notify(CacheEvent::Updated(msg.clone()));
+        }
+        true
+    }
+
+    /// Gets the last message received on the topic, if any.
+    pub fn get(&self, topic: &str) -> Option<Message> {
+        self.inner.lock().unwrap().msgs.get(topic).cloned()
+    }
+
+    /// Gets all the messages in the cache for topics that match the
+    /// specified topic filter.
+    pub fn matches(&self, filter: &str) -> Vec<Message> {
+        self.inner
+            .lock()
+            .unwrap()
+            .msgs
+            .iter()
+            .filter(|(topic, _)| topic_matches(filter, topic))
+            .map(|(_, msg)| msg.clone())
+            .collect()
+    }
+
+    /// Gets all the topics currently in the cache.
+    pub fn topics(&self) -> Vec<String> {
+        self.inner.lock().unwrap().msgs.keys().cloned().collect()
+    }
+
+    /// Gets a copy of all the messages currently in the cache.
+    pub fn messages(&self) -> Vec<Message> {
+        self.inner.lock().unwrap().msgs.values().cloned().collect()
+    }
+
+    /// Removes the message for the topic from the cache, returning it,
+    /// if it was there.
+    pub fn remove(&self, topic: &str) -> Option<Message> {
+        let mut inner = self.inner.lock().unwrap();
+        let msg = inner.msgs.remove(topic);
+        if msg.is_some() {
+            inner.notify(CacheEvent::Removed(topic.to_string()));
+        }
+        msg
+    }
+
+    /// Removes all the messages from the cache.
+    /// Watchers get a `Removed` event for each topic.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let topics: Vec<String> = inner.msgs.drain().map(|(topic, _)| topic).collect();
+        for topic in topics {
+            inner.notify(CacheEvent::Removed(topic));
+        }
+    }
+
+    /// Gets the number of topics in the cache.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().msgs.len()
+    }
+
+    /// Determines if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().msgs.is_empty()
+    }
+
+    /// Gets a stream of the changes to the cache.
+    ///
+    /// Each call creates a new stream which receives the changes from
+    /// that point onward. The stream is unbounded, so the application
+    /// should keep reading it, or drop it when it's no longer needed.
+    pub fn changes(&self) -> crate::AsyncReceiver<CacheEvent> {
+        let (tx, rx) = async_channel::unbounded();
+        self.inner.lock().unwrap().watchers.push(tx);
+        rx
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_and_get() {
+        let cache = RetainedCache::new("data/#").unwrap();
+        assert!(cache.is_empty());
+
+        assert!(cache.update(&Message::new_retained("data/a", "1", 1)));
+        assert!(cache.update(&Message::new("data/b", "2", 1)));
+        assert!(!cache.update(&Message::new("other/c", "3", 1)));
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.update(&Message::new("data/a", "4", 1)));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("data/a").unwrap().payload_str(), "4");
+        assert!(cache.get("other/c").is_none());
+    }
+
+    #[test]
+    fn test_empty_payload_deletes() {
+        let cache = RetainedCache::new("data/+").unwrap();
+
+        cache.update(&Message::new_retained("data/a", "1", 1));
+        assert!(cache.get("data/a").is_some());
+
+        assert!(cache.update(&Message::new_retained("data/a", "", 1)));
+        assert!(cache.get("data/a").is_none());
+        assert!(cache.is_empty());
+
+        // Removing a missing topic is not a change
+        assert!(!cache.update(&Message::new("data/a", "", 1)));
+    }
+
+    #[test]
+    fn test_matches() {
+        let cache = RetainedCache::new("#").unwrap();
+
+        cache.update(&Message::new("site/1/temp", "20", 0));
+        cache.update(&Message::new("site/2/temp", "21", 0));
+        cache.update(&Message::new("site/2/humidity", "40", 0));
+
+        assert_eq!(cache.matches("site/+/temp").len(), 2);
+        assert_eq!(cache.matches("site/2/#").len(), 2);
+        assert_eq!(cache.matches("site/3/#").len(), 0);
+    }
+
+    #[test]
+    fn test_changes() {
+        let cache = RetainedCache::new("data/#").unwrap();
+        let rx = cache.changes();
+
+        cache.update(&Message::new("data/a", "1", 0));
+        cache.update(&Message::new("data/a", "", 0));
+
+        match rx.try_recv().unwrap() {
+            CacheEvent::Updated(msg) => assert_eq!(msg.topic(), "data/a"),
+            evt => panic!("Unexpected event: {:?}", evt),
+        }
+        match rx.try_recv().unwrap() {
+            CacheEvent::Removed(topic) => assert_eq!(topic, "data/a"),
+            evt => panic!("Unexpected event: {:?}", evt),
+        }
+        assert!(rx.try_recv().is_err());
+
+        // Closed streams are dropped from the cache
+        drop(rx);
+        cache.update(&Message::new("data/b", "1", 0));
+        assert!(cache.inner.lock().unwrap().watchers.is_empty());
+    }
+}
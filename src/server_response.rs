@@ -31,7 +31,14 @@
 //! combined with any other Rust futures.
 //!
 
-use crate::{ffi, from_c_bool, properties::Properties, reason_code::ReasonCode};
+use crate::{
+    ffi, from_c_bool,
+    properties::{PacketType, Properties},
+    reason_code::ReasonCode,
+    server_uri::ServerUri,
+    user_properties::UserProperties,
+    Result,
+};
 use std::ffi::CStr;
 
 /////////////////////////////////////////////////////////////////////////////
@@ -56,6 +63,19 @@ pub enum ServerRequest {
     UnsubscribeMany(usize),
 }
 
+impl ServerRequest {
+    // Gets the type of packet the server sends to acknowledge the request.
+    pub(crate) fn ack_packet_type(&self) -> Option<PacketType> {
+        use ServerRequest::*;
+        match *self {
+            None => Option::None,
+            Connect => Some(PacketType::Connack),
+            Subscribe | SubscribeMany(_) => Some(PacketType::Suback),
+            Unsubscribe | UnsubscribeMany(_) => Some(PacketType::Unsuback),
+        }
+    }
+}
+
 impl Default for ServerRequest {
     fn default() -> Self {
         ServerRequest::None
@@ -100,6 +120,13 @@ pub struct ConnectResponse {
     pub session_present: bool,
 }
 
+impl ConnectResponse {
+    /// Parses the URI of the server that the client connected to.
+    pub fn parsed_server_uri(&self) -> Result<ServerUri> {
+        ServerUri::new(&self.server_uri)
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // ServerResponse
 
@@ -306,6 +333,11 @@ impl ServerResponse {
         &self.props
     }
 
+    /// Gets the MQTT v5 user properties returned from the server.
+    pub fn user_properties(&self) -> UserProperties {
+        self.props.user_properties()
+    }
+
     /// Gets the reason code returned from the server.
     pub fn reason_code(&self) -> ReasonCode {
         self.reason_code
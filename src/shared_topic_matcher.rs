@@ -0,0 +1,265 @@
+// shared_topic_matcher.rs
+//
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! A topic matcher that can be shared between threads, with lock-free
+//! lookups.
+//!
+//! This uses a read-copy-update (RCU) scheme in which readers get an
+//! immutable snapshot of the collection, while writers make a copy of the
+//! collection, update it, and then atomically swap it in as the current
+//! snapshot. This is ideal for routing tables that are consulted for every
+//! incoming message, but only change occasionally, as subscriptions are
+//! added and removed.
+
+use crate::topic_matcher::TopicMatcher;
+use arc_swap::ArcSwap;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// A collection of topic filters that can be shared between threads,
+/// allowing lock-free lookups while it's being updated.
+///
+/// Lookups are done against an atomically-loaded snapshot of the
+/// collection, so they never block, even while another thread is updating
+/// the collection. Updates clone the current collection, so they are
+/// relatively expensive, and are serialized with each other. A lookup that
+/// races with an update will see the collection either entirely before or
+/// entirely after the update.
+///
+/// ```
+/// use paho_mqtt::SharedTopicMatcher;
+/// use std::{sync::Arc, thread};
+///
+/// let matcher = Arc::new(SharedTopicMatcher::new());
+/// matcher.insert("data/+/temp", 1);
+///
+/// let m = matcher.clone();
+/// thread::spawn(move || m.insert("data/#", 2)).join().unwrap();
+///
+/// let mut vals: Vec<i32> = Vec::new();
+/// matcher.for_each_match("data/engine/temp", |_filter, val| vals.push(*val));
+/// vals.sort();
+/// assert_eq!(vals, vec![1, 2]);
+/// ```
+pub struct SharedTopicMatcher<T> {
+    /// The current snapshot of the collection
+    current: ArcSwap<TopicMatcher<T>>,
+    /// Lock to serialize the writers
+    writer: Mutex<()>,
+}
+
+impl<T> SharedTopicMatcher<T> {
+    /// Creates a new, empty, collection.
+    pub fn new() -> Self {
+        Self::from(TopicMatcher::new())
+    }
+
+    /// Gets a snapshot of the current state of the collection.
+    ///
+    /// The snapshot is not affected by any subsequent updates, and can be
+    /// kept for as long as needed, although it does keep the memory for
+    /// that version of the collection alive.
+    pub fn snapshot(&self) -> Arc<TopicMatcher<T>> {
+        self.current.load_full()
+    }
+
+    /// Determines if the collection contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.current.load().is_empty()
+    }
+
+    /// Determines if the topic matches any of the filters in the collection.
+    pub fn has_match(&self, topic: &str) -> bool {
+        self.current.load().has_match(topic)
+    }
+
+    /// Calls the function for each of the filters in the collection that
+    /// match the topic, with the filter and its value.
+    pub fn for_each_match<F>(&self, topic: &str, mut f: F)
+    where
+        F: FnMut(&str, &T),
+    {
+        let snapshot = self.current.load();
+        for (filter, val) in snapshot.matches(topic) {
+            f(filter, val);
+        }
+    }
+
+    /// Replaces the entire contents of the collection.
+    pub fn store(&self, matcher: TopicMatcher<T>) {
+        let _guard = self.writer.lock().unwrap();
+        self.current.store(Arc::new(matcher));
+    }
+
+    /// Removes all the entries in the collection.
+    pub fn clear(&self) {
+        self.store(TopicMatcher::new());
+    }
+}
+
+impl<T: Clone> SharedTopicMatcher<T> {
+    /// Updates the collection.
+    ///
+    /// The function is called with a copy of the current collection, which
+    /// it can modify as needed. When it returns, the copy atomically
+    /// replaces the current collection. Any value returned by the function
+    /// is passed back to the caller.
+    pub fn update<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut TopicMatcher<T>) -> R,
+    {
+        let _guard = self.writer.lock().unwrap();
+        let mut matcher = TopicMatcher::clone(&self.current.load());
+        let ret = f(&mut matcher);
+        self.current.store(Arc::new(matcher));
+        ret
+    }
+
+    /// Inserts a new topic filter and value into the collection.
+    pub fn insert<S>(&self, filter: S, val: T)
+    where
+        S: Into<String>,
+    {
+        self.update(|matcher| matcher.insert(filter, val));
+    }
+
+    /// Inserts multiple filters all with (a clone of) the same value.
+    pub fn insert_many<S: AsRef<str>>(&self, filters: &[S], val: T) {
+        self.update(|matcher| matcher.insert_many(filters, val));
+    }
+
+    /// Removes the entry with the filter, returning its value, if found.
+    pub fn remove(&self, filter: &str) -> Option<T> {
+        self.update(|matcher| matcher.remove(filter))
+    }
+
+    /// Gets a copy of the value for the specified filter, if any.
+    pub fn get(&self, filter: &str) -> Option<T> {
+        self.current.load().get(filter).cloned()
+    }
+
+    /// Gets copies of all the filters and values that match the topic.
+    pub fn matches(&self, topic: &str) -> Vec<(String, T)> {
+        self.current
+            .load()
+            .matches(topic)
+            .map(|(filter, val)| (filter.to_string(), val.clone()))
+            .collect()
+    }
+}
+
+impl<T> Default for SharedTopicMatcher<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<TopicMatcher<T>> for SharedTopicMatcher<T> {
+    fn from(matcher: TopicMatcher<T>) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(matcher),
+            writer: Mutex::new(()),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SharedTopicMatcher<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedTopicMatcher")
+            .field("current", &self.current.load())
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_shared_matcher() {
+        let matcher = SharedTopicMatcher::new();
+        assert!(matcher.is_empty());
+
+        matcher.insert("some/+/topic", 1);
+        matcher.insert("some/#", 2);
+        assert!(!matcher.is_empty());
+        assert!(matcher.has_match("some/test/topic"));
+        assert!(!matcher.has_match("other/topic"));
+        assert_eq!(matcher.get("some/#"), Some(2));
+
+        let mut vals: Vec<_> = matcher
+            .matches("some/test/topic")
+            .into_iter()
+            .map(|(_, v)| v)
+            .collect();
+        vals.sort();
+        assert_eq!(vals, vec![1, 2]);
+
+        assert_eq!(matcher.remove("some/#"), Some(2));
+        assert_eq!(matcher.remove("some/#"), None);
+        assert_eq!(matcher.matches("some/test/topic").len(), 1);
+
+        matcher.clear();
+        assert!(matcher.is_empty());
+    }
+
+    #[test]
+    fn test_shared_matcher_snapshot() {
+        let matcher = SharedTopicMatcher::new();
+        matcher.insert("a/b", 1);
+
+        let snapshot = matcher.snapshot();
+        matcher.insert("a/+", 2);
+
+        assert_eq!(snapshot.matches("a/b").count(), 1);
+        assert_eq!(matcher.snapshot().matches("a/b").count(), 2);
+    }
+
+    #[test]
+    fn test_shared_matcher_threads() {
+        let matcher = Arc::new(SharedTopicMatcher::new());
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let matcher = matcher.clone();
+                thread::spawn(move || {
+                    for j in 0..25 {
+                        matcher.insert(format!("data/{}/{}", i, j), i * 100 + j);
+                        assert!(matcher.has_match(&format!("data/{}/{}", i, j)));
+                    }
+                })
+            })
+            .collect();
+
+        for thr in threads {
+            thr.join().unwrap();
+        }
+
+        assert_eq!(matcher.snapshot().iter().count(), 100);
+    }
+}
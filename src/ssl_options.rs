@@ -20,15 +20,26 @@
  *******************************************************************************/
 
 use std::{
-    ffi::CString,
-    io,
-    os::raw::{c_char, c_uchar, c_uint},
+    ffi::{CStr, CString},
+    fmt, io,
+    os::raw::{c_char, c_int, c_uchar, c_uint, c_void},
     path::{Path, PathBuf},
     pin::Pin,
-    ptr,
+    ptr, slice,
+    sync::{Arc, Mutex},
 };
 
-use crate::{errors::Result, ffi, from_c_bool, to_c_bool};
+#[cfg(feature = "ssl")]
+use std::time::Duration;
+
+#[cfg(feature = "ssl")]
+use crate::cert_provider::{CertReloader, CertificateSet, ExpiryWarning, ProviderState};
+use crate::{
+    errors::{Error, Result},
+    ffi, from_c_bool,
+    ssl_secret::{self, SecretFile},
+    to_c_bool,
+};
 
 // Implementation note:
 // The C library seems to require the SSL Options struct to provide valid
@@ -40,6 +51,45 @@ use crate::{errors::Result, ffi, from_c_bool, to_c_bool};
 // underlying C struct.
 //
 
+/// User callback type to get the identity and key for TLS-PSK.
+///
+/// This receives the identity hint from the server, if any, and returns
+/// the client's identity and the pre-shared key, or `None` to abort the
+/// handshake.
+pub type PskCallback = dyn Fn(Option<&str>) -> Option<(String, Vec<u8>)> + Send + Sync + 'static;
+
+// The PSK callback, kept behind a thin pointer to use as the C context.
+struct PskHandler {
+    cb: Box<PskCallback>,
+}
+
+impl fmt::Debug for PskHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PskHandler")
+    }
+}
+
+/// User callback type for errors reported by the SSL/TLS library.
+///
+/// This receives each line of the error text from OpenSSL, such as when
+/// the server's certificate fails verification during the handshake.
+pub type SslErrorCallback = dyn Fn(&str) + Send + Sync + 'static;
+
+// The handler for SSL/TLS errors.
+// This keeps the most recent error, to attach to a failed connection,
+// along with the user callback, if any.
+#[derive(Default)]
+struct SslErrorHandler {
+    cb: Option<Box<SslErrorCallback>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl fmt::Debug for SslErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SslErrorHandler")
+    }
+}
+
 /// The options for SSL socket connections to the broker.
 #[derive(Debug)]
 pub struct SslOptions {
@@ -65,6 +115,24 @@ struct SslOptionsData {
     ca_path: CString,
     // The list of ALPN protocols available to be negotiated.
     protos: Vec<c_uchar>,
+    // The callback for TLS-PSK, if any.
+    // This is shared by clones, so the C context pointer stays valid.
+    psk: Option<Arc<PskHandler>>,
+    // The handler for SSL/TLS errors, also shared by clones.
+    errors: Arc<SslErrorHandler>,
+    // The in-memory trust store, key store, and private key, if they
+    // were given as data rather than file names. These are shared by
+    // clones, and wiped when the last one is dropped.
+    trust_store_data: Option<Arc<SecretFile>>,
+    key_store_data: Option<Arc<SecretFile>>,
+    private_key_data: Option<Arc<SecretFile>>,
+    // The provider to reload the client certificates before each
+    // connection attempt, if any.
+    #[cfg(feature = "ssl")]
+    cert_provider: Option<Arc<ProviderState>>,
+    // The warning for a client certificate near expiry, if any.
+    #[cfg(feature = "ssl")]
+    expiry_warning: Option<Arc<ExpiryWarning>>,
 }
 
 /// The SSL/TLS versions that can be requested.
@@ -135,9 +203,96 @@ impl SslOptions {
         };
         copts.protos_len = data.protos.len() as c_uint;
 
+        match data.psk {
+            Some(ref psk) => {
+                copts.ssl_psk_cb = Some(Self::on_psk);
+                copts.ssl_psk_context = Arc::as_ptr(psk) as *mut c_void;
+            }
+            None => {
+                copts.ssl_psk_cb = None;
+                copts.ssl_psk_context = ptr::null_mut();
+            }
+        }
+
+        copts.ssl_error_cb = Some(Self::on_ssl_error);
+        copts.ssl_error_context = Arc::as_ptr(&data.errors) as *mut c_void;
+
         Self { copts, data }
     }
 
+    // Low-level callback from the C library with the text of an error
+    // from the SSL/TLS library.
+    unsafe extern "C" fn on_ssl_error(
+        msg: *const c_char,
+        len: usize,
+        context: *mut c_void,
+    ) -> c_int {
+        if context.is_null() || msg.is_null() {
+            return 1;
+        }
+        let handler = &*(context as *const SslErrorHandler);
+
+        let msg = slice::from_raw_parts(msg as *const u8, len);
+        let msg = String::from_utf8_lossy(msg);
+        let msg = msg.trim_end();
+        warn!("SSL/TLS error: {}", msg);
+
+        if let Some(ref cb) = handler.cb {
+            trace!("Invoking SSL error callback");
+            cb(msg);
+        }
+        *handler.last_error.lock().unwrap() = Some(msg.to_string());
+        1
+    }
+
+    // Low-level callback from the C library to get the TLS-PSK identity
+    // and key. Returns the length of the key, or zero on failure.
+    unsafe extern "C" fn on_psk(
+        hint: *const c_char,
+        identity: *mut c_char,
+        max_identity_len: c_uint,
+        psk: *mut c_uchar,
+        max_psk_len: c_uint,
+        context: *mut c_void,
+    ) -> c_uint {
+        if context.is_null() {
+            return 0;
+        }
+        let handler = &*(context as *const PskHandler);
+
+        let hint = if hint.is_null() {
+            None
+        }
+        else {
+            CStr::from_ptr(hint).to_str().ok()
+        };
+        trace!("Invoking PSK callback with hint: {:?}", hint);
+
+        let (id, mut key) = match (handler.cb)(hint) {
+            Some(creds) => creds,
+            None => return 0,
+        };
+
+        let id = id.as_bytes();
+        let n = if id.contains(&0)
+            || id.len() >= max_identity_len as usize
+            || key.len() > max_psk_len as usize
+        {
+            warn!("PSK identity or key is invalid or too long");
+            0
+        }
+        else {
+            ptr::copy_nonoverlapping(id.as_ptr(), identity as *mut u8, id.len());
+            *identity.add(id.len()) = 0;
+            ptr::copy_nonoverlapping(key.as_ptr(), psk, key.len());
+            key.len()
+        };
+
+        // Don't leave a copy of the key lying around on the heap
+        ssl_secret::wipe(&mut key);
+        n as c_uint
+    }
+
     /// Set the name of the PEM file containing the public
     /// digital certificates trusted by the client.
     pub fn trust_store(&self) -> PathBuf {
@@ -187,6 +342,65 @@ impl SslOptions {
     pub fn alpn_proto_vec(&self) -> &[c_uchar] {
         &self.data.protos
     }
+
+    /// Determines if any of the certificates or keys were given as
+    /// in-memory data, rather than as file names.
+    pub fn has_in_memory_credentials(&self) -> bool {
+        self.data.trust_store_data.is_some()
+            || self.data.key_store_data.is_some()
+            || self.data.private_key_data.is_some()
+    }
+
+    /// Determines if the client certificates come from a provider that
+    /// is called before each connection attempt.
+    #[cfg(feature = "ssl")]
+    pub fn has_certificate_provider(&self) -> bool {
+        self.data.cert_provider.is_some()
+    }
+
+    /// Gets the current client certificates from the provider, if any,
+    /// for the next connection attempt.
+    ///
+    /// The client does this automatically before each connection attempt,
+    /// so this is only needed to check the provider, or to pick up new
+    /// certificates early. If the provider fails, or gives certificates
+    /// that are invalid or expired, the previous ones are kept.
+    #[cfg(feature = "ssl")]
+    pub fn reload_certificates(&self) -> Result<()> {
+        match self.cert_reloader() {
+            Some(reloader) => reloader.reload(),
+            None => Ok(()),
+        }
+    }
+
+    // Gets the reloader for the client certificates, if they come from
+    // a provider.
+    #[cfg(feature = "ssl")]
+    pub(crate) fn cert_reloader(&self) -> Option<CertReloader> {
+        self.data.cert_provider.as_ref().map(|state| CertReloader {
+            state: state.clone(),
+            expiry: self.data.expiry_warning.clone(),
+        })
+    }
+
+    /// Determines if the options use a TLS pre-shared key (PSK).
+    pub fn has_psk(&self) -> bool {
+        self.data.psk.is_some()
+    }
+
+    /// Gets the most recent error reported by the SSL/TLS library for
+    /// connections using these options, if any.
+    ///
+    /// This is shared by all clones of the options, and is cleared before
+    /// each connection attempt with them, including automatic reconnects.
+    pub fn last_error(&self) -> Option<String> {
+        self.data.errors.last_error.lock().unwrap().clone()
+    }
+
+    // Clears the most recent SSL/TLS error.
+    pub(crate) fn clear_last_error(&self) {
+        *self.data.errors.last_error.lock().unwrap() = None;
+    }
 }
 
 impl Default for SslOptions {
@@ -236,6 +450,7 @@ impl SslOptionsBuilder {
                 .to_str()
                 .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Path string error"))?,
         )?;
+        self.data.trust_store_data = None;
         Ok(self)
     }
 
@@ -253,6 +468,11 @@ impl SslOptionsBuilder {
                 .to_str()
                 .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Path string error"))?,
         )?;
+        self.data.key_store_data = None;
+        #[cfg(feature = "ssl")]
+        {
+            self.data.cert_provider = None;
+        }
         Ok(self)
     }
 
@@ -268,6 +488,157 @@ impl SslOptionsBuilder {
                 .to_str()
                 .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Path string error"))?,
         )?;
+        self.data.private_key_data = None;
+        Ok(self)
+    }
+
+    // Puts PEM data into a secret file, returning it along with its path
+    // for the C library. The data is wiped from memory either way.
+    fn secret_pem(mut pem: Vec<u8>) -> Result<(Arc<SecretFile>, CString)> {
+        if !ssl_secret::is_pem(&pem) {
+            ssl_secret::wipe(&mut pem);
+            return Err(Error::General("Data is not in PEM format"));
+        }
+        let secret = Arc::new(SecretFile::new(pem)?);
+        let path = Self::secret_path(&secret)?;
+        Ok((secret, path))
+    }
+
+    // Gets the path to a secret file for the C library.
+    fn secret_path(secret: &SecretFile) -> Result<CString> {
+        Ok(CString::new(secret.path().to_str().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "Path string error")
+        })?)?)
+    }
+
+    /// Sets the public digital certificates trusted by the client, from
+    /// in-memory data in PEM format.
+    ///
+    /// This is an alternative to [`trust_store()`](Self::trust_store) for
+    /// when the certificates should not be read from, or written to, the
+    /// filesystem. See [`private_key_pem()`](Self::private_key_pem) for
+    /// how the data is kept.
+    pub fn trust_store_pem<B>(&mut self, pem: B) -> Result<&mut Self>
+    where
+        B: Into<Vec<u8>>,
+    {
+        let (secret, path) = Self::secret_pem(pem.into())?;
+        self.data.trust_store = path;
+        self.data.trust_store_data = Some(secret);
+        Ok(self)
+    }
+
+    /// Sets the public certificate chain of the client, from in-memory
+    /// data in PEM format.
+    ///
+    /// It may also include the client's private key. See
+    /// [`private_key_pem()`](Self::private_key_pem) for how the data is
+    /// kept.
+    pub fn key_store_pem<B>(&mut self, pem: B) -> Result<&mut Self>
+    where
+        B: Into<Vec<u8>>,
+    {
+        let (secret, path) = Self::secret_pem(pem.into())?;
+        self.data.key_store = path;
+        self.data.key_store_data = Some(secret);
+        #[cfg(feature = "ssl")]
+        {
+            self.data.cert_provider = None;
+        }
+        Ok(self)
+    }
+
+    /// Sets the client's private key, from in-memory data in PEM format.
+    ///
+    /// If the key is encrypted, the password should be set with
+    /// [`private_key_password()`](Self::private_key_password).
+    ///
+    /// The C library can only load certificates and keys from files, so
+    /// on Linux and Android the data is moved to an anonymous file that
+    /// only exists in memory. On other systems it goes into a file, in a
+    /// new temporary directory, that only the current user can read. In
+    /// either case the file is overwritten with zeros and removed when the
+    /// last copy of the options is dropped. When the data is passed in as
+    /// a `Vec<u8>`, that buffer is also wiped once it's been copied.
+    pub fn private_key_pem<B>(&mut self, pem: B) -> Result<&mut Self>
+    where
+        B: Into<Vec<u8>>,
+    {
+        let (secret, path) = Self::secret_pem(pem.into())?;
+        self.data.private_key = path;
+        self.data.private_key_data = Some(secret);
+        Ok(self)
+    }
+
+    /// Sets the public digital certificates trusted by the client, from
+    /// one or more in-memory certificates in DER format, back to back.
+    #[cfg(feature = "ssl")]
+    pub fn trust_store_der<B>(&mut self, der: B) -> Result<&mut Self>
+    where
+        B: Into<Vec<u8>>,
+    {
+        let mut der = der.into();
+        let pem = ssl_secret::der_certs_to_pem(&der);
+        ssl_secret::wipe(&mut der);
+        self.trust_store_pem(pem?)
+    }
+
+    /// Sets the public certificate chain of the client, from one or more
+    /// in-memory certificates in DER format, back to back, starting with
+    /// the client's certificate.
+    #[cfg(feature = "ssl")]
+    pub fn key_store_der<B>(&mut self, der: B) -> Result<&mut Self>
+    where
+        B: Into<Vec<u8>>,
+    {
+        let mut der = der.into();
+        let pem = ssl_secret::der_certs_to_pem(&der);
+        ssl_secret::wipe(&mut der);
+        self.key_store_pem(pem?)
+    }
+
+    /// Sets the client's private key, from in-memory data in DER format.
+    ///
+    /// The key must not be encrypted.
+    #[cfg(feature = "ssl")]
+    pub fn private_key_der<B>(&mut self, der: B) -> Result<&mut Self>
+    where
+        B: Into<Vec<u8>>,
+    {
+        let mut der = der.into();
+        let pem = ssl_secret::der_key_to_pem(&der);
+        ssl_secret::wipe(&mut der);
+        self.private_key_pem(pem?)
+    }
+
+    /// Sets the client's certificate chain and private key from an
+    /// in-memory PKCS#12 (.p12 or .pfx) bundle.
+    ///
+    /// The bundle is decrypted with the password, and the certificate,
+    /// along with any CA certificates in the bundle, becomes the key
+    /// store, while the key becomes the private key.
+    #[cfg(feature = "ssl")]
+    pub fn pkcs12<B>(&mut self, der: B, password: &str) -> Result<&mut Self>
+    where
+        B: Into<Vec<u8>>,
+    {
+        let mut der = der.into();
+        let res = ssl_secret::pkcs12_to_pem(&der, password);
+        ssl_secret::wipe(&mut der);
+
+        let (chain, key) = res?;
+        let (key_store, key_store_path) = Self::secret_pem(chain)?;
+        let (private_key, private_key_path) = Self::secret_pem(key)?;
+
+        self.data.key_store = key_store_path;
+        self.data.key_store_data = Some(key_store);
+        #[cfg(feature = "ssl")]
+        {
+            self.data.cert_provider = None;
+        }
+        self.data.private_key = private_key_path;
+        self.data.private_key_data = Some(private_key);
+        self.data.private_key_password = CString::default();
         Ok(self)
     }
 
@@ -347,6 +718,119 @@ impl SslOptionsBuilder {
         self
     }
 
+    /// Sets a callback for the errors reported by the SSL/TLS library.
+    ///
+    /// The callback receives the text of each error from OpenSSL, such as
+    /// for an expired or untrusted certificate, or a host name mismatch.
+    /// It is called from the library's network thread, while connecting.
+    ///
+    /// Whether or not there is a callback, the last error is attached to
+    /// the error from a failed connection attempt, as [`Error::Ssl`](crate::Error::Ssl).
+    pub fn error_callback<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.data.errors = Arc::new(SslErrorHandler {
+            cb: Some(Box::new(cb)),
+            last_error: Mutex::new(None),
+        });
+        self
+    }
+
+    /// Sets a callback to get the identity and pre-shared key for
+    /// connecting with TLS-PSK.
+    ///
+    /// The callback is called during the TLS handshake with the identity
+    /// hint from the server, if any. It should return the client's
+    /// identity and the key, or `None` to abort the handshake.
+    ///
+    /// The server must be configured for PSK, and the cipher suites
+    /// should be limited to the PSK ones, such as with
+    /// `enabled_cipher_suites("PSK")`. It's also a good idea to
+    /// [`disable_default_trust_store()`](Self::disable_default_trust_store)
+    /// so that servers with certificates are not accepted instead.
+    pub fn psk_callback<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(Option<&str>) -> Option<(String, Vec<u8>)> + Send + Sync + 'static,
+    {
+        self.data.psk = Some(Arc::new(PskHandler { cb: Box::new(cb) }));
+        self
+    }
+
+    /// Sets a fixed identity and pre-shared key for connecting with
+    /// TLS-PSK, regardless of the identity hint from the server.
+    ///
+    /// See [`psk_callback()`](Self::psk_callback) for the other options
+    /// that are needed for PSK.
+    pub fn psk<S, K>(&mut self, identity: S, key: K) -> &mut Self
+    where
+        S: Into<String>,
+        K: Into<Vec<u8>>,
+    {
+        let identity = identity.into();
+        let key = key.into();
+        self.psk_callback(move |_hint| Some((identity.clone(), key.clone())))
+    }
+
+    /// Sets a provider for the client's certificates and key, which is
+    /// called before each connection attempt, including automatic
+    /// reconnects.
+    ///
+    /// This lets a long-running client pick up certificates that are
+    /// rotated regularly without being rebuilt. The provider is called
+    /// once here, to get the initial certificates, and this fails if it
+    /// does or if they're invalid or expired. After that, if the provider
+    /// fails, or gives certificates that are invalid or expired, a warning
+    /// is logged and the previous ones are used.
+    ///
+    /// The certificates are kept in memory, as with
+    /// [`key_store_pem()`](Self::key_store_pem), and this replaces any
+    /// key store or private key set previously.
+    ///
+    /// To re-read files on disk, the provider can use
+    /// [`CertificateSet::from_files()`](crate::CertificateSet::from_files).
+    #[cfg(feature = "ssl")]
+    pub fn certificate_provider<F>(&mut self, provider: F) -> Result<&mut Self>
+    where
+        F: Fn() -> Result<CertificateSet> + Send + Sync + 'static,
+    {
+        let state = ProviderState::new(Box::new(provider))?;
+
+        self.data.key_store = Self::secret_path(state.key_store())?;
+        self.data.key_store_data = Some(state.key_store().clone());
+        self.data.private_key = CString::default();
+        self.data.private_key_data = None;
+
+        if let Some(trust_store) = state.trust_store() {
+            self.data.trust_store = Self::secret_path(trust_store)?;
+            self.data.trust_store_data = Some(trust_store.clone());
+        }
+
+        self.data.cert_provider = Some(Arc::new(state));
+        Ok(self)
+    }
+
+    /// Sets a callback to warn when the client certificate from the
+    /// [`certificate_provider()`](Self::certificate_provider) is close
+    /// to expiring.
+    ///
+    /// Before each connection attempt, the certificate that is about to
+    /// be presented to the server is checked, and if it expires within
+    /// the `threshold`, the callback is called with the time left. This
+    /// is zero if the certificate has already expired, in which case the
+    /// previous certificate is used instead.
+    #[cfg(feature = "ssl")]
+    pub fn expiry_warning<F>(&mut self, threshold: Duration, cb: F) -> &mut Self
+    where
+        F: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.data.expiry_warning = Some(Arc::new(ExpiryWarning {
+            threshold,
+            cb: Box::new(cb),
+        }));
+        self
+    }
+
     /// Create the SSL options from the builder.
     pub fn finalize(&self) -> SslOptions {
         SslOptions::from_data(self.copts, self.data.clone())
@@ -394,6 +878,107 @@ mod tests {
         // TODO: Check the other strings
     }
 
+    #[test]
+    fn test_psk() {
+        let opts = SslOptionsBuilder::new().finalize();
+        assert!(!opts.has_psk());
+        assert!(opts.copts.ssl_psk_cb.is_none());
+
+        let opts = SslOptionsBuilder::new()
+            .psk_callback(|hint| match hint {
+                Some("device-hint") => Some(("device-1".to_string(), vec![0xA5; 16])),
+                _ => None,
+            })
+            .finalize();
+        assert!(opts.has_psk());
+        assert!(opts.copts.ssl_psk_cb.is_some());
+        assert!(!opts.copts.ssl_psk_context.is_null());
+
+        // Clones share the callback, so the context stays valid
+        let opts2 = opts.clone();
+        assert_eq!(opts.copts.ssl_psk_context, opts2.copts.ssl_psk_context);
+
+        let mut id = [0 as c_char; 32];
+        let mut key = [0u8; 32];
+        let hint = CString::new("device-hint").unwrap();
+
+        let n = unsafe {
+            SslOptions::on_psk(
+                hint.as_ptr(),
+                id.as_mut_ptr(),
+                id.len() as c_uint,
+                key.as_mut_ptr(),
+                key.len() as c_uint,
+                opts.copts.ssl_psk_context,
+            )
+        };
+        assert_eq!(n, 16);
+        assert_eq!(&key[..16], &[0xA5; 16]);
+        let id = unsafe { CStr::from_ptr(id.as_ptr()) };
+        assert_eq!(id.to_str().unwrap(), "device-1");
+
+        // No hint, so the callback refuses
+        let mut id = [0 as c_char; 32];
+        let n = unsafe {
+            SslOptions::on_psk(
+                ptr::null(),
+                id.as_mut_ptr(),
+                id.len() as c_uint,
+                key.as_mut_ptr(),
+                key.len() as c_uint,
+                opts.copts.ssl_psk_context,
+            )
+        };
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn test_error_callback() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errs = errors.clone();
+
+        let opts = SslOptionsBuilder::new()
+            .error_callback(move |msg| errs.lock().unwrap().push(msg.to_string()))
+            .finalize();
+        assert!(opts.last_error().is_none());
+
+        let msg = "error:0A000086:SSL routines::certificate verify failed\n";
+        let rc = unsafe {
+            SslOptions::on_ssl_error(
+                msg.as_ptr() as *const c_char,
+                msg.len(),
+                opts.copts.ssl_error_context,
+            )
+        };
+        assert_eq!(rc, 1);
+
+        let msg = "error:0A000086:SSL routines::certificate verify failed";
+        assert_eq!(opts.last_error().as_deref(), Some(msg));
+        assert_eq!(*errors.lock().unwrap(), vec![msg.to_string()]);
+
+        opts.clear_last_error();
+        assert!(opts.last_error().is_none());
+    }
+
+    #[test]
+    fn test_psk_too_long() {
+        let opts = SslOptionsBuilder::new().psk("device-1", vec![1u8; 64]).finalize();
+
+        let mut id = [0 as c_char; 32];
+        let mut key = [0u8; 32];
+        let n = unsafe {
+            SslOptions::on_psk(
+                ptr::null(),
+                id.as_mut_ptr(),
+                id.len() as c_uint,
+                key.as_mut_ptr(),
+                key.len() as c_uint,
+                opts.copts.ssl_psk_context,
+            )
+        };
+        assert_eq!(n, 0);
+    }
+
     #[test]
     fn test_builder_trust_store() {
         const TRUST_STORE: &str = "some_file.crt";
@@ -408,6 +993,58 @@ mod tests {
         assert_eq!(TRUST_STORE, s.to_str().unwrap());
     }
 
+    #[test]
+    fn test_builder_pem() {
+        const CERT: &[u8] = b"-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n";
+
+        let opts = SslOptionsBuilder::new().finalize();
+        assert!(!opts.has_in_memory_credentials());
+
+        let opts = SslOptionsBuilder::new()
+            .trust_store_pem(CERT)
+            .unwrap()
+            .finalize();
+        assert!(opts.has_in_memory_credentials());
+        assert_eq!(std::fs::read(opts.trust_store()).unwrap(), CERT);
+
+        let s = unsafe { CStr::from_ptr(opts.copts.trustStore) };
+        assert_eq!(opts.trust_store().to_str().unwrap(), s.to_str().unwrap());
+
+        // Clones share the in-memory file
+        let opts2 = opts.clone();
+        drop(opts);
+        assert_eq!(std::fs::read(opts2.trust_store()).unwrap(), CERT);
+
+        // A file name replaces the in-memory data
+        let opts = SslOptionsBuilder::new()
+            .trust_store_pem(CERT)
+            .unwrap()
+            .trust_store("some_file.crt")
+            .unwrap()
+            .finalize();
+        assert!(!opts.has_in_memory_credentials());
+
+        assert!(SslOptionsBuilder::new()
+            .private_key_pem(vec![0x30, 0x82, 0x01])
+            .is_err());
+    }
+
+    #[cfg(feature = "ssl")]
+    #[test]
+    fn test_certificate_provider() {
+        let opts = SslOptionsBuilder::new().finalize();
+        assert!(!opts.has_certificate_provider());
+        assert!(opts.reload_certificates().is_ok());
+
+        // The provider is called up front, and must succeed
+        assert!(SslOptionsBuilder::new()
+            .certificate_provider(|| Err(Error::General("No certificates")))
+            .is_err());
+        assert!(SslOptionsBuilder::new()
+            .certificate_provider(|| Ok(CertificateSet::new(&b"not a cert"[..])))
+            .is_err());
+    }
+
     #[test]
     fn test_builder_key_store() {
         const KEY_STORE: &str = "some_file.crt";
@@ -113,6 +113,15 @@ impl StringCollection {
         self.data.coll.len()
     }
 
+    /// Gets a copy of the strings in the collection.
+    pub fn to_vec(&self) -> Vec<String> {
+        self.data
+            .coll
+            .iter()
+            .map(|s| s.to_string_lossy().into_owned())
+            .collect()
+    }
+
     /// Gets the collection as a pointer to const C string pointers.
     ///
     /// This returns a pointer that can be sent to a C API that takes a
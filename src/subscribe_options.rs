@@ -227,6 +227,75 @@ impl SubscribeOptionsBuilder {
     }
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// Serde support
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::*;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::result::Result;
+
+    /// The retain handling is serialized as its number, 0, 1, or 2.
+    impl Serialize for RetainHandling {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u8(*self as u8)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RetainHandling {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let val = u8::deserialize(deserializer)?;
+            RetainHandling::try_from(val as i32).map_err(de::Error::custom)
+        }
+    }
+
+    // The serialized form of the subscribe options.
+    #[derive(Serialize, Deserialize)]
+    #[serde(default)]
+    struct SubscribeOptionsDef {
+        no_local: bool,
+        retain_as_published: bool,
+        retain_handling: RetainHandling,
+    }
+
+    impl Default for SubscribeOptionsDef {
+        fn default() -> Self {
+            let opts = SubscribeOptions::default();
+            Self {
+                no_local: opts.no_local(),
+                retain_as_published: opts.retain_as_published(),
+                retain_handling: opts.retain_handling(),
+            }
+        }
+    }
+
+    /// The subscribe options are serialized as a struct with the fields
+    /// `no_local`, `retain_as_published`, and `retain_handling`. Any
+    /// that are missing take the default value when deserialized.
+    impl Serialize for SubscribeOptions {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            SubscribeOptionsDef {
+                no_local: self.no_local(),
+                retain_as_published: self.retain_as_published(),
+                retain_handling: self.retain_handling(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SubscribeOptions {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let def = SubscribeOptionsDef::deserialize(deserializer)?;
+            Ok(SubscribeOptions::new(
+                def.no_local,
+                def.retain_as_published,
+                def.retain_handling,
+            ))
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 //                              Unit Tests
 /////////////////////////////////////////////////////////////////////////////
@@ -378,4 +447,23 @@ mod tests {
         assert!(!opts.retain_as_published());
         assert_eq!(opts.retain_handling(), RetainHandling::DontSendRetained);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let opts = SubscribeOptions::new(true, false, RetainHandling::DontSendRetained);
+        let json = serde_json::to_string(&opts).unwrap();
+        assert_eq!(
+            json,
+            r#"{"no_local":true,"retain_as_published":false,"retain_handling":2}"#
+        );
+
+        let opts: SubscribeOptions =
+            serde_json::from_str(r#"{"retain_as_published":true}"#).unwrap();
+        assert!(!opts.no_local());
+        assert!(opts.retain_as_published());
+        assert_eq!(opts.retain_handling(), RetainHandling::SendRetainedOnSubscribe);
+
+        assert!(serde_json::from_str::<RetainHandling>("3").is_err());
+    }
 }
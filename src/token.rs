@@ -34,10 +34,12 @@
 use {
     crate::{
         async_client::AsyncClient,
-        errors::{Error, Result},
+        errors::{Error, Result, ServerError},
         ffi,
         message::Message,
-        server_response::{ServerRequest, ServerResponse},
+        properties::{PacketType, Properties, PropertyCode},
+        reason_code::ReasonCode,
+        server_response::{RequestResponse, ServerRequest, ServerResponse},
     },
     futures::{
         executor::block_on,
@@ -48,6 +50,7 @@ use {
     futures_timer::Delay,
     std::{
         ffi::CStr,
+        fmt,
         future::Future,
         os::raw::c_void,
         pin::Pin,
@@ -67,10 +70,13 @@ pub type SuccessCallback = dyn Fn(&AsyncClient, u16) + 'static;
 /// Callback for the token on failed completion
 pub type FailureCallback = dyn Fn(&AsyncClient, u16, i32) + 'static;
 
+// Callback inside the library for the result when the token completes
+pub(crate) type CompleteCallback = dyn FnOnce(&Result<ServerResponse>) + Send + 'static;
+
 /// The result data for the token.
 /// This contains the guarded elements in the token which are updated by
 /// the C library callback when the asynchronous operation completes.
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub(crate) struct TokenData {
     /// The MQTT Message ID
     msg_id: i16,
@@ -79,6 +85,8 @@ pub(crate) struct TokenData {
     res: Option<Result<ServerResponse>>,
     /// To wake the future on completion
     waker: Option<Waker>,
+    /// Called with the result on completion, before the future is woken
+    on_complete: Option<Box<CompleteCallback>>,
 }
 
 impl TokenData {
@@ -98,6 +106,23 @@ impl TokenData {
         }
     }
 
+    /// Creates a new token that is already signaled with the error.
+    pub fn from_err(err: Error) -> TokenData {
+        TokenData {
+            res: Some(Err(err)),
+            ..TokenData::default()
+        }
+    }
+
+    // Sets the result of the operation, passing it to the completion
+    // callback, if any.
+    fn complete(&mut self, res: Result<ServerResponse>) {
+        if let Some(cb) = self.on_complete.take() {
+            cb(&res);
+        }
+        self.res = Some(res);
+    }
+
     /// Creates a new token that is already signaled with an error.
     // TODO: Use this!
     #[allow(dead_code)]
@@ -109,6 +134,16 @@ impl TokenData {
     }
 }
 
+impl fmt::Debug for TokenData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenData")
+            .field("msg_id", &self.msg_id)
+            .field("res", &self.res)
+            .field("waker", &self.waker)
+            .finish()
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // TokenInner
 
@@ -189,6 +224,14 @@ impl TokenInner {
         })
     }
 
+    /// Creates a new, signaled token with the error.
+    pub fn from_err(err: Error) -> Arc<TokenInner> {
+        Arc::new(Self {
+            lock: Mutex::new(TokenData::from_err(err)),
+            ..Self::default()
+        })
+    }
+
     // Callback from the C library for when an MQTT v3.x operation succeeds.
     pub(crate) unsafe extern "C" fn on_success(
         context: *mut c_void,
@@ -281,6 +324,7 @@ impl TokenInner {
         let mut msgid = 0;
         let mut rc = -1;
         let mut err_msg = None;
+        let mut server_err = None;
 
         if let Some(rsp) = rsp.as_ref() {
             msgid = rsp.token as u16;
@@ -292,10 +336,30 @@ impl TokenInner {
                     err_msg = Some(cmsg.to_string());
                 }
             }
+
+            // Keep the server's reason code and diagnostics for a v5 failure
+            let reason_code = ReasonCode::from(rsp.reasonCode);
+            if reason_code.is_err() && reason_code != ReasonCode::MqttppV3Code {
+                let pkt_type = PacketType::from_control_packet(rsp.packet_type)
+                    .or_else(|| tok.inner.req.ack_packet_type());
+
+                if let Some(pkt_type) = pkt_type {
+                    let props = Properties::from_c_struct(&rsp.properties);
+                    server_err = Some(ServerError::from_properties(pkt_type, reason_code, &props));
+                }
+            }
         }
 
         debug!("Token w ID {} failed with code: {}", msgid, rc);
 
+        // A redirected connection continues with the same token
+        if let (Some(err), Some(cli)) = (&server_err, &tok.inner.cli) {
+            let is_connect = tok.inner.req == ServerRequest::Connect;
+            if is_connect && cli.follow_redirect(err, Some(tok.clone())) {
+                return;
+            }
+        }
+
         // Fire off any user callbacks
 
         if let Some(ref cli) = tok.inner.cli {
@@ -306,12 +370,15 @@ impl TokenInner {
                 );
                 cb(cli, msgid, rc);
             }
+            if tok.inner.req == ServerRequest::Connect {
+                cli.failover_failed();
+            }
         }
 
         // Signal completion of the token
 
         let mut data = tok.inner.lock.lock().unwrap();
-        data.res = Some(if rc == 0 {
+        data.complete(if rc == 0 {
             if let Some(rsp) = rsp.as_ref() {
                 Ok(ServerResponse::from_failure5(rsp))
             }
@@ -319,8 +386,11 @@ impl TokenInner {
                 Ok(ServerResponse::default())
             }
         }
+        else if let Some(err) = server_err {
+            Err(Error::Server(err))
+        }
         else {
-            Err(Error::from((rc, err_msg)))
+            Err(tok.inner.failure_error(rc, err_msg))
         });
 
         // If this is none, it means that no one is waiting on
@@ -330,6 +400,16 @@ impl TokenInner {
         }
     }
 
+    // Gets the error for a failed request, attaching the last SSL/TLS
+    // error, if any, when a connection fails.
+    fn failure_error(&self, rc: i32, err_msg: Option<String>) -> Error {
+        let err = Error::from((rc, err_msg));
+        match self.cli {
+            Some(ref cli) if self.req == ServerRequest::Connect => cli.with_ssl_error(err),
+            _ => err,
+        }
+    }
+
     // Callback function to update the token when the action completes.
     pub(crate) fn on_complete(
         &self,
@@ -359,13 +439,16 @@ impl TokenInner {
                 );
                 cb(cli, msgid, rc);
             }
+            if rc != 0 && self.req == ServerRequest::Connect {
+                cli.failover_failed();
+            }
         }
 
         // Signal completion of the token
 
         let mut data = self.lock.lock().unwrap();
         unsafe {
-            data.res = Some(if rc == 0 {
+            data.complete(if rc == 0 {
                 // Get the response from the server, if any.
                 debug!("Expecting server response for: {:?}", self.req);
                 let rsp = if let Some(rsp) = rsp.as_ref() {
@@ -379,12 +462,13 @@ impl TokenInner {
                 if let Some(rsp) = rsp.connect_response() {
                     if let Some(cli) = &self.cli {
                         cli.set_mqtt_version(rsp.mqtt_version);
+                        cli.failover_connected(&rsp.server_uri);
                     }
                 }
                 Ok(rsp)
             }
             else {
-                Err(Error::from((rc, err_msg)))
+                Err(self.failure_error(rc, err_msg))
             });
         }
 
@@ -405,6 +489,24 @@ impl TokenInner {
     ) {
         debug!("Token completed with code: {}", rc);
 
+        // Get the response from the server, if any, and check whether the
+        // server refused all the topics.
+        let rsp = if rc == 0 {
+            debug!("Expecting server response for: {:?}", self.req);
+            let rsp = match unsafe { rsp.as_ref() } {
+                Some(rsp) => unsafe { ServerResponse::from_success5(self.req, rsp) },
+                None => ServerResponse::default(),
+            };
+            debug!("Got response: {:?}", rsp);
+            Some(rsp)
+        }
+        else {
+            None
+        };
+
+        let topic_err = rsp.as_ref().and_then(|rsp| topic_failure(self.req, rsp));
+        let rc = if topic_err.is_some() { -1 } else { rc };
+
         // Fire off any user callbacks
 
         if let Some(ref cli) = self.cli {
@@ -429,29 +531,30 @@ impl TokenInner {
         // Signal completion of the token
 
         let mut data = self.lock.lock().unwrap();
-        unsafe {
-            data.res = Some(if rc == 0 {
-                // Get the response from the server, if any.
-                debug!("Expecting server response for: {:?}", self.req);
-                let rsp = if let Some(rsp) = rsp.as_ref() {
-                    ServerResponse::from_success5(self.req, rsp)
-                }
-                else {
-                    ServerResponse::default()
-                };
-                debug!("Got response: {:?}", rsp);
-
-                if let Some(rsp) = rsp.connect_response() {
+        data.complete(match (rsp, topic_err) {
+            (_, Some(err)) => Err(Error::Server(err)),
+            (Some(rsp), None) => {
+                if let Some(conn_rsp) = rsp.connect_response() {
                     if let Some(cli) = &self.cli {
-                        cli.set_mqtt_version(rsp.mqtt_version);
+                        cli.set_mqtt_version(conn_rsp.mqtt_version);
+                        cli.reset_redirects();
+                        cli.failover_connected(&conn_rsp.server_uri);
+
+                        let props = rsp.properties();
+                        if let Some(id) = props.get_string(PropertyCode::AssignedClientIdentifer) {
+                            cli.set_assigned_client_id(id);
+                        }
+                        cli.set_session_expiry_interval(
+                            props
+                                .get_int(PropertyCode::SessionExpiryInterval)
+                                .map(|secs| Duration::from_secs(secs as u32 as u64)),
+                        );
                     }
                 }
                 Ok(rsp)
             }
-            else {
-                Err(Error::from((rc, err_msg)))
-            });
-        }
+            (None, None) => Err(self.failure_error(rc, err_msg)),
+        });
 
         // If this is none, it means that no one is waiting on
         // the future yet, so we don't need to wake it.
@@ -461,6 +564,30 @@ impl TokenInner {
     }
 }
 
+// Gets the error for a subscribe or unsubscribe request when the server
+// refused every topic in the SUBACK or UNSUBACK. When it accepted some of
+// them, the request succeeds, and the response has the code for each.
+fn topic_failure(req: ServerRequest, rsp: &ServerResponse) -> Option<ServerError> {
+    let codes = match rsp.request_response() {
+        RequestResponse::Subscribe(code) | RequestResponse::Unsubscribe(code) => vec![*code],
+        RequestResponse::SubscribeMany(codes) | RequestResponse::UnsubscribeMany(codes) => {
+            codes.clone()
+        }
+        _ => return None,
+    };
+    let reason_codes: Vec<ReasonCode> = codes
+        .into_iter()
+        .map(|code| ReasonCode::from(code as ffi::MQTTReasonCodes))
+        .collect();
+    let reason_code = *reason_codes.first()?;
+    if !reason_codes.iter().all(|reason_code| reason_code.is_err()) {
+        return None;
+    }
+
+    let pkt_type = req.ack_packet_type()?;
+    Some(ServerError::from_properties(pkt_type, reason_code, rsp.properties()))
+}
+
 impl Default for TokenInner {
     fn default() -> Self {
         Self {
@@ -527,6 +654,27 @@ impl Token {
         }
     }
 
+    // Sets a function to get the result when the token completes, before
+    // anyone waiting on it is woken, or right away if it already has.
+    // This replaces any function set before.
+    pub(crate) fn when_complete<F>(&self, f: F)
+    where
+        F: FnOnce(&Result<ServerResponse>) + Send + 'static,
+    {
+        let mut data = self.inner.lock.lock().unwrap();
+        match data.res {
+            Some(ref res) => f(res),
+            None => data.on_complete = Some(Box::new(f)),
+        }
+    }
+
+    /// Creates a new Token signaled with the error.
+    pub fn from_err(err: Error) -> Self {
+        Self {
+            inner: TokenInner::from_err(err),
+        }
+    }
+
     /// Creates a new Token signaled with a "success" return code.
     pub fn from_success() -> Self {
         Self {
@@ -652,6 +800,14 @@ impl DeliveryToken {
         }
     }
 
+    /// Creates a new Token signaled with the error.
+    pub fn from_err(msg: Message, err: Error) -> DeliveryToken {
+        DeliveryToken {
+            inner: TokenInner::from_err(err),
+            msg,
+        }
+    }
+
     /// Sets the message ID for the token
     pub(crate) fn set_msgid(&self, msg_id: i16) {
         let mut data = self.inner.lock.lock().unwrap();
@@ -724,7 +880,7 @@ impl Future for DeliveryToken {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::thread;
+    use std::{mem, thread};
 
     #[test]
     fn test_new() {
@@ -792,6 +948,53 @@ mod tests {
         let _ = thr.join().unwrap();
     }
 
+    // Only a subscribe that the server refuses for every topic fails
+    #[test]
+    fn test_topic_failure() {
+        const GRANTED: ffi::MQTTReasonCodes = ffi::MQTTReasonCodes_MQTTREASONCODE_GRANTED_QOS_1;
+        const REFUSED: ffi::MQTTReasonCodes = ffi::MQTTReasonCodes_MQTTREASONCODE_NOT_AUTHORIZED;
+        let req = ServerRequest::SubscribeMany(2);
+
+        let suback = |mut codes: [ffi::MQTTReasonCodes; 2]| {
+            let mut data: ffi::MQTTAsync_successData5 = unsafe { mem::zeroed() };
+            data.alt.sub.reasonCodeCount = 2;
+            data.alt.sub.reasonCodes = codes.as_mut_ptr();
+            unsafe { ServerResponse::from_success5(req, &data) }
+        };
+
+        let rsp = suback([GRANTED, REFUSED]);
+        assert!(topic_failure(req, &rsp).is_none());
+        assert!(matches!(
+            rsp.request_response(),
+            RequestResponse::SubscribeMany(codes) if codes == &[GRANTED as i32, REFUSED as i32]
+        ));
+
+        let rsp = suback([REFUSED, REFUSED]);
+        let err = topic_failure(req, &rsp).unwrap();
+        assert_eq!(err.reason_code(), ReasonCode::NotAuthorized);
+    }
+
+    #[test]
+    fn test_when_complete() {
+        use std::sync::mpsc;
+
+        // Set before the token completes
+        let tok = Token::new();
+        let (tx, rx) = mpsc::channel();
+        let tx2 = tx.clone();
+        tok.when_complete(move |res| tx2.send(res.is_ok()).unwrap());
+        assert!(rx.try_recv().is_err());
+
+        tok.inner.on_complete(0, 0, None, ptr::null_mut());
+        assert_eq!(rx.try_recv(), Ok(true));
+        assert!(tok.wait().is_ok());
+
+        // Set after it completes
+        let tok = Token::from_error(ffi::MQTTASYNC_BAD_QOS);
+        tok.when_complete(move |res| tx.send(res.is_ok()).unwrap());
+        assert_eq!(rx.try_recv(), Ok(false));
+    }
+
     #[test]
     fn test_try_wait() {
         const ERR_CODE: i32 = ffi::MQTTASYNC_BAD_QOS;
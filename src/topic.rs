@@ -25,7 +25,7 @@
 use crate::{
     async_client::AsyncClient,
     client::Client,
-    errors::{Error, Result},
+    errors::{Error, Result, TopicError, TopicErrorKind},
     message::{Message, MessageBuilder},
     properties::{Properties, PropertyCode},
     subscribe_options::SubscribeOptions,
@@ -420,7 +420,7 @@ impl<'a> SyncTopic<'a> {
 ///     '#' - Matches all subsequent fields (must be last field in filter)
 ///
 /// It can be used to match against topics.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TopicFilter {
     /// If there are no wildcards, the filter is a straight topic string
     Topic(String),
@@ -494,6 +494,55 @@ impl TopicFilter {
     pub fn is_match(&self, topic: &str) -> bool {
         self.matches(topic)
     }
+
+    /// Gets the levels (fields) of the filter.
+    fn levels(&self) -> Vec<&str> {
+        match self {
+            Self::Topic(filter) => filter.split('/').collect(),
+            Self::Fields(fields) => fields.iter().map(|s| s.as_str()).collect(),
+        }
+    }
+
+    /// Determines if every topic matched by this filter is also matched
+    /// by the `other` one.
+    ///
+    /// For example, `a/+/c` is a subset of `a/#`. A filter is always a
+    /// subset of itself.
+    pub fn is_subset_of(&self, other: &TopicFilter) -> bool {
+        use crate::topic_matcher::filter_is_subset_iter;
+        filter_is_subset_iter(self.levels(), other.levels())
+    }
+
+    /// Determines if there are any topics that would match both this
+    /// filter and the `other` one.
+    pub fn overlaps(&self, other: &TopicFilter) -> bool {
+        use crate::topic_matcher::filters_overlap_iter;
+        filters_overlap_iter(self.levels(), other.levels())
+    }
+
+    /// Reduces a set of filters to the smallest set that matches the same
+    /// topics, by removing any filter that is covered by another one.
+    ///
+    /// Of any duplicate filters, only the first is kept. Otherwise the
+    /// filters remain in their original order.
+    ///
+    /// This is useful to avoid duplicate deliveries of a message from
+    /// overlapping subscriptions, particularly for MQTT v3.x, where
+    /// subscription identifiers aren't available.
+    pub fn minimize(filters: &[TopicFilter]) -> Vec<TopicFilter> {
+        filters
+            .iter()
+            .enumerate()
+            .filter(|(i, filter)| {
+                !filters.iter().enumerate().any(|(j, other)| {
+                    j != *i
+                        && filter.is_subset_of(other)
+                        && (j < *i || !other.is_subset_of(filter))
+                })
+            })
+            .map(|(_, filter)| filter.clone())
+            .collect()
+    }
 }
 
 impl fmt::Display for TopicFilter {
@@ -505,6 +554,144 @@ impl fmt::Display for TopicFilter {
     }
 }
 
+/// A topic filter is serialized as its string, and is checked when it is
+/// deserialized, as with `TopicFilter::new()`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TopicFilter {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TopicFilter {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error as _;
+        let filter = String::deserialize(deserializer)?;
+        TopicFilter::new(filter.as_str())
+            .map_err(|err| D::Error::custom(format!("invalid topic filter '{}': {}", filter, err)))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Validation
+/////////////////////////////////////////////////////////////////////////////
+
+/// The maximum length of a topic, in bytes.
+pub const MAX_TOPIC_LEN: usize = 65535;
+
+/// The prefix for shared subscriptions.
+const SHARED_PREFIX: &str = "$share/";
+
+// Determines if the character should not be used in a topic.
+// The spec disallows U+0000, and says that control characters and Unicode
+// non-characters SHOULD NOT be used.
+fn is_discouraged_topic_char(c: char) -> bool {
+    let n = c as u32;
+    matches!(n, 0x01..=0x1F | 0x7F..=0x9F | 0xFDD0..=0xFDEF) || (n & 0xFFFE) == 0xFFFE
+}
+
+/// Finds the first level of a topic name or filter that contains a
+/// character that the MQTT spec says should not be used: a control
+/// character or a Unicode non-character.
+///
+/// Since that is not a hard rule, the validation functions accept these
+/// characters. This lets the application decide what to do about them.
+pub fn discouraged_topic_level(topic: &str) -> Option<usize> {
+    topic
+        .split('/')
+        .position(|field| field.contains(is_discouraged_topic_char))
+}
+
+// Checks the rules common to topic names and filters.
+fn validate_topic_common(topic: &str) -> std::result::Result<(), TopicError> {
+    if topic.is_empty() {
+        return Err(TopicError::new(TopicErrorKind::Empty));
+    }
+    if topic.len() > MAX_TOPIC_LEN {
+        return Err(TopicError::new(TopicErrorKind::TooLong));
+    }
+
+    for (i, field) in topic.split('/').enumerate() {
+        if field.contains('\0') {
+            return Err(TopicError::at_level(TopicErrorKind::NulChar, i, field));
+        }
+    }
+    Ok(())
+}
+
+/// Validates a topic name, as used to publish a message, against the
+/// rules of the MQTT v3.1.1 and v5 specifications.
+///
+/// A topic name must be at least one character, and no more than 65535
+/// bytes long. It must not contain the wildcard characters, '+' or '#',
+/// or the NUL character. It should not contain control characters or
+/// Unicode non-characters, but since that is not a hard rule, these are
+/// not checked here. See [`discouraged_topic_level()`] for that.
+///
+/// On failure, the error indicates the rule that was broken and the
+/// offending level of the topic.
+pub fn validate_topic_name(topic: &str) -> std::result::Result<(), TopicError> {
+    validate_topic_common(topic)?;
+
+    for (i, field) in topic.split('/').enumerate() {
+        if field.contains(&['+', '#'][..]) {
+            return Err(TopicError::at_level(TopicErrorKind::WildcardInName, i, field));
+        }
+    }
+    Ok(())
+}
+
+/// Validates a topic filter, as used to subscribe, against the rules of
+/// the MQTT v3.1.1 and v5 specifications.
+///
+/// In addition to the rules for topic names, except that wildcards are
+/// allowed, a wildcard must take up an entire level of the filter, and a
+/// multi-level wildcard, '#', must be the last level. A shared
+/// subscription, `$share/{ShareName}/{filter}`, must have a non-empty
+/// share name without wildcards, followed by a valid filter.
+///
+/// On failure, the error indicates the rule that was broken and the
+/// offending level of the filter.
+pub fn validate_topic_filter(filter: &str) -> std::result::Result<(), TopicError> {
+    validate_topic_common(filter)?;
+
+    let mut fields = filter.split('/').enumerate().peekable();
+
+    if filter.starts_with(SHARED_PREFIX) {
+        fields.next();
+        match fields.next() {
+            Some((i, name)) if name.is_empty() || name.contains(&['+', '#'][..]) => {
+                return Err(TopicError::at_level(TopicErrorKind::BadShareName, i, name));
+            }
+            _ => (),
+        }
+        if filter.splitn(3, '/').nth(2).map_or(true, str::is_empty) {
+            return Err(TopicError::new(TopicErrorKind::MissingShareFilter));
+        }
+    }
+
+    while let Some((i, field)) = fields.next() {
+        match field {
+            "#" if fields.peek().is_some() => {
+                return Err(TopicError::at_level(TopicErrorKind::MisplacedMultiLevel, i, field));
+            }
+            "#" | "+" => (),
+            _ if field.contains(&['+', '#'][..]) => {
+                return Err(TopicError::at_level(TopicErrorKind::PartialWildcard, i, field));
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
 /////////////////////////////////////////////////////////////////////////////
 //                              Unit Tests
 /////////////////////////////////////////////////////////////////////////////
@@ -576,4 +763,133 @@ mod tests {
         assert!(!TopicFilter::new_unchecked("$BOB/bar").matches("$SYS/bar"));
         assert!(!TopicFilter::new_unchecked("+/bar").matches("$SYS/bar"));
     }
+
+    #[test]
+    fn test_validate_topic_name() {
+        assert!(validate_topic_name("some/topic").is_ok());
+        assert!(validate_topic_name("/").is_ok());
+        assert!(validate_topic_name("$SYS/broker").is_ok());
+        assert!(validate_topic_name("some//topic").is_ok());
+
+        let err = validate_topic_name("").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::Empty);
+
+        let err = validate_topic_name("some/+/topic").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::WildcardInName);
+        assert_eq!(err.level(), Some(1));
+        assert_eq!(err.field(), Some("+"));
+
+        let err = validate_topic_name("some/topic#").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::WildcardInName);
+        assert_eq!(err.level(), Some(1));
+
+        let err = validate_topic_name("some/to\0pic").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::NulChar);
+        assert_eq!(err.level(), Some(1));
+
+        // Control characters and non-characters are only discouraged
+        assert!(validate_topic_name("a/b\x07").is_ok());
+        assert!(validate_topic_name("a/\u{FFFF}").is_ok());
+        assert_eq!(discouraged_topic_level("a/b\x07"), Some(1));
+        assert_eq!(discouraged_topic_level("\u{FFFF}/b"), Some(0));
+        assert_eq!(discouraged_topic_level("a/b/c"), None);
+
+        let long = "x".repeat(MAX_TOPIC_LEN + 1);
+        let err = validate_topic_name(&long).unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::TooLong);
+        assert!(validate_topic_name(&long[1..]).is_ok());
+    }
+
+    #[test]
+    fn test_validate_topic_filter() {
+        assert!(validate_topic_filter("some/topic").is_ok());
+        assert!(validate_topic_filter("#").is_ok());
+        assert!(validate_topic_filter("+").is_ok());
+        assert!(validate_topic_filter("some/+/topic/#").is_ok());
+        assert!(validate_topic_filter("+/+/+").is_ok());
+        assert!(validate_topic_filter("$share/group/some/#").is_ok());
+
+        let err = validate_topic_filter("some/#/topic").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::MisplacedMultiLevel);
+        assert_eq!(err.level(), Some(1));
+
+        let err = validate_topic_filter("some/to+/topic").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::PartialWildcard);
+        assert_eq!(err.level(), Some(1));
+        assert_eq!(err.field(), Some("to+"));
+
+        let err = validate_topic_filter("some/topic#").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::PartialWildcard);
+
+        let err = validate_topic_filter("$share//some/topic").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::BadShareName);
+        assert_eq!(err.level(), Some(1));
+
+        let err = validate_topic_filter("$share/gr+oup/topic").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::BadShareName);
+
+        let err = validate_topic_filter("$share/group").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::MissingShareFilter);
+
+        let err = validate_topic_filter("$share/group/").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::MissingShareFilter);
+        assert!(validate_topic_filter("$share/group//").is_ok());
+
+        let err = validate_topic_filter("$share/group/a/#/b").unwrap_err();
+        assert_eq!(err.kind(), TopicErrorKind::MisplacedMultiLevel);
+        assert_eq!(err.level(), Some(3));
+    }
+
+    #[test]
+    fn test_topic_filter_subset() {
+        let a = TopicFilter::new("a/+/c").unwrap();
+        let b = TopicFilter::new("a/#").unwrap();
+        let c = TopicFilter::new("a/b/d").unwrap();
+
+        assert!(a.is_subset_of(&b));
+        assert!(!b.is_subset_of(&a));
+        assert!(c.is_subset_of(&b));
+        assert!(!c.is_subset_of(&a));
+
+        assert!(a.overlaps(&b));
+        assert!(!a.overlaps(&c));
+    }
+
+    #[test]
+    fn test_topic_filter_minimize() {
+        let filters: Vec<_> = ["a/+/c", "x/y", "a/#", "a/b/c", "x/y", "x/+"]
+            .iter()
+            .map(|f| TopicFilter::new(*f).unwrap())
+            .collect();
+
+        let min: Vec<_> = TopicFilter::minimize(&filters)
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(min, vec!["a/#", "x/+"]);
+
+        let filters: Vec<_> = ["x/y", "a/b", "x/y"]
+            .iter()
+            .map(|f| TopicFilter::new(*f).unwrap())
+            .collect();
+
+        let min: Vec<_> = TopicFilter::minimize(&filters)
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(min, vec!["x/y", "a/b"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_topic_filter_serde() {
+        let filter = TopicFilter::new("some/+/topic").unwrap();
+        let json = serde_json::to_string(&filter).unwrap();
+        assert_eq!(json, r#""some/+/topic""#);
+
+        let filter2: TopicFilter = serde_json::from_str(&json).unwrap();
+        assert_eq!(filter, filter2);
+
+        assert!(serde_json::from_str::<TopicFilter>(r#""some/#/topic""#).is_err());
+    }
 }
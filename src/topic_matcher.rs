@@ -69,6 +69,136 @@ where
     }
 }
 
+/// Checks if every topic matched by filter `a` is also matched by filter `b`.
+///
+/// For example, `a/+/c` is a subset of `a/#`, and `a/b` is a subset of
+/// `a/+`. Every filter is a subset of itself.
+pub fn filter_is_subset(a: &str, b: &str) -> bool {
+    filter_is_subset_iter(a.split('/'), b.split('/'))
+}
+
+/// Checks if every topic matched by the split filter `a` is also matched
+/// by the split filter `b`.
+pub fn filter_is_subset_iter<'a, 'b, A, B>(a: A, b: B) -> bool
+where
+    A: IntoIterator<Item = &'a str>,
+    B: IntoIterator<Item = &'b str>,
+{
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+
+    // Topics starting with '$' don't match a wildcard in the first field,
+    // so a '$' filter can't be covered by one starting with a wildcard.
+    if matches!(b.peek(), Some(&"#" | &"+")) && matches!(a.peek(), Some(x) if x.starts_with('$'))
+    {
+        return false;
+    }
+
+    loop {
+        match (a.next(), b.next()) {
+            // Exhausted both filters
+            (None, None) => return true,
+            // A multi-level wildcard covers everything below it,
+            // including the parent level
+            (_, Some("#")) => return true,
+            // Nothing else covers a multi-level wildcard
+            (Some("#"), _) => return false,
+            // A single-level wildcard covers any single level
+            (Some(_), Some("+")) => (),
+            // Only a single-level wildcard covers another
+            (Some("+"), _) => return false,
+            // Equal levels
+            (Some(a), Some(b)) if a == b => (),
+            // Otherwise, not a subset
+            _ => return false,
+        }
+    }
+}
+
+/// Checks if there are any topics that would match both filters.
+///
+/// For example, `a/+/c` and `a/b/#` overlap, as both match `a/b/c`,
+/// whereas `a/+/c` and `a/b/d` do not.
+pub fn filters_overlap(a: &str, b: &str) -> bool {
+    filters_overlap_iter(a.split('/'), b.split('/'))
+}
+
+/// Checks if there are any topics that would match both split filters.
+pub fn filters_overlap_iter<'a, 'b, A, B>(a: A, b: B) -> bool
+where
+    A: IntoIterator<Item = &'a str>,
+    B: IntoIterator<Item = &'b str>,
+{
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+
+    // A wildcard in the first field doesn't match a '$' topic.
+    let is_wild = |x: &&str| *x == "#" || *x == "+";
+    let is_sys = |x: &&str| x.starts_with('$');
+
+    match (a.peek(), b.peek()) {
+        (Some(x), Some(y)) if (is_wild(x) && is_sys(y)) || (is_sys(x) && is_wild(y)) => {
+            return false
+        }
+        _ => (),
+    }
+
+    loop {
+        match (a.next(), b.next()) {
+            // Exhausted both filters
+            (None, None) => return true,
+            // A multi-level wildcard matches everything below it,
+            // including the parent level
+            (Some("#"), _) | (_, Some("#")) => return true,
+            // A single-level wildcard matches any single level
+            (Some("+"), Some(_)) | (Some(_), Some("+")) => (),
+            // Equal levels
+            (Some(a), Some(b)) if a == b => (),
+            // Otherwise, no overlap
+            _ => return false,
+        }
+    }
+}
+
+/// Gets the parts of the topic that were captured by the wildcards in the
+/// filter, or `None` if the topic doesn't match the filter.
+///
+/// Each single-level wildcard, '+', captures one field of the topic. A
+/// multi-level wildcard, '#', captures the remainder of the topic as a
+/// single string, which may be empty if it matched the parent level.
+///
+/// ```
+/// use paho_mqtt::topic_matcher::topic_captures;
+///
+/// let caps = topic_captures("data/+/temp/#", "data/engine/temp/1/2");
+/// assert_eq!(caps, Some(vec!["engine", "1/2"]));
+/// ```
+pub fn topic_captures<'t>(filter: &str, topic: &'t str) -> Option<Vec<&'t str>> {
+    if !topic_matches(filter, topic) {
+        return None;
+    }
+
+    let mut caps = Vec::new();
+    let mut pos = 0;
+
+    for field in filter.split('/') {
+        match field {
+            "#" => {
+                caps.push(topic.get(pos..).unwrap_or(""));
+                break;
+            }
+            _ => {
+                let end = topic[pos..].find('/').map(|i| pos + i).unwrap_or(topic.len());
+                if field == "+" {
+                    caps.push(&topic[pos..end]);
+                }
+                pos = end + 1;
+            }
+        }
+    }
+    Some(caps)
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // Node (for TopicMatcher)
 
@@ -83,7 +213,7 @@ where
 /// map, we specifically have separate fields for them. That allows us to
 /// have separate mutable references for each, allowing for a mutable
 /// iterator.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Node<T> {
     /// The value that matches the topic at this node, if any.
     /// This includes a ached value of the filter.
@@ -208,7 +338,7 @@ type NodeIterMut<'a, T> = Box<dyn Iterator<Item = (&'a str, &'a mut T)> + 'a>;
 /// and solve the problem of multiple overlapped callbacks. See:
 /// <https://github.com/eclipse/paho.mqtt.rust/blob/master/examples/sync_consume_v5.rs>
 ///
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TopicMatcher<T> {
     root: Node<T>,
 }
@@ -320,6 +450,18 @@ impl<T> TopicMatcher<T> {
         MatchIter::new(&self.root, topic)
     }
 
+    /// Gets an iterator for all the matches to the specified topic, along
+    /// with the fields of the topic captured by the wildcards in each
+    /// matching filter.
+    ///
+    /// See [`topic_captures`] for the way the wildcards are captured.
+    pub fn matches_with_captures<'a, 'b>(&'a self, topic: &'b str) -> CaptureIter<'a, 'b, T> {
+        CaptureIter {
+            iter: self.matches(topic),
+            topic,
+        }
+    }
+
     /// Determines if the topic matches any of the filters in the collection.
     pub fn has_match(&self, topic: &str) -> bool {
         self.matches(topic).next().is_some()
@@ -431,7 +573,10 @@ impl<'a, 'b, T> Iterator for MatchIter<'a, 'b, T> {
 
             if let Some(child) = node.children.get("#") {
                 // By protocol definition, a '#' must be a terminating leaf.
-                return child.value.as_ref().map(|(k, v)| (k.as_str(), v));
+                // But it may be empty if its value was removed.
+                if let Some((k, v)) = child.value.as_ref() {
+                    return Some((k.as_str(), v));
+                }
             }
         }
 
@@ -439,6 +584,62 @@ impl<'a, 'b, T> Iterator for MatchIter<'a, 'b, T> {
     }
 }
 
+/// Iterator for the matches to a topic, along with the wildcard captures.
+///
+/// This is created by [`TopicMatcher::matches_with_captures`]. Each item
+/// is the filter, the value, and the fields of the topic that were
+/// captured by the wildcards in the filter.
+#[derive(Debug)]
+pub struct CaptureIter<'a, 'b, T> {
+    iter: MatchIter<'a, 'b, T>,
+    topic: &'b str,
+}
+
+impl<'a, 'b, T> Iterator for CaptureIter<'a, 'b, T> {
+    type Item = (&'a str, &'a T, Vec<&'b str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (filter, val) = self.iter.next()?;
+        let caps = topic_captures(filter, self.topic).unwrap_or_default();
+        Some((filter, val, caps))
+    }
+}
+
+/// A topic matcher is serialized as a map of the filters to their values.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for TopicMatcher<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+/// A topic matcher is deserialized from a map of filters to values.
+/// Each of the filters is checked with
+/// [`validate_topic_filter`](crate::topic::validate_topic_filter).
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for TopicMatcher<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error as _;
+
+        let map = std::collections::BTreeMap::<String, T>::deserialize(deserializer)?;
+        let mut matcher = TopicMatcher::new();
+
+        for (filter, val) in map {
+            if let Err(err) = crate::topic::validate_topic_filter(&filter) {
+                return Err(D::Error::custom(format!(
+                    "invalid topic filter '{}': {}",
+                    filter, err
+                )));
+            }
+            matcher.insert(filter, val);
+        }
+        Ok(matcher)
+    }
+}
+
 /// Macro to create a `TopicMatcher` collection.
 #[macro_export]
 macro_rules! topic_matcher {
@@ -593,4 +794,119 @@ mod tests {
         assert_eq!(tm.get("some/prod/topic"), Some(&42));
         assert_eq!(tm.get("some/test/bubba"), None);
     }
+
+    #[test]
+    fn test_topic_matcher_removed_multi_level() {
+        let mut tm = TopicMatcher::new();
+        tm.insert("some/+/topic", 1);
+        tm.insert("some/#", 2);
+
+        // The empty '#' node left by the remove doesn't end the search
+        assert_eq!(tm.remove("some/#"), Some(2));
+        let matches: Vec<_> = tm.matches("some/test/topic").collect();
+        assert_eq!(matches, vec![("some/+/topic", &1)]);
+    }
+
+    #[test]
+    fn test_topic_captures() {
+        assert_eq!(topic_captures("some/topic", "some/topic"), Some(vec![]));
+        assert_eq!(topic_captures("some/topic", "some/other"), None);
+        assert_eq!(topic_captures("some/+/topic", "some/thing/topic"), Some(vec!["thing"]));
+        assert_eq!(topic_captures("+/+", "a/b"), Some(vec!["a", "b"]));
+        assert_eq!(topic_captures("+/+", "a/"), Some(vec!["a", ""]));
+        assert_eq!(topic_captures("some/#", "some/a/b/c"), Some(vec!["a/b/c"]));
+        assert_eq!(topic_captures("some/#", "some"), Some(vec![""]));
+        assert_eq!(topic_captures("+/x/#", "a/x/b"), Some(vec!["a", "b"]));
+        assert_eq!(topic_captures("#", "a/b"), Some(vec!["a/b"]));
+        assert_eq!(topic_captures("+/x", "$SYS/x"), None);
+    }
+
+    #[test]
+    fn test_topic_matcher_captures() {
+        let tm = topic_matcher! {
+            "sensors/+/+/temp" => 1,
+            "sensors/#" => 2,
+            "sensors/site1/dev1/temp" => 3
+        };
+
+        let mut caps: Vec<_> = tm
+            .matches_with_captures("sensors/site1/dev1/temp")
+            .map(|(_, v, caps)| (*v, caps))
+            .collect();
+        caps.sort();
+
+        assert_eq!(
+            caps,
+            vec![
+                (1, vec!["site1", "dev1"]),
+                (2, vec!["site1/dev1/temp"]),
+                (3, vec![])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_is_subset() {
+        assert!(filter_is_subset("a/b/c", "a/b/c"));
+        assert!(filter_is_subset("a/+/c", "a/#"));
+        assert!(filter_is_subset("a/b/c", "a/+/c"));
+        assert!(filter_is_subset("a/+/c", "a/+/c"));
+        assert!(filter_is_subset("a", "a/#"));
+        assert!(filter_is_subset("a/#", "a/#"));
+        assert!(filter_is_subset("a/b/#", "a/#"));
+        assert!(filter_is_subset("a/b/#", "a/+/#"));
+        assert!(filter_is_subset("a/b/c", "#"));
+        assert!(filter_is_subset("+/b", "+/+"));
+
+        assert!(!filter_is_subset("a/#", "a/+/c"));
+        assert!(!filter_is_subset("a/+/c", "a/b/c"));
+        assert!(!filter_is_subset("a/#", "a/+"));
+        assert!(!filter_is_subset("a/b", "a/b/c"));
+        assert!(!filter_is_subset("a/b/c", "a/b"));
+        assert!(!filter_is_subset("a/#", "a"));
+        assert!(!filter_is_subset("$SYS/a", "#"));
+        assert!(!filter_is_subset("$SYS/a", "+/a"));
+        assert!(filter_is_subset("$SYS/a", "$SYS/#"));
+    }
+
+    #[test]
+    fn test_filters_overlap() {
+        assert!(filters_overlap("a/b/c", "a/b/c"));
+        assert!(filters_overlap("a/+/c", "a/b/#"));
+        assert!(filters_overlap("a/+", "+/b"));
+        assert!(filters_overlap("a/#", "a"));
+        assert!(filters_overlap("a", "a/#"));
+        assert!(filters_overlap("#", "a/b"));
+        assert!(filters_overlap("+/+", "a/b"));
+
+        assert!(!filters_overlap("a/+/c", "a/b/d"));
+        assert!(!filters_overlap("a/b", "a/b/c"));
+        assert!(!filters_overlap("a/+", "a/b/c"));
+        assert!(!filters_overlap("#", "$SYS/a"));
+        assert!(!filters_overlap("$SYS/#", "+/a"));
+        assert!(filters_overlap("$SYS/#", "$SYS/+"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_topic_matcher_serde() {
+        let tm = topic_matcher! {
+            "some/+/topic" => 1,
+            "some/#" => 2
+        };
+
+        let json = serde_json::to_string(&tm).unwrap();
+        let tm2: TopicMatcher<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(tm2.get("some/+/topic"), Some(&1));
+        assert_eq!(tm2.get("some/#"), Some(&2));
+        assert_eq!(tm2.iter().count(), 2);
+
+        let toml = toml::to_string(&tm).unwrap();
+        let tm3: TopicMatcher<i32> = toml::from_str(&toml).unwrap();
+        assert_eq!(tm3.get("some/+/topic"), Some(&1));
+        assert_eq!(tm3.get("some/#"), Some(&2));
+
+        let res = serde_json::from_str::<TopicMatcher<i32>>(r#"{"some/#/topic": 1}"#);
+        assert!(res.is_err());
+    }
 }
@@ -0,0 +1,487 @@
+// topic_template.rs
+//
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! Topic templates with named fields.
+//!
+//! A topic template is a topic with some of the fields replaced by named
+//! placeholders, like `sensors/{site}/{device}/temp`. The template can be
+//! used to create the topic filter to subscribe to the matching topics,
+//! to format concrete topics for publishing, and to extract the named
+//! fields from the topics of incoming messages.
+
+use crate::{
+    errors::{Error, Result},
+    topic::TopicFilter,
+};
+use std::{collections::HashMap, fmt};
+
+// A single level (field) in the template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Level {
+    /// A literal topic field
+    Literal(String),
+    /// A named placeholder for a single field
+    Named(String),
+    /// An anonymous single-level wildcard, '+'
+    SingleWild,
+    /// An anonymous multi-level wildcard, '#'
+    MultiWild,
+}
+
+/// A topic template with named placeholders for some of the fields.
+///
+/// Each placeholder takes up a whole level of the topic, and is written as
+/// a name in braces, like `{device}`. Anonymous wildcards, '+' and '#', can
+/// also be used in the template. They match the same as in a topic filter,
+/// but are not captured by name, and can't be used to format a topic.
+///
+/// ```
+/// use paho_mqtt::TopicTemplate;
+///
+/// let tmpl = TopicTemplate::new("sensors/{site}/{device}/temp").unwrap();
+/// assert_eq!(tmpl.filter(), "sensors/+/+/temp");
+///
+/// let topic = tmpl.format([("site", "north"), ("device", "t100")]).unwrap();
+/// assert_eq!(topic, "sensors/north/t100/temp");
+///
+/// let fields = tmpl.extract(&topic).unwrap();
+/// assert_eq!(fields["device"], "t100");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicTemplate {
+    levels: Vec<Level>,
+}
+
+impl TopicTemplate {
+    /// Creates a new topic template from the string.
+    ///
+    /// This fails if a placeholder doesn't take up a whole level, if a name
+    /// is used more than once, or if the template isn't otherwise a valid
+    /// topic filter.
+    pub fn new<S>(template: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let template = template.as_ref();
+        if template.is_empty() {
+            return Err(Error::BadTopicFilter);
+        }
+
+        let n = template.split('/').count();
+        let mut levels = Vec::with_capacity(n);
+
+        for (i, field) in template.split('/').enumerate() {
+            let level = match field {
+                "+" => Level::SingleWild,
+                "#" if i == n - 1 => Level::MultiWild,
+                _ if field.starts_with('{') && field.ends_with('}') && field.len() > 2 => {
+                    let name = &field[1..field.len() - 1];
+                    if name.contains(&['{', '}', '+', '#'][..])
+                        || levels.contains(&Level::Named(name.to_string()))
+                    {
+                        return Err(Error::BadTopicFilter);
+                    }
+                    Level::Named(name.to_string())
+                }
+                _ if field.contains(&['{', '}', '+', '#', '\0'][..]) => {
+                    return Err(Error::BadTopicFilter);
+                }
+                _ => Level::Literal(field.to_string()),
+            };
+            levels.push(level);
+        }
+
+        Ok(Self { levels })
+    }
+
+    /// Gets the names of the placeholders in the template, in order.
+    pub fn names(&self) -> Vec<&str> {
+        self.levels
+            .iter()
+            .filter_map(|level| match level {
+                Level::Named(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Gets the topic filter string that matches all the topics that fit
+    /// the template.
+    pub fn filter(&self) -> String {
+        self.levels
+            .iter()
+            .map(|level| match level {
+                Level::Literal(s) => s.as_str(),
+                Level::Named(_) | Level::SingleWild => "+",
+                Level::MultiWild => "#",
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Gets the topic filter that matches all the topics that fit the
+    /// template.
+    pub fn topic_filter(&self) -> TopicFilter {
+        TopicFilter::new_unchecked(self.filter())
+    }
+
+    /// Creates a concrete topic from the template by replacing each of the
+    /// placeholders with the value for that name.
+    ///
+    /// This fails if a value is missing for any of the names, if a value
+    /// is not a valid topic field, or if the template contains anonymous
+    /// wildcards.
+    pub fn format<I, K, V>(&self, values: I) -> Result<String>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let values: HashMap<String, String> = values
+            .into_iter()
+            .map(|(k, v)| (k.as_ref().to_string(), v.as_ref().to_string()))
+            .collect();
+
+        let mut fields = Vec::with_capacity(self.levels.len());
+
+        for level in &self.levels {
+            let field = match level {
+                Level::Literal(s) => s.as_str(),
+                Level::Named(name) => match values.get(name) {
+                    Some(val) if !val.contains(&['/', '+', '#', '\0'][..]) => {
+                        val.as_str()
+                    }
+                    Some(_) => {
+                        return Err(Error::GeneralString(format!(
+                            "Invalid value for topic template field '{}'",
+                            name
+                        )))
+                    }
+                    None => {
+                        return Err(Error::GeneralString(format!(
+                            "Missing value for topic template field '{}'",
+                            name
+                        )))
+                    }
+                },
+                _ => return Err(Error::General("Can't format a topic with wildcards")),
+            };
+            fields.push(field);
+        }
+
+        Ok(fields.join("/"))
+    }
+
+    /// Determines if the topic fits the template.
+    pub fn matches(&self, topic: &str) -> bool {
+        self.captures(topic).is_some()
+    }
+
+    /// Gets the named fields from the topic, as pairs of the placeholder
+    /// name and the field from the topic, in template order.
+    ///
+    /// Returns `None` if the topic doesn't fit the template.
+    pub fn captures<'a, 't>(&'a self, topic: &'t str) -> Option<Vec<(&'a str, &'t str)>> {
+        let mut fields = topic.split('/');
+        let mut caps = Vec::new();
+
+        // Topics starting with '$' don't match a wildcard in the first field.
+        if topic.starts_with('$') && !matches!(self.levels.first(), Some(Level::Literal(_))) {
+            return None;
+        }
+
+        for level in &self.levels {
+            match (level, fields.next()) {
+                (Level::MultiWild, _) => return Some(caps),
+                (Level::Literal(s), Some(field)) if s == field => (),
+                (Level::Named(name), Some(field)) => caps.push((name.as_str(), field)),
+                (Level::SingleWild, Some(_)) => (),
+                _ => return None,
+            }
+        }
+
+        match fields.next() {
+            None => Some(caps),
+            Some(_) => None,
+        }
+    }
+
+    /// Extracts the named fields from the topic into a map.
+    ///
+    /// Returns `None` if the topic doesn't fit the template.
+    pub fn extract(&self, topic: &str) -> Option<HashMap<String, String>> {
+        self.captures(topic).map(|caps| {
+            caps.into_iter()
+                .map(|(name, field)| (name.to_string(), field.to_string()))
+                .collect()
+        })
+    }
+
+    /// Extracts the named fields from the topic into a struct that can be
+    /// deserialized with serde.
+    ///
+    /// The placeholder names are used as the field names of the struct.
+    /// Numeric and boolean fields are parsed from the text of the topic.
+    ///
+    /// ```
+    /// # #[cfg(feature = "serde")] {
+    /// use paho_mqtt::TopicTemplate;
+    /// use serde_derive::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Sensor {
+    ///     site: String,
+    ///     device: u32,
+    /// }
+    ///
+    /// let tmpl = TopicTemplate::new("sensors/{site}/{device}/temp").unwrap();
+    /// let sensor: Sensor = tmpl.extract_as("sensors/north/42/temp").unwrap();
+    /// assert_eq!(sensor.device, 42);
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn extract_as<T>(&self, topic: &str) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        use serde::de::value::MapDeserializer;
+
+        let caps = self
+            .captures(topic)
+            .ok_or(Error::General("Topic doesn't match the template"))?;
+
+        let de = MapDeserializer::new(
+            caps.into_iter()
+                .map(|(name, field)| (name, de::FieldDeserializer(field))),
+        );
+        T::deserialize(de).map_err(|err| Error::GeneralString(err.to_string()))
+    }
+}
+
+impl fmt::Display for TopicTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<String> = self
+            .levels
+            .iter()
+            .map(|level| match level {
+                Level::Literal(s) => s.clone(),
+                Level::Named(name) => format!("{{{}}}", name),
+                Level::SingleWild => "+".to_string(),
+                Level::MultiWild => "#".to_string(),
+            })
+            .collect();
+        write!(f, "{}", fields.join("/"))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Serde support
+
+#[cfg(feature = "serde")]
+mod de {
+    use serde::de::{self, value::Error, Deserializer, IntoDeserializer, Visitor};
+    use serde::forward_to_deserialize_any;
+
+    /// A deserializer for a single topic field that parses the text into
+    /// numbers and booleans on request.
+    pub(super) struct FieldDeserializer<'de>(pub(super) &'de str);
+
+    macro_rules! deserialize_parsed {
+        ($($method:ident => $visit:ident),*) => {
+            $(
+                fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                    match self.0.parse() {
+                        Ok(val) => visitor.$visit(val),
+                        Err(_) => Err(de::Error::invalid_value(
+                            de::Unexpected::Str(self.0),
+                            &visitor,
+                        )),
+                    }
+                }
+            )*
+        };
+    }
+
+    impl<'de> Deserializer<'de> for FieldDeserializer<'de> {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.visit_borrowed_str(self.0)
+        }
+
+        deserialize_parsed! {
+            deserialize_bool => visit_bool,
+            deserialize_i8 => visit_i8,
+            deserialize_i16 => visit_i16,
+            deserialize_i32 => visit_i32,
+            deserialize_i64 => visit_i64,
+            deserialize_u8 => visit_u8,
+            deserialize_u16 => visit_u16,
+            deserialize_u32 => visit_u32,
+            deserialize_u64 => visit_u64,
+            deserialize_f32 => visit_f32,
+            deserialize_f64 => visit_f64
+        }
+
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.visit_some(self)
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            _variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_enum(self.0.into_deserializer())
+        }
+
+        forward_to_deserialize_any! {
+            i128 u128 char str string bytes byte_buf unit unit_struct seq
+            tuple tuple_struct map struct identifier ignored_any
+        }
+    }
+
+    impl<'de> IntoDeserializer<'de, Error> for FieldDeserializer<'de> {
+        type Deserializer = Self;
+
+        fn into_deserializer(self) -> Self {
+            self
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = "sensors/{site}/{device}/temp";
+
+    #[test]
+    fn test_new() {
+        let tmpl = TopicTemplate::new(TEMPLATE).unwrap();
+        assert_eq!(tmpl.names(), vec!["site", "device"]);
+        assert_eq!(tmpl.to_string(), TEMPLATE);
+
+        assert!(TopicTemplate::new("").is_err());
+        assert!(TopicTemplate::new("a/{x}/{x}").is_err());
+        assert!(TopicTemplate::new("a/b{x}/c").is_err());
+        assert!(TopicTemplate::new("a/{}/c").is_err());
+        assert!(TopicTemplate::new("a/#/c").is_err());
+        assert!(TopicTemplate::new("a/+/{x}/#").is_ok());
+    }
+
+    #[test]
+    fn test_filter() {
+        let tmpl = TopicTemplate::new(TEMPLATE).unwrap();
+        assert_eq!(tmpl.filter(), "sensors/+/+/temp");
+        assert!(tmpl.topic_filter().is_match("sensors/a/b/temp"));
+
+        let tmpl = TopicTemplate::new("data/{id}/#").unwrap();
+        assert_eq!(tmpl.filter(), "data/+/#");
+    }
+
+    #[test]
+    fn test_format() {
+        let tmpl = TopicTemplate::new(TEMPLATE).unwrap();
+
+        let topic = tmpl.format([("device", "d1"), ("site", "s1")]).unwrap();
+        assert_eq!(topic, "sensors/s1/d1/temp");
+
+        let mut vals = HashMap::new();
+        vals.insert("site".to_string(), "s2".to_string());
+        vals.insert("device".to_string(), "d2".to_string());
+        assert_eq!(tmpl.format(&vals).unwrap(), "sensors/s2/d2/temp");
+
+        assert!(tmpl.format([("site", "s1")]).is_err());
+        assert!(tmpl.format([("site", "s/1"), ("device", "d1")]).is_err());
+        assert!(tmpl.format([("site", "+"), ("device", "d1")]).is_err());
+
+        let tmpl = TopicTemplate::new("data/{id}/#").unwrap();
+        assert!(tmpl.format([("id", "1")]).is_err());
+    }
+
+    #[test]
+    fn test_extract() {
+        let tmpl = TopicTemplate::new(TEMPLATE).unwrap();
+
+        let caps = tmpl.captures("sensors/s1/d1/temp").unwrap();
+        assert_eq!(caps, vec![("site", "s1"), ("device", "d1")]);
+
+        let map = tmpl.extract("sensors/s1/d1/temp").unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["site"], "s1");
+        assert_eq!(map["device"], "d1");
+
+        assert!(tmpl.extract("sensors/s1/d1/humidity").is_none());
+        assert!(tmpl.extract("sensors/s1/d1/temp/x").is_none());
+        assert!(tmpl.extract("sensors/s1/temp").is_none());
+    }
+
+    #[test]
+    fn test_extract_wildcards() {
+        let tmpl = TopicTemplate::new("{id}/+/data/#").unwrap();
+        assert!(tmpl.matches("a/b/data"));
+        assert!(tmpl.matches("a/b/data/c/d"));
+        assert_eq!(tmpl.captures("a/b/data/c").unwrap(), vec![("id", "a")]);
+        assert!(!tmpl.matches("$SYS/b/data"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_extract_as() {
+        use serde_derive::Deserialize;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Sensor {
+            site: String,
+            device: u32,
+            active: Option<bool>,
+        }
+
+        let tmpl = TopicTemplate::new("sensors/{site}/{device}/{active}").unwrap();
+
+        let sensor: Sensor = tmpl.extract_as("sensors/north/42/true").unwrap();
+        assert_eq!(
+            sensor,
+            Sensor {
+                site: "north".to_string(),
+                device: 42,
+                active: Some(true)
+            }
+        );
+
+        assert!(tmpl.extract_as::<Sensor>("sensors/north/x/true").is_err());
+        assert!(tmpl.extract_as::<Sensor>("other/north/42/true").is_err());
+    }
+}
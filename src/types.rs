@@ -135,3 +135,65 @@ impl From<i32> for QoS {
         Self::try_from(qos as u8).unwrap_or_default()
     }
 }
+
+/////////////////////////////////////////////////////////////////////////////
+// Serde support
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::*;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::result::Result;
+
+    /// The MQTT version is serialized as its protocol number.
+    impl Serialize for MqttVersion {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u32(*self as u32)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for MqttVersion {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            match u32::deserialize(deserializer)? {
+                ver @ (MQTT_VERSION_DEFAULT | MQTT_VERSION_3_1 | MQTT_VERSION_3_1_1
+                | MQTT_VERSION_5) => Ok(MqttVersion::from(ver)),
+                ver => Err(de::Error::custom(format!("invalid MQTT version: {}", ver))),
+            }
+        }
+    }
+
+    /// The QoS is serialized as its number, 0, 1, or 2.
+    impl Serialize for QoS {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u8(*self as u8)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for QoS {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let qos = u8::deserialize(deserializer)?;
+            QoS::try_from(qos).map_err(|_| de::Error::custom(format!("invalid QoS: {}", qos)))
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use super::*;
+
+        assert_eq!(serde_json::to_string(&QoS::ExactlyOnce).unwrap(), "2");
+        assert_eq!(serde_json::from_str::<QoS>("0").unwrap(), QoS::AtMostOnce);
+        assert!(serde_json::from_str::<QoS>("3").is_err());
+
+        assert_eq!(serde_json::to_string(&MqttVersion::V5).unwrap(), "5");
+        assert_eq!(serde_json::from_str::<MqttVersion>("4").unwrap(), MqttVersion::V3_1_1);
+        assert!(serde_json::from_str::<MqttVersion>("6").is_err());
+    }
+}
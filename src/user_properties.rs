@@ -0,0 +1,305 @@
+// user_properties.rs
+//
+// A multimap of MQTT v5 user properties.
+// This file is part of the Eclipse Paho MQTT Rust Client library.
+//
+
+/*******************************************************************************
+ * Copyright (c) 2026 agent <agent@local>
+ *
+ * All rights reserved. This program and the accompanying materials
+ * are made available under the terms of the Eclipse Public License v2.0
+ * and Eclipse Distribution License v1.0 which accompany this distribution.
+ *
+ * The Eclipse Public License is available at
+ *    http://www.eclipse.org/legal/epl-v20.html
+ * and the Eclipse Distribution License is available at
+ *   http://www.eclipse.org/org/documents/edl-v10.php.
+ *
+ * Contributors:
+ *    agent - initial implementation and documentation
+ *******************************************************************************/
+
+//! A collection of MQTT v5 user properties.
+//!
+//! User properties are name/value string pairs that can be attached to
+//! most MQTT v5 packets. The same name can appear any number of times, and
+//! the order of the pairs is significant, so they are kept here as an
+//! ordered multimap.
+
+use crate::properties::{Properties, PropertyCode};
+use std::{fmt, slice, vec};
+
+/// An ordered collection of MQTT v5 user properties, in which a name can
+/// map to more than one value.
+///
+/// This keeps the pairs in the order in which they were inserted, which is
+/// the order in which they are sent to, or were received from, the server.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UserProperties {
+    pairs: Vec<(String, String)>,
+}
+
+impl UserProperties {
+    /// Creates a new, empty, collection of user properties.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the user properties from a collection of MQTT v5 properties.
+    pub fn from_properties(props: &Properties) -> Self {
+        Self {
+            pairs: props.user_iter().collect(),
+        }
+    }
+
+    /// Gets the total number of name/value pairs in the collection.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Determines if the collection is empty.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Determines if there are any values for the name.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == key)
+    }
+
+    /// Gets the first value for the name, if any.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Gets an iterator over all the values for the name, in order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Adds a name/value pair to the end of the collection.
+    ///
+    /// This does not replace any existing values for the name.
+    pub fn insert<K, V>(&mut self, key: K, val: V)
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.pairs.push((key.into(), val.into()));
+    }
+
+    /// Removes all the values for the name, returning them in order.
+    pub fn remove(&mut self, key: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+        let mut pairs = Vec::with_capacity(self.pairs.len());
+
+        for (k, v) in self.pairs.drain(..) {
+            if k == key {
+                removed.push(v);
+            }
+            else {
+                pairs.push((k, v));
+            }
+        }
+        self.pairs = pairs;
+        removed
+    }
+
+    /// Removes all the pairs from the collection.
+    pub fn clear(&mut self) {
+        self.pairs.clear();
+    }
+
+    /// Gets an iterator over the name/value pairs, in order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.pairs.iter(),
+        }
+    }
+
+    /// Replaces the user properties in the collection of MQTT v5
+    /// properties with the ones in this collection, leaving any other
+    /// properties alone.
+    pub fn replace_in(&self, props: &mut Properties) {
+        props.remove_all(PropertyCode::UserProperty);
+        self.add_to(props);
+    }
+
+    /// Appends the user properties to a collection of MQTT v5 properties.
+    pub fn add_to(&self, props: &mut Properties) {
+        for (k, v) in &self.pairs {
+            let _ = props.push_string_pair(PropertyCode::UserProperty, k, v);
+        }
+    }
+}
+
+impl From<&Properties> for UserProperties {
+    fn from(props: &Properties) -> Self {
+        Self::from_properties(props)
+    }
+}
+
+impl From<UserProperties> for Properties {
+    fn from(user_props: UserProperties) -> Self {
+        let mut props = Properties::new();
+        user_props.add_to(&mut props);
+        props
+    }
+}
+
+impl From<Vec<(String, String)>> for UserProperties {
+    fn from(pairs: Vec<(String, String)>) -> Self {
+        Self { pairs }
+    }
+}
+
+impl From<UserProperties> for Vec<(String, String)> {
+    fn from(user_props: UserProperties) -> Self {
+        user_props.pairs
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for UserProperties
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut user_props = Self::new();
+        user_props.extend(iter);
+        user_props
+    }
+}
+
+impl<K, V> Extend<(K, V)> for UserProperties
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl IntoIterator for UserProperties {
+    type Item = (String, String);
+    type IntoIter = vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pairs.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a UserProperties {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Display for UserProperties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (k, v)) in self.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}: {:?}", k, v)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// An iterator over the name/value pairs of a [`UserProperties`]
+/// collection.
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, (String, String)>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+/////////////////////////////////////////////////////////////////////////////
+//                              Unit Tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multimap() {
+        let mut user_props = UserProperties::new();
+        assert!(user_props.is_empty());
+
+        user_props.insert("trace", "1");
+        user_props.insert("tenant", "acme");
+        user_props.insert("trace", "2");
+
+        assert_eq!(user_props.len(), 3);
+        assert!(user_props.contains_key("tenant"));
+        assert!(!user_props.contains_key("missing"));
+        assert_eq!(user_props.get("trace"), Some("1"));
+        assert_eq!(user_props.get_all("trace").collect::<Vec<_>>(), ["1", "2"]);
+        assert_eq!(user_props.get("missing"), None);
+
+        assert_eq!(
+            user_props.iter().collect::<Vec<_>>(),
+            [("trace", "1"), ("tenant", "acme"), ("trace", "2")]
+        );
+        assert_eq!(
+            user_props.to_string(),
+            r#"{"trace": "1", "tenant": "acme", "trace": "2"}"#
+        );
+
+        assert_eq!(user_props.remove("trace"), ["1", "2"]);
+        assert_eq!(user_props.len(), 1);
+        assert!(user_props.remove("trace").is_empty());
+        assert_eq!(user_props.get("tenant"), Some("acme"));
+    }
+
+    #[test]
+    fn test_properties_conversion() {
+        let user_props: UserProperties = [("a", "1"), ("b", "2"), ("a", "3")]
+            .into_iter()
+            .collect();
+
+        let mut props = Properties::from(user_props.clone());
+        assert_eq!(props.len(), 3);
+        assert_eq!(UserProperties::from(&props), user_props);
+
+        props
+            .push_int(PropertyCode::MessageExpiryInterval, 30)
+            .unwrap();
+
+        let user_props: UserProperties = [("c", "4")].into_iter().collect();
+        user_props.replace_in(&mut props);
+
+        assert_eq!(props.len(), 2);
+        assert_eq!(props.get_int(PropertyCode::MessageExpiryInterval), Some(30));
+        assert_eq!(UserProperties::from(&props), user_props);
+    }
+}
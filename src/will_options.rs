@@ -22,12 +22,13 @@
 //! Last Will and Testament (LWT) options for the Paho MQTT Rust client library.
 //!
 
-use std::{borrow::Cow, os::raw::c_void, pin::Pin, ptr};
+use std::{borrow::Cow, os::raw::c_void, pin::Pin, ptr, time::Duration};
 
 use crate::{
+    errors::Result,
     ffi,
     message::{Message, MessageData},
-    properties::Properties,
+    properties::{PacketType, Properties, PropertyCode},
 };
 
 /// The options for the Last Will and Testament (LWT).
@@ -119,11 +120,8 @@ impl WillOptions {
         };
         // Note: For some reason, properties aren't in the will options
         //   They're in the connect options
-        Self {
-            copts,
-            data,
-            props: Properties::default(),
-        }
+        let props = data.props.clone();
+        Self { copts, data, props }
     }
 
     /// Gets the topic string for the LWT
@@ -156,6 +154,33 @@ impl WillOptions {
     pub fn properties(&self) -> &Properties {
         &self.props
     }
+
+    /// Gets the MQTT v5 will delay interval, if any.
+    pub fn will_delay_interval(&self) -> Option<Duration> {
+        self.props
+            .get_int(PropertyCode::WillDelayInterval)
+            .map(|secs| Duration::from_secs(secs as u32 as u64))
+    }
+
+    /// Sets the MQTT v5 will delay interval.
+    ///
+    /// This is how long the server waits after the connection is lost
+    /// before publishing the will message. If the client reconnects within
+    /// that time, or the session ends first, the message is not sent.
+    /// The interval has a resolution of one second, and saturates at
+    /// `u32::MAX` seconds.
+    pub fn set_will_delay_interval(&mut self, delay: Duration) {
+        let secs = u32::try_from(delay.as_secs()).unwrap_or(u32::MAX);
+        self.props.remove_all(PropertyCode::WillDelayInterval);
+        let _ = self.props.push_u32(PropertyCode::WillDelayInterval, secs);
+        self.data.props = self.props.clone();
+    }
+
+    /// Checks that the properties are allowed as Will properties in the
+    /// CONNECT packet.
+    pub fn validate_properties(&self) -> Result<()> {
+        self.props.validate(PacketType::Will)
+    }
 }
 
 impl Default for WillOptions {
@@ -363,4 +388,40 @@ mod tests {
         assert_eq!(QOS, opts.copts.qos);
         assert!(opts.copts.retained != 0);
     }
+
+    #[test]
+    fn test_validate_properties() {
+        let lwt = MessageBuilder::new()
+            .topic(TOPIC)
+            .payload(PAYLOAD)
+            .message_expiry_interval(std::time::Duration::from_secs(60))
+            .finalize();
+        let opts = WillOptions::from(lwt);
+        assert_eq!(opts.properties().len(), 1);
+        assert!(opts.validate_properties().is_ok());
+
+        let lwt = MessageBuilder::new()
+            .topic(TOPIC)
+            .payload(PAYLOAD)
+            .topic_alias(1)
+            .finalize();
+        let opts = WillOptions::from(lwt);
+        assert!(opts.validate_properties().is_err());
+    }
+
+    #[test]
+    fn test_will_delay_interval() {
+        let mut opts = WillOptions::new(TOPIC, PAYLOAD, QOS);
+        assert_eq!(opts.will_delay_interval(), None);
+
+        opts.set_will_delay_interval(Duration::from_secs(10));
+        opts.set_will_delay_interval(Duration::from_secs(30));
+        assert_eq!(opts.will_delay_interval(), Some(Duration::from_secs(30)));
+        assert_eq!(opts.properties().len(), 1);
+        assert!(opts.validate_properties().is_ok());
+
+        let opts = opts.clone();
+        assert_eq!(opts.will_delay_interval(), Some(Duration::from_secs(30)));
+        assert_eq!(opts.topic(), TOPIC);
+    }
 }